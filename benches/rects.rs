@@ -0,0 +1,81 @@
+// Rect painter frames under criterion: `cargo bench --bench rects`.
+//
+// Each sample is timed with `bench_frame` or `time_draws`, which wait for
+// the GPU, so only finished frames are counted rather than how fast
+// commands are queued.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use oreb::{
+    bench::{self, Target},
+    graph::{Node, RenderGraph},
+    rect::RectInstance,
+    Context,
+};
+
+fn frames(c: &mut Criterion) {
+    let rc = async_std::task::block_on(Context::headless(1024, 1024))
+        .expect("Failed to make a headless context");
+    let mut painter = rc.make_rect_painter();
+    painter.set_uniforms(&rc, &Default::default());
+    let target = Target::new(&rc);
+
+    let mut group = c.benchmark_group("rect frames");
+    for count in [1_000, 10_000, 100_000] {
+        let rects = bench::grid_rects(count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_function(BenchmarkId::from_parameter(count), |b| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| bench::bench_frame(&rc, &mut painter, &rects, &target).unwrap())
+                    .sum()
+            })
+        });
+    }
+    group.finish();
+}
+
+// Rounded rects as one instanced draw, against a draw call each: one
+// single-rect painter per rect, all in one render pass.
+fn instanced_vs_separate(c: &mut Criterion) {
+    const COUNT: usize = 2_000;
+    let rc = async_std::task::block_on(Context::headless(1024, 1024))
+        .expect("Failed to make a headless context");
+    let target = Target::new(&rc);
+    let instances: Vec<RectInstance> = bench::grid_rects(COUNT)
+        .iter()
+        .map(|rect| RectInstance {
+            orientation_radians: rect.orientation_radians,
+            ..RectInstance::new(rect.center, rect.size, 4.0)
+        })
+        .collect();
+    let painter = |instances: &[RectInstance]| {
+        let mut painter = rc.make_rect_instance_painter();
+        painter.set_uniforms(&rc, &Default::default());
+        painter.set_instances(&rc, instances).unwrap();
+        painter
+    };
+    let batched = painter(&instances);
+    let separate: Vec<_> = instances.chunks(1).map(painter).collect();
+    let mut graph = RenderGraph::new();
+
+    let mut group = c.benchmark_group("instanced vs separate draws");
+    group.throughput(Throughput::Elements(COUNT as u64));
+    let mut bench = |name, nodes: Vec<Node>| {
+        group.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| bench::time_draws(&rc, || graph.run(&rc, &nodes, target.view())))
+                    .sum()
+            })
+        });
+    };
+    bench("one instanced draw", vec![Node::InstancedRects(&batched)]);
+    bench(
+        "a draw per rect",
+        separate.iter().map(Node::InstancedRects).collect(),
+    );
+    group.finish();
+}
+
+criterion_group!(benches, frames, instanced_vs_separate);
+criterion_main!(benches);
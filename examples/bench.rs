@@ -0,0 +1,42 @@
+// Measures rect painter throughput offscreen, without a window.
+//
+// benches/rects.rs times the same `bench_frame` call under criterion.
+
+use dotenv::dotenv;
+use oreb::{
+    bench::{self, Target},
+    Context,
+};
+
+const WARMUP_FRAMES: usize = 5;
+const FRAMES: usize = 50;
+
+#[async_std::main]
+async fn main() {
+    dotenv().ok();
+    env_logger::init();
+
+    let rc = Context::headless(1024, 1024)
+        .await
+        .expect("Failed to make a headless context");
+    let mut painter = rc.make_rect_painter();
+    painter.set_uniforms(&rc, &Default::default());
+    let target = Target::new(&rc);
+
+    for count in [1_000, 10_000, 100_000] {
+        let rects = bench::grid_rects(count);
+        for _ in 0..WARMUP_FRAMES {
+            bench::bench_frame(&rc, &mut painter, &rects, &target).unwrap();
+        }
+        let mut times: Vec<_> = (0..FRAMES)
+            .map(|_| bench::bench_frame(&rc, &mut painter, &rects, &target).unwrap())
+            .collect();
+        times.sort();
+        let median = times[FRAMES / 2];
+        println!(
+            "{count:>7} rects: median {:>8.3} ms, {:>6.1} M rects/s",
+            median.as_secs_f64() * 1e3,
+            count as f64 / median.as_secs_f64() / 1e6
+        );
+    }
+}
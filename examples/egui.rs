@@ -0,0 +1,79 @@
+use std::f32::consts::PI;
+
+use dotenv::dotenv;
+use eframe::egui;
+use log::info;
+use oreb::{
+    egui::{rects_callback, Resources},
+    rect::{PainterSettings, Rect},
+};
+
+// Rects are generated in clip space, which the callback maps onto the panel.
+fn make_rects(time_seconds: f32) -> Vec<Rect> {
+    let steps = 20;
+    let dx = 1.8 / steps as f32;
+    (0..steps)
+        .map(|i| {
+            let i = i as f32;
+            let ph = 2.0 * PI * i / steps as f32;
+            let cx = -0.9 + dx * (i + 0.5);
+            let cy = 0.7 * (ph + 2.0 * PI * time_seconds / 7.0).cos();
+            let th = 2.0 * PI * time_seconds / 7.0;
+            Rect::new([cx, cy], [dx, dx], th)
+        })
+        .collect()
+}
+
+struct App {
+    clock: std::time::Instant,
+}
+
+impl App {
+    fn new(cc: &eframe::CreationContext) -> Self {
+        let render_state = cc
+            .wgpu_render_state
+            .as_ref()
+            .expect("eframe should be using the wgpu renderer");
+        Resources::install(render_state);
+        Self {
+            clock: std::time::Instant::now(),
+        }
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::Window::new("Rectangles").show(ctx, |ui| {
+            ui.label("Drawn by oreb inside an egui paint callback.");
+            let (viewport, _) =
+                ui.allocate_exact_size(egui::vec2(300.0, 300.0), egui::Sense::hover());
+            ui.painter().add(rects_callback(
+                viewport,
+                make_rects(self.clock.elapsed().as_secs_f32()),
+                PainterSettings {
+                    edge: [0.0, 0.0, 0.0, 1.0],
+                    fill: [0.8, 0.4, 0.2, 1.0],
+                    line_width_px: 2.0,
+                    corner_radius_px: 4.0,
+                    ..Default::default()
+                },
+            ));
+        });
+        ctx.request_repaint();
+    }
+}
+
+fn main() -> eframe::Result<()> {
+    dotenv().ok();
+    env_logger::init();
+    info!("Hello egui");
+
+    eframe::run_native(
+        "Oreb: egui",
+        eframe::NativeOptions {
+            renderer: eframe::Renderer::Wgpu,
+            ..Default::default()
+        },
+        Box::new(|cc| Box::new(App::new(cc))),
+    )
+}
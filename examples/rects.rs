@@ -1,123 +1,49 @@
-use std::f32::consts::PI;
+use std::time::{Duration, Instant};
 
 use dotenv::dotenv;
-use log::{error, info};
+use log::{error, info, warn};
 use oreb::{
-    rect::{Painter, PainterSettings, Vertex},
-    Context,
+    bench,
+    rect::{self, DebugViz, GeometryMode, PainterSettings},
+    CheckerSpec, Context, DrawOptions, SurfaceRotation, Transform2D,
 };
-use wgpu::{Color, SurfaceError, SurfaceTexture, TextureViewDescriptor};
+use wgpu::{Color, LoadOp, SurfaceError, TextureViewDescriptor};
 use winit::{
-    dpi::{LogicalSize, PhysicalSize},
+    dpi::LogicalSize,
     event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
 
-struct Rect {
-    center: [f32; 2],
-    size: [f32; 2],
-    orientation_radians: f32,
-}
-
-// x0,x1,y0,y1 are the bounds within which the rectangles should be generated.
-// They should be in clip space.
-fn make_rects(time_seconds: f32, x0: f32, x1: f32, y0: f32, y1: f32) -> Vec<Rect> {
-    let steps = 100;
-    let dx = (x1 - x0) / (steps + 1) as f32;
-    let dy = y1 - y0;
-    let sz = dx.max(0.1);
-    (0..steps)
-        .map(|i| {
-            let is_odd = (i & 1) == 1;
-            let i = i as f32;
-            let ph = 2.0 * PI * i / (steps + 1) as f32;
-            let cx = x0 + dx * (i + 0.5);
-            let cy = y0 + 0.5 * dy * (1.0 + (ph + 2.0 * PI * time_seconds / 7.0).cos());
-            let w = 3.0 * sz; // + 1.0 * sz * (2.0 * PI * time_seconds / 0.5).cos();
-            let h = 3.0 * sz; // + 1.0 * sz * (2.0 * PI * (0.3 + time_seconds / 3.0)).cos();
-            let th = (2.0 * PI * time_seconds / 7.0) * if is_odd { 1.0 } else { -1.0 };
-            Rect {
-                center: [cx, cy],
-                size: [w, h],
-                orientation_radians: th,
-            }
-        })
-        .collect()
-}
+const DEFAULT_RECTS: usize = 100;
 
-fn encode_geometry(rects: &[Rect]) -> (Vec<Vertex>, Vec<u32>) {
-    fn mk_vertices(rect: &Rect) -> [Vertex; 3] {
-        let [cx, cy] = rect.center;
-        let [half_w, half_h] = rect.size.map(|e| 0.5 * e);
-        let side = half_h + half_w;
-        let (s, c) = rect.orientation_radians.sin_cos();
-
-        // create an isosceles right triangle within which the rect will be painted
-        // center is at uv: [0,0]
-        // rect's [w,h] in uv coords is [1,1]
-        [
-            // top-left
-            Vertex {
-                xyz: [-half_w, -half_h, 0.0],
-                uv: [-0.5, -0.5],
-            },
-            // bottom-right
-            Vertex {
-                xyz: [2.0 * half_h - half_w, -half_h, 0.0],
-                uv: [-0.5 + side / half_h, -0.5],
-            },
-            // bottom-left
-            Vertex {
-                xyz: [-half_w, 2.0 * half_w - half_h, 0.0],
-                uv: [-0.5, -0.5 + side / half_w],
-            },
-        ]
-        .map(|mut v| {
-            // rotate about (0,0) by theta
-            // then translate
-            v.xyz[0] += half_w * 0.5;
-            v.xyz[1] += half_h * 0.5;
-            let x = v.xyz[0] * c - v.xyz[1] * s;
-            let y = v.xyz[0] * s + v.xyz[1] * c;
-            v.xyz[0] = x + cx;
-            v.xyz[1] = y + cy;
-            v
-        })
+// How many rects to draw: the first argument, or `OREB_RECTS` from the
+// environment or `.env`, or 100. Try 100000 to stress the pipeline.
+fn rect_count() -> usize {
+    let arg = std::env::args().nth(1);
+    let var = std::env::var("OREB_RECTS").ok();
+    match arg.or(var) {
+        Some(count) => count.parse().unwrap_or_else(|_| {
+            warn!("Can't read {count:?} as a rect count. Using {DEFAULT_RECTS}");
+            DEFAULT_RECTS
+        }),
+        None => DEFAULT_RECTS,
     }
-
-    let verts = rects
-        .into_iter()
-        .map(|r| mk_vertices(r))
-        .flatten()
-        .collect();
-    let idxs = (0..3 * rects.len() as u32).collect();
-    (verts, idxs)
 }
 
-fn draw(
-    rc: &Context,
-    painter: &mut Painter,
-    clear_color: Color,
-    time_seconds: f32,
-) -> Result<SurfaceTexture, SurfaceError> {
-    /*
-    let b=painter.builder();
-    let painter=b.finish();
-    painter.draw(rc,&target,clear_color);
-    */
-
-    // 1. Generate some random rectangles
-    // 2. encode geometry
-    let (vs, is) = encode_geometry(&make_rects(time_seconds, -0.9, 0.9, -0.9, 0.9));
-    // 3. stage
-    painter.set_geometry(rc, &vs, &is);
-    // 4. draw
-
-    let frame = rc.get_next_frame()?;
-    let target = frame.texture.create_view(&TextureViewDescriptor::default());
-    painter.draw(rc, &target, clear_color)?;
-    Ok(frame)
+// Draws the rects over a checkerboard, so their translucency shows.
+fn present_over_checkerboard(
+    rc: &mut Context,
+    painter: &rect::Painter,
+) -> Result<(), SurfaceError> {
+    let Some(frame) = rc.get_next_frame()? else {
+        return Ok(());
+    };
+    let view = frame.texture.create_view(&TextureViewDescriptor::default());
+    rc.draw_checkerboard(&view, &CheckerSpec::default());
+    painter.draw_with(rc, &view, LoadOp::Load, &DrawOptions::default());
+    rc.present_frame(frame);
+    Ok(())
 }
 
 #[async_std::main]
@@ -138,21 +64,20 @@ async fn main() {
         .build(&events)
         .expect("Failed to build window");
 
-    let mut rc = {
-        let PhysicalSize { width, height } = window.inner_size();
-        Context::with_window(&window, width, height).await
-    };
+    let rect_count = rect_count();
+    info!("Drawing {rect_count} rects");
+
+    let mut rc = Context::for_window(&window).await;
     let mut painter = rc.make_rect_painter();
 
-    painter.set_uniforms(
-        &rc,
-        &PainterSettings {
-            edge: [0.0, 0.0, 0.0, 1.0],
-            fill: [0.2, 0.2, 0.2, 0.5],
-            line_width_px: 2.0,
-            corner_radius_px: 0.0,
-        },
-    );
+    let mut settings = PainterSettings {
+        edge: [0.0, 0.0, 0.0, 1.0],
+        fill: [0.2, 0.2, 0.2, 0.5],
+        line_width_px: 2.0,
+        corner_radius_px: 0.0,
+        ..Default::default()
+    };
+    painter.set_uniforms(&rc, &settings);
 
     let clear_color = Color {
         r: 0.3,
@@ -161,45 +86,180 @@ async fn main() {
         a: 1.0,
     };
 
-    let clock = std::time::Instant::now();
+    // When on, the rects are drawn over a checkerboard instead of the clear
+    // color.
+    let mut checkerboard = true;
+
+    // When on, squares stay square however the window is shaped.
+    let mut preserve_aspect = false;
+    let update_transform = |rc: &Context, painter: &oreb::rect::Painter, preserve_aspect| {
+        let transform = if preserve_aspect {
+            Transform2D::aspect_correct(rc.aspect_ratio())
+        } else {
+            Transform2D::IDENTITY
+        };
+        painter.set_transform(rc, &transform);
+    };
+
+    let clock = Instant::now();
+    // Frames presented since `fps_since`, logged about once a second.
+    let mut fps_frames = 0;
+    let mut fps_since = Instant::now();
     let main_window_id = window.id();
     events.run(move |event, _, control_flow| match event {
         Event::RedrawRequested(window_id) if window_id == main_window_id => {
-            match {
-                draw(
-                    &rc,
-                    &mut painter,
-                    clear_color,
-                    clock.elapsed().as_secs_f32(),
-                )
-            } {
-                Ok(frame) => {
-                    frame.present();
+            // 1. Generate some random rectangles
+            // 2. encode geometry and stage
+            // Keep a 24 px margin around the rects
+            let size = window.inner_size();
+            let [x0, y1] = rc.pixel_to_clip([24.0, 24.0]);
+            let [x1, y0] = rc.pixel_to_clip([size.width as f32 - 24.0, size.height as f32 - 24.0]);
+            let rects =
+                bench::wave_rects(rect_count, clock.elapsed().as_secs_f32(), x0, x1, y0, y1);
+            if let Err(e) = painter.set_rects(&rc, &rects) {
+                error!("{e}");
+            }
+            // 3. draw and present
+            let presented = if checkerboard {
+                present_over_checkerboard(&mut rc, &painter)
+            } else {
+                painter.present(&mut rc, clear_color)
+            };
+            match presented {
+                Ok(()) => {
+                    fps_frames += 1;
+                    let elapsed = fps_since.elapsed();
+                    if elapsed >= Duration::from_secs(1) {
+                        let fps = fps_frames as f64 / elapsed.as_secs_f64();
+                        info!("{fps:.1} fps ({rect_count} rects)");
+                        fps_frames = 0;
+                        fps_since = Instant::now();
+                    }
                 }
-                Err(wgpu::SurfaceError::Lost) => rc.reset(),
-                Err(wgpu::SurfaceError::OutOfMemory) => {
+                Err(SurfaceError::Lost | SurfaceError::Outdated) => rc.reset(),
+                Err(SurfaceError::OutOfMemory) => {
                     error!("The render context appears out of memory. Exiting.");
                     *control_flow = ControlFlow::Exit;
                 }
-                Err(_) => todo!(),
+                Err(e) => warn!("Dropped a frame: {e}"),
             }
         }
 
-        Event::MainEventsCleared => {
+        // Nothing to draw while paused or minimized
+        Event::MainEventsCleared if rc.is_active() && !rc.is_minimized() => {
             window.request_redraw();
         }
 
         Event::WindowEvent { window_id, event } if window_id == main_window_id => match event {
             WindowEvent::Resized(size) => {
                 rc.resize(size.width, size.height);
+                update_transform(&rc, &painter, preserve_aspect);
                 window.request_redraw();
             }
 
-            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+            // Stop rendering while the window can't be seen
+            WindowEvent::Occluded(occluded) => {
+                info!("Occluded: {occluded}");
+                rc.set_active(!occluded);
+            }
+
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                new_inner_size,
+            } => {
+                rc.set_scale_factor(scale_factor as f32);
                 rc.resize(new_inner_size.width, new_inner_size.height);
+                update_transform(&rc, &painter, preserve_aspect);
                 window.request_redraw();
             }
 
+            // Toggle aspect correction
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::A),
+                        ..
+                    },
+                ..
+            } => {
+                preserve_aspect = !preserve_aspect;
+                info!("Preserve aspect: {preserve_aspect}");
+                update_transform(&rc, &painter, preserve_aspect);
+            }
+
+            // Toggle the checkerboard backdrop
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::C),
+                        ..
+                    },
+                ..
+            } => {
+                checkerboard = !checkerboard;
+                info!("Checkerboard: {checkerboard}");
+            }
+
+            // Toggle between covering triangles and tight quads
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::G),
+                        ..
+                    },
+                ..
+            } => {
+                let mode = match painter.geometry_mode() {
+                    GeometryMode::Triangle => GeometryMode::Quad,
+                    GeometryMode::Quad => GeometryMode::Triangle,
+                };
+                info!("Geometry mode: {mode:?}");
+                painter.set_geometry_mode(mode);
+            }
+
+            // Cycle through display orientations
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::R),
+                        ..
+                    },
+                ..
+            } => {
+                let rotation = match rc.orientation() {
+                    SurfaceRotation::Rotate0 => SurfaceRotation::Rotate90,
+                    SurfaceRotation::Rotate90 => SurfaceRotation::Rotate180,
+                    SurfaceRotation::Rotate180 => SurfaceRotation::Rotate270,
+                    SurfaceRotation::Rotate270 => SurfaceRotation::Rotate0,
+                };
+                info!("Orientation: {rotation:?}");
+                rc.set_orientation(rotation);
+            }
+
+            // Cycle through shader debug visualizations (debug builds only)
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::D),
+                        ..
+                    },
+                ..
+            } => {
+                settings.debug_visualize = match settings.debug_visualize {
+                    DebugViz::None => DebugViz::Distance,
+                    DebugViz::Distance => DebugViz::UV,
+                    DebugViz::UV => DebugViz::Coverage,
+                    DebugViz::Coverage => DebugViz::None,
+                };
+                info!("Debug visualization: {:?}", settings.debug_visualize);
+                painter.set_uniforms(&rc, &settings);
+            }
+
             WindowEvent::CloseRequested
             | WindowEvent::KeyboardInput {
                 input:
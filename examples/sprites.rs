@@ -0,0 +1,151 @@
+use std::f32::consts::PI;
+
+use dotenv::dotenv;
+use log::{error, info, warn};
+use oreb::{
+    sprite::{SamplerDesc, Sprite, SpriteTexture, MAX_TEXTURES},
+    Context,
+};
+use wgpu::{Color, FilterMode, SurfaceError};
+use winit::{
+    dpi::{LogicalSize, PhysicalSize},
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+// A checkerboard "icon" with a hue picked by `i`.
+fn make_icon(i: usize) -> Vec<u8> {
+    let [r, g, b] = [0.0, 2.0, 4.0].map(|ph: f32| {
+        let t = 2.0 * PI * i as f32 / MAX_TEXTURES as f32;
+        (127.5 * (1.0 + (t + ph).cos())) as u8
+    });
+    (0..16 * 16)
+        .flat_map(|p| {
+            let (x, y) = (p % 16, p / 16);
+            if (x / 4 + y / 4) % 2 == 0 {
+                [r, g, b, 255]
+            } else {
+                [255, 255, 255, 96]
+            }
+        })
+        .collect()
+}
+
+fn make_sprites(time_seconds: f32) -> Vec<Sprite> {
+    let steps = 10;
+    let d = 1.8 / steps as f32;
+    (0..steps * steps)
+        .map(|i| {
+            let (col, row) = ((i % steps) as f32, (i / steps) as f32);
+            let ph = 2.0 * PI * (col + row) / steps as f32;
+            Sprite {
+                orientation_radians: ph + 2.0 * PI * time_seconds / 7.0,
+                ..Sprite::new(
+                    [-0.9 + d * (col + 0.5), -0.9 + d * (row + 0.5)],
+                    [0.7 * d, 0.7 * d],
+                    (i % MAX_TEXTURES) as u32,
+                )
+            }
+        })
+        .collect()
+}
+
+#[async_std::main]
+async fn main() {
+    dotenv().ok();
+    env_logger::init();
+    info!("Hello sprites");
+
+    let events = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Oreb: Sprites")
+        .with_transparent(false)
+        .with_resizable(true)
+        .with_inner_size(LogicalSize {
+            width: 500,
+            height: 500,
+        })
+        .build(&events)
+        .expect("Failed to build window");
+
+    let mut rc = {
+        let PhysicalSize { width, height } = window.inner_size();
+        let mut rc = Context::with_window(&window, width, height).await;
+        rc.set_scale_factor(window.scale_factor() as f32);
+        rc
+    };
+    let mut painter = rc.make_sprite_painter();
+
+    // Every icon gets its own texture, but they all draw in one call.
+    // Nearest filtering keeps the checkers crisp when scaled up.
+    let pixel_art = SamplerDesc {
+        mag_filter: FilterMode::Nearest,
+        ..Default::default()
+    };
+    let icons: Vec<SpriteTexture> = (0..MAX_TEXTURES)
+        .map(|i| {
+            rc.upload_texture(16, 16, &make_icon(i))
+                .with_sampler(&rc, pixel_art)
+        })
+        .collect();
+    painter.set_textures(&rc, &icons.iter().collect::<Vec<_>>());
+
+    rc.set_clear_color(Color {
+        r: 0.3,
+        g: 0.2,
+        b: 0.1,
+        a: 1.0,
+    });
+
+    let clock = std::time::Instant::now();
+    let main_window_id = window.id();
+    events.run(move |event, _, control_flow| match event {
+        Event::RedrawRequested(window_id) if window_id == main_window_id => {
+            if let Err(e) = painter.set_sprites(&rc, &make_sprites(clock.elapsed().as_secs_f32())) {
+                error!("{e}");
+            }
+            match painter.present(&mut rc, None) {
+                Ok(()) => {}
+                Err(SurfaceError::OutOfMemory) => {
+                    error!("The render context appears out of memory. Exiting.");
+                    *control_flow = ControlFlow::Exit;
+                }
+                Err(e) => warn!("Dropped a frame: {e}"),
+            }
+        }
+
+        Event::MainEventsCleared => {
+            window.request_redraw();
+        }
+
+        Event::WindowEvent { window_id, event } if window_id == main_window_id => match event {
+            WindowEvent::Resized(size) => {
+                rc.resize(size.width, size.height);
+                window.request_redraw();
+            }
+
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                new_inner_size,
+            } => {
+                rc.set_scale_factor(scale_factor as f32);
+                rc.resize(new_inner_size.width, new_inner_size.height);
+                window.request_redraw();
+            }
+
+            WindowEvent::CloseRequested
+            | WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::Escape),
+                        ..
+                    },
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            _ => {}
+        },
+        _ => {}
+    });
+}
@@ -0,0 +1,197 @@
+//! Tweening values, such as `PainterSettings`, from one state to another.
+
+use std::time::Duration;
+
+use crate::rect::PainterSettings;
+
+/// Values that can be blended, `t` going from 0 at `self` to 1 at `other`.
+pub trait Lerp {
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+/// Componentwise, e.g. for colors and positions.
+impl<const N: usize> Lerp for [f32; N] {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        std::array::from_fn(|i| self[i].lerp(&other[i], t))
+    }
+}
+
+/// Blends the colors, line width and corner radius, and the alpha cutoff
+/// when both have one. `debug_visualize`, `line_width_space`,
+/// `stroke_align` and a cutoff turning on or off can't be blended, so they
+/// switch to `other`'s once `t` reaches 1.
+///
+/// Colors blend in whatever space they're given in, which is linear on
+/// sRGB targets.
+impl Lerp for PainterSettings {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let end = if t < 1.0 { self } else { other };
+        Self {
+            edge: self.edge.lerp(&other.edge, t),
+            fill: self.fill.lerp(&other.fill, t),
+            line_width_px: self.line_width_px.lerp(&other.line_width_px, t),
+            corner_radius_px: self.corner_radius_px.lerp(&other.corner_radius_px, t),
+            debug_visualize: end.debug_visualize,
+            line_width_space: end.line_width_space,
+            stroke_align: end.stroke_align,
+            alpha_cutoff: match (self.alpha_cutoff, other.alpha_cutoff) {
+                (Some(a), Some(b)) => Some(a.lerp(&b, t)),
+                _ => end.alpha_cutoff,
+            },
+        }
+    }
+}
+
+/// How a tween's progress maps to the blend between its ends.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Easing {
+    /// Constant speed.
+    #[default]
+    Linear,
+    /// Starts slow, quadratically.
+    EaseIn,
+    /// Ends slow, quadratically.
+    EaseOut,
+    /// Starts and ends slow, quadratically.
+    EaseInOut,
+    /// Starts slow, cubically. Snappier than `EaseIn`.
+    CubicIn,
+    /// Ends slow, cubically.
+    CubicOut,
+    /// Starts and ends slow, cubically.
+    CubicInOut,
+}
+
+impl Easing {
+    /// Maps progress `t` in `[0, 1]` to a blend factor, also 0 at 0 and 1
+    /// at 1. `t` outside that range is clamped.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut if t < 0.5 => 2.0 * t * t,
+            Easing::EaseInOut => 1.0 - 2.0 * (1.0 - t) * (1.0 - t),
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::CubicInOut => 1.0 - 4.0 * (1.0 - t).powi(3),
+        }
+    }
+}
+
+/// A blend from `from` to `to` over `duration`.
+///
+/// The tween doesn't keep time; pass `value` the time since it started,
+/// e.g. `start.elapsed()` on an `Instant` taken then, and hand the result
+/// to `Painter::set_uniforms` each frame.
+#[derive(Debug, Copy, Clone)]
+pub struct Tween<T> {
+    pub from: T,
+    pub to: T,
+    pub duration: Duration,
+    pub easing: Easing,
+}
+
+impl<T: Lerp + Clone> Tween<T> {
+    /// A linear tween.
+    pub fn new(from: T, to: T, duration: Duration) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            easing: Easing::Linear,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// The value `elapsed` into the tween: `from` at the start, `to` from
+    /// `duration` on. A zero `duration` jumps straight to `to`.
+    pub fn value(&self, elapsed: Duration) -> T {
+        if elapsed >= self.duration {
+            return self.to.clone();
+        }
+        let t = elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        self.from.lerp(&self.to, self.easing.apply(t))
+    }
+
+    pub fn is_finished(&self, elapsed: Duration) -> bool {
+        elapsed >= self.duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 7] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+    ];
+
+    #[test]
+    fn easings_run_from_0_to_1() {
+        for easing in EASINGS {
+            assert_eq!(easing.apply(0.0), 0.0, "{easing:?}");
+            assert_eq!(easing.apply(1.0), 1.0, "{easing:?}");
+            // Clamped outside [0, 1].
+            assert_eq!(easing.apply(-2.0), 0.0, "{easing:?}");
+            assert_eq!(easing.apply(3.0), 1.0, "{easing:?}");
+            // And never going back.
+            let mut last = 0.0;
+            for i in 1..=100 {
+                let value = easing.apply(i as f32 / 100.0);
+                assert!(value >= last, "{easing:?} at {i}");
+                last = value;
+            }
+        }
+    }
+
+    #[test]
+    fn in_out_easings_are_continuous_at_the_middle() {
+        for easing in [Easing::EaseInOut, Easing::CubicInOut] {
+            let below = easing.apply(0.5 - 1e-4);
+            let above = easing.apply(0.5 + 1e-4);
+            assert_eq!(easing.apply(0.5), 0.5);
+            assert!((above - below).abs() < 1e-3, "{easing:?}");
+        }
+    }
+
+    #[test]
+    fn tween_runs_from_from_to_to() {
+        let second = Duration::from_secs(1);
+        for easing in EASINGS {
+            let tween = Tween::new(2.0, 4.0, second).with_easing(easing);
+            assert_eq!(tween.value(Duration::ZERO), 2.0);
+            assert_eq!(tween.value(second), 4.0);
+            assert_eq!(tween.value(10 * second), 4.0);
+            assert!(!tween.is_finished(second / 2));
+            assert!(tween.is_finished(second));
+        }
+        let linear = Tween::new([0.0, 10.0], [1.0, 20.0], second);
+        assert_eq!(linear.value(second / 4), [0.25, 12.5]);
+    }
+
+    #[test]
+    fn zero_duration_tween_is_at_to() {
+        let tween = Tween::new(2.0, 4.0, Duration::ZERO);
+        assert_eq!(tween.value(Duration::ZERO), 4.0);
+        assert!(tween.is_finished(Duration::ZERO));
+    }
+}
@@ -0,0 +1,115 @@
+use wgpu::{
+    Extent3d, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    TextureView, TextureViewDescriptor, TextureViewDimension,
+};
+
+use crate::Context;
+
+/// A 2D array texture to draw into layer by layer, e.g. for shadow maps or
+/// other layered rendering. See `Context::create_array_render_target`.
+///
+/// Painters draw into one layer at a time, through that layer's view. To
+/// make a view of some other range yourself, give `TextureViewDescriptor`
+/// `dimension: Some(TextureViewDimension::D2)`, the layer as
+/// `base_array_layer`, and an `array_layer_count` of 1. Sampling all the
+/// layers at once takes a `D2Array` view instead.
+pub struct ArrayTarget {
+    texture: Texture,
+    layers: Vec<TextureView>,
+}
+
+impl ArrayTarget {
+    pub(crate) fn new(
+        rc: &Context,
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: TextureFormat,
+    ) -> Self {
+        let texture = rc.device.create_texture(&TextureDescriptor {
+            label: Some("Array render target"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let layers = (0..layers)
+            .map(|layer| {
+                texture.create_view(&TextureViewDescriptor {
+                    label: Some("Array render target layer"),
+                    dimension: Some(TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        Self { texture, layers }
+    }
+
+    /// The view to draw into `layer` through.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there's no such layer.
+    pub fn layer(&self, layer: u32) -> &TextureView {
+        &self.layers[layer as usize]
+    }
+
+    pub fn layer_count(&self) -> u32 {
+        self.layers.len() as u32
+    }
+
+    /// For reading a layer back with `Context::read_pixels` and
+    /// `ScreenshotOptions::layer`, or making other views.
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{rect::Rect, test_util::context, ScreenshotOptions};
+
+    #[test]
+    fn layers_hold_their_own_content() {
+        let Some(rc) = context(32, 32) else {
+            return;
+        };
+        let target = rc.create_array_render_target(32, 32, 2, rc.format());
+        assert_eq!(target.layer_count(), 2);
+        let mut painter = rc.make_rect_painter();
+        painter.set_uniforms(&rc, &Default::default());
+        // A rect on the left in layer 0 and on the right in layer 1.
+        for (layer, x) in [(0, -0.5), (1, 0.5)] {
+            painter
+                .set_rects(&rc, &[Rect::new([x, 0.0], [0.75, 1.0], 0.0)])
+                .unwrap();
+            painter
+                .draw(&rc, target.layer(layer), wgpu::Color::BLACK)
+                .unwrap();
+        }
+
+        let read = |layer| {
+            let options = ScreenshotOptions {
+                layer,
+                ..Default::default()
+            };
+            let shot = rc.read_pixels(target.texture(), &options);
+            let at = |x: usize| shot.rgba[4 * (16 * 32 + x)..][..4].to_vec();
+            (at(8), at(24))
+        };
+        let (white, black) = (vec![255; 4], vec![0, 0, 0, 255]);
+        assert_eq!(read(0), (white.clone(), black.clone()));
+        assert_eq!(read(1), (black, white));
+    }
+}
@@ -0,0 +1,151 @@
+//! Timing the rect pipeline without a window or event loop, e.g. from
+//! criterion or a plain loop.
+//!
+//! Make the context with `Context::headless`, or `Context::headless_software`
+//! for numbers that don't depend on the machine's GPU.
+
+use std::{
+    f32::consts::PI,
+    time::{Duration, Instant},
+};
+
+use wgpu::{
+    Color, Extent3d, Maintain, Texture, TextureDescriptor, TextureDimension, TextureUsages,
+    TextureView, TextureViewDescriptor,
+};
+
+use crate::{
+    rect::{Painter, Rect},
+    Context, GeometryError,
+};
+
+/// An offscreen texture of the context's format and size to draw frames
+/// into.
+pub struct Target {
+    texture: Texture,
+    view: TextureView,
+}
+
+impl Target {
+    /// # Panics
+    ///
+    /// Panics if the context has no size yet (see `Context::resize`).
+    pub fn new(rc: &Context) -> Self {
+        let (width, height) = (rc.config.width, rc.config.height);
+        assert!(
+            width > 0 && height > 0,
+            "The context needs a size for a bench target"
+        );
+        let texture = rc.device.create_texture(&TextureDescriptor {
+            label: Some("Bench target"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: rc.format(),
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        Self { texture, view }
+    }
+
+    /// For checking what was drawn, with `Context::read_pixels`.
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+}
+
+/// Uploads `rects`, draws them into `target`, and blocks until the GPU is
+/// done, returning the wall time that took.
+///
+/// Work submitted earlier is finished before the clock starts, so it isn't
+/// counted. The time covers encoding, the upload and the draw, as for one
+/// frame of an app that sets its rects every frame, but not presenting.
+/// The first frames include pipeline compilation and buffer growth on some
+/// backends, so warm up before measuring.
+pub fn bench_frame(
+    rc: &Context,
+    painter: &mut Painter,
+    rects: &[Rect],
+    target: &Target,
+) -> Result<Duration, GeometryError> {
+    rc.device.poll(Maintain::Wait);
+    let start = Instant::now();
+    painter.set_rects(rc, rects)?;
+    // Draws don't fail on offscreen targets; only acquiring frames does.
+    let _ = painter.draw(rc, &target.view, Color::TRANSPARENT);
+    rc.device.poll(Maintain::Wait);
+    Ok(start.elapsed())
+}
+
+/// Runs `draw`, which submits work of its own, and blocks until the GPU is
+/// done, returning the wall time that took. Earlier work is finished
+/// before the clock starts, as for `bench_frame`.
+pub fn time_draws(rc: &Context, draw: impl FnOnce()) -> Duration {
+    rc.device.poll(Maintain::Wait);
+    let start = Instant::now();
+    draw();
+    rc.device.poll(Maintain::Wait);
+    start.elapsed()
+}
+
+/// `count` rects on a square grid filling clip space, each rotated a
+/// little more than the last. The same every call, for steady numbers.
+pub fn grid_rects(count: usize) -> Vec<Rect> {
+    let side = (count as f32).sqrt().ceil() as usize;
+    let step = 2.0 / side as f32;
+    (0..count)
+        .map(|i| {
+            let (x, y) = ((i % side) as f32, (i / side) as f32);
+            Rect {
+                center: [-1.0 + step * (x + 0.5), -1.0 + step * (y + 0.5)],
+                size: [0.8 * step, 0.8 * step],
+                orientation_radians: PI * i as f32 / count as f32,
+            }
+        })
+        .collect()
+}
+
+/// `count` spinning squares in a row between `x0` and `x1`, bobbing
+/// between `y0` and `y1` in a wave that takes 7 seconds to go round, as of
+/// `time_seconds`. Bounds are in clip space.
+///
+/// Squares are at least 0.15 across, so large counts overlap heavily,
+/// which makes for a fill-rate heavy load.
+pub fn wave_rects(
+    count: usize,
+    time_seconds: f32,
+    x0: f32,
+    x1: f32,
+    y0: f32,
+    y1: f32,
+) -> Vec<Rect> {
+    let dx = (x1 - x0) / (count + 1) as f32;
+    let dy = y1 - y0;
+    let size = 1.5 * dx.max(0.1);
+    (0..count)
+        .map(|i| {
+            let is_odd = (i & 1) == 1;
+            let i = i as f32;
+            let phase = 2.0 * PI * i / (count + 1) as f32;
+            let spin = 2.0 * PI * time_seconds / 7.0;
+            Rect {
+                center: [
+                    x0 + dx * (i + 0.5),
+                    y0 + 0.5 * dy * (1.0 + (phase + spin).cos()),
+                ],
+                size: [size, size],
+                orientation_radians: if is_odd { spin } else { -spin },
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,98 @@
+use wgpu::{BlendComponent, BlendFactor, BlendOperation, BlendState};
+
+use crate::BlendError;
+
+/// How a painter's output combines with what's already in the target.
+///
+/// Painters output premultiplied alpha, so the presets and any `Custom`
+/// state should expect color already multiplied by alpha. See
+/// `rect::Painter::set_blend_mode`.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum BlendMode {
+    /// Standard "over" compositing of premultiplied color.
+    #[default]
+    Premultiplied,
+    /// Adds color to the target, brightening it, e.g. for glows and
+    /// particles. Alpha composites as for `Premultiplied`.
+    Additive,
+    /// Multiplies the target by the color where it's opaque, darkening it,
+    /// e.g. for shadows and tinting. White leaves the target as is.
+    Multiply,
+    /// Exact factors and operations for color and alpha.
+    ///
+    /// Some recipes, with `dst` the target and `src` the painter's
+    /// premultiplied output:
+    ///
+    /// - Subtractive, `dst - src`: color `One`/`One` with
+    ///   `BlendOperation::ReverseSubtract`, alpha as `Premultiplied`.
+    ///   Darkens towards black by the painter's color.
+    /// - Screen, `src + dst * (1 - src)`: color `One`/`OneMinusSrc` with
+    ///   `Add`, alpha as `Premultiplied`. Lightens without blowing out the
+    ///   way `Additive` does.
+    ///
+    /// `Min` and `Max` need both factors `One`, and `Constant` factors
+    /// aren't allowed since painters don't set a blend constant. Painters
+    /// check this when they make their pipelines.
+    Custom(BlendState),
+}
+
+/// Premultiplied "over", for alpha in every preset.
+const OVER: BlendComponent = BlendComponent {
+    src_factor: BlendFactor::One,
+    dst_factor: BlendFactor::OneMinusSrcAlpha,
+    operation: BlendOperation::Add,
+};
+
+impl BlendMode {
+    /// The pipeline blend state for the mode.
+    pub fn state(self) -> BlendState {
+        match self {
+            BlendMode::Premultiplied => BlendState {
+                color: OVER,
+                alpha: OVER,
+            },
+            BlendMode::Additive => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: OVER,
+            },
+            BlendMode::Multiply => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::Dst,
+                    dst_factor: BlendFactor::OneMinusSrcAlpha,
+                    operation: BlendOperation::Add,
+                },
+                alpha: OVER,
+            },
+            BlendMode::Custom(state) => state,
+        }
+    }
+
+    /// Checks the mode's state is one a painter can use. The presets always
+    /// are.
+    pub fn validate(self) -> Result<BlendState, BlendError> {
+        let state = self.state();
+        for (component, name) in [(state.color, "color"), (state.alpha, "alpha")] {
+            let factors = [component.src_factor, component.dst_factor];
+            if matches!(
+                component.operation,
+                BlendOperation::Min | BlendOperation::Max
+            ) && factors != [BlendFactor::One; 2]
+            {
+                return Err(BlendError::MinMaxFactors { component: name });
+            }
+            if factors.iter().any(|factor| {
+                matches!(
+                    factor,
+                    BlendFactor::Constant | BlendFactor::OneMinusConstant
+                )
+            }) {
+                return Err(BlendError::ConstantFactor { component: name });
+            }
+        }
+        Ok(state)
+    }
+}
@@ -0,0 +1,323 @@
+use std::sync::{Arc, OnceLock};
+
+use log::{info, warn};
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+use wgpu::{
+    Adapter, Backends, Color, DeviceDescriptor, Instance, InstanceDescriptor, PresentMode,
+    RequestAdapterOptions, Surface, SurfaceConfiguration, TextureFormat, TextureUsages,
+};
+
+use crate::{
+    dirty_from_start, make_stencil, present, view::View, Context, ContextError, SurfaceRotation,
+    Transform2D,
+};
+
+/// Options for creating a `Context`.
+#[derive(Debug, Clone)]
+pub struct ContextBuilder {
+    stencil: bool,
+    backends: Option<Backends>,
+    adapter_name: Option<String>,
+    require_adapter_name: bool,
+    adapter_index: Option<usize>,
+    prefer_srgb: bool,
+    prefer_wide_gamut: bool,
+    present_mode: Option<PresentMode>,
+    clear_color: Color,
+}
+
+impl Default for ContextBuilder {
+    fn default() -> Self {
+        Self {
+            stencil: false,
+            backends: None,
+            adapter_name: None,
+            require_adapter_name: false,
+            adapter_index: None,
+            prefer_srgb: true,
+            prefer_wide_gamut: false,
+            present_mode: None,
+            clear_color: Color::BLACK,
+        }
+    }
+}
+
+impl ContextBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allocates a depth/stencil buffer alongside the surface so rect
+    /// painters can draw stencil masks (see `rect::Painter::draw_mask`).
+    ///
+    /// The buffer is recreated when the context is resized.
+    pub fn with_stencil(mut self, enable: bool) -> Self {
+        self.stencil = enable;
+        self
+    }
+
+    /// Restricts which graphics APIs adapters are looked for on.
+    /// All of them by default.
+    pub fn with_backends(mut self, backends: Backends) -> Self {
+        self.backends = Some(backends);
+        self
+    }
+
+    /// Prefers the first adapter whose name contains `name`, ignoring case,
+    /// e.g. "llvmpipe" for the software rasterizer on CI.
+    ///
+    /// If none matches, the usual high-performance choice is made instead,
+    /// unless `require_adapter_name(true)` is set. The adapter that ends up
+    /// being used is logged at info level either way.
+    pub fn adapter_name_contains(mut self, name: &str) -> Self {
+        self.adapter_name = Some(name.to_lowercase());
+        self
+    }
+
+    /// Makes `try_build` fail with `ContextError::NoMatchingAdapter` instead
+    /// of falling back when no adapter matches `adapter_name_contains`.
+    pub fn require_adapter_name(mut self, require: bool) -> Self {
+        self.require_adapter_name = require;
+        self
+    }
+
+    /// Uses the adapter at `index` in `Context::available_adapters()`,
+    /// overriding `adapter_name_contains` and `with_backends`.
+    ///
+    /// `try_build` fails with `ContextError::NoAdapterAtIndex` if there's
+    /// no such adapter, or `ContextError::IncompatibleAdapter` if it can't
+    /// draw to the window.
+    pub fn adapter_index(mut self, index: usize) -> Self {
+        self.adapter_index = Some(index);
+        self
+    }
+
+    /// Whether to pick an sRGB surface format when the surface offers one.
+    /// True by default.
+    ///
+    /// With an sRGB surface, painter colors like `PainterSettings::fill`
+    /// are linear: the GPU encodes them to sRGB on write, after blending.
+    /// Passing false picks a non-sRGB format where available, which stores
+    /// colors exactly as given, so they're effectively already-encoded
+    /// values and blending happens on them. Use this to do your own color
+    /// management. Check `Context::format` for what was picked; the surface
+    /// may only offer one kind.
+    pub fn prefer_srgb(mut self, prefer: bool) -> Self {
+        self.prefer_srgb = prefer;
+        self
+    }
+
+    /// Picks an `Rgba16Float` surface where the surface offers one, over
+    /// `prefer_srgb`. Off by default.
+    ///
+    /// wgpu doesn't expose surface color spaces, so this is as wide a gamut
+    /// as can be asked for: compositors that take float surfaces read them
+    /// as extended linear sRGB, where colors past [0, 1] reach outside
+    /// sRGB on capable displays, e.g. Windows with HDR on. Elsewhere they
+    /// clamp. `Context::output_color_space` says `Linear` when it worked.
+    pub fn prefer_wide_gamut(mut self, prefer: bool) -> Self {
+        self.prefer_wide_gamut = prefer;
+        self
+    }
+
+    /// Asks for a present mode, e.g. `Mailbox` for triple buffering.
+    ///
+    /// If the surface doesn't support it, the surface's first mode is used
+    /// instead and that's logged as a warning; Metal, for one, has no
+    /// `Mailbox` and only has `Immediate` on macOS. By default the first
+    /// mode is used, which is `Fifo` on most platforms. See `BufferingInfo` for
+    /// what each implies, and `Context::present_mode_buffering` for what
+    /// was picked.
+    pub fn present_mode(mut self, mode: PresentMode) -> Self {
+        self.present_mode = Some(mode);
+        self
+    }
+
+    /// The context's initial `clear_color()`. Black by default.
+    pub fn clear_color(mut self, color: Color) -> Self {
+        self.clear_color = color;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `try_build` fails.
+    pub async fn build<W>(self, window: &W, width: u32, height: u32) -> Context
+    where
+        W: HasRawWindowHandle + HasRawDisplayHandle,
+    {
+        match self.try_build(window, width, height).await {
+            Ok(context) => context,
+            Err(e) => panic!("Failed to create a context: {e}"),
+        }
+    }
+
+    pub async fn try_build<W>(
+        self,
+        window: &W,
+        width: u32,
+        height: u32,
+    ) -> Result<Context, ContextError>
+    where
+        W: HasRawWindowHandle + HasRawDisplayHandle,
+    {
+        // Indexes count adapters on every backend.
+        let backends = match self.adapter_index {
+            Some(_) => Backends::all(),
+            None => self.backends.unwrap_or(Backends::all()),
+        };
+        let instance = Instance::new(InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
+
+        let surface = unsafe { instance.create_surface(window) }?;
+
+        let adapter = self.select_adapter(&instance, backends, &surface).await?;
+        let info = adapter.get_info();
+        info!("Using adapter {:?} ({:?})", info.name, info.backend);
+
+        let (device, commands) = adapter
+            .request_device(
+                &DeviceDescriptor {
+                    label: None,
+                    features: Default::default(),
+                    limits: Default::default(),
+                },
+                None,
+            )
+            .await?;
+
+        let caps = surface.get_capabilities(&adapter);
+        let surface_formats = caps.formats.clone();
+        let present_modes = caps.present_modes.clone();
+        let config = {
+            let (Some(&first_format), Some(&first_present_mode), Some(&alpha_mode)) = (
+                caps.formats.first(),
+                caps.present_modes.first(),
+                caps.alpha_modes.first(),
+            ) else {
+                return Err(ContextError::NoCompatibleSurface {
+                    formats: caps.formats,
+                    present_modes: caps.present_modes,
+                    alpha_modes: caps.alpha_modes,
+                });
+            };
+            // pick an srgb (or, if not preferred, a non-srgb) format if
+            // available
+            let wide_gamut = (self.prefer_wide_gamut
+                && caps.formats.contains(&TextureFormat::Rgba16Float))
+            .then_some(TextureFormat::Rgba16Float);
+            let format = wide_gamut.unwrap_or_else(|| {
+                caps.formats
+                    .iter()
+                    .filter(|&f| f.is_srgb() == self.prefer_srgb)
+                    .copied()
+                    .next()
+                    .unwrap_or(first_format)
+            });
+            let present_mode = match self.present_mode {
+                Some(mode) if present::is_supported(&caps.present_modes, mode) => mode,
+                Some(mode) => {
+                    warn!(
+                        "The surface doesn't support {mode:?}, only {:?}. Using {first_present_mode:?}",
+                        caps.present_modes
+                    );
+                    first_present_mode
+                }
+                None => first_present_mode,
+            };
+            SurfaceConfiguration {
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                format,
+                width: width.min(2),
+                height: height.min(2),
+                present_mode,
+                alpha_mode,
+                view_formats: Default::default(),
+            }
+        };
+        surface.configure(&device, &config);
+
+        let stencil = self
+            .stencil
+            .then(|| make_stencil(&device, config.width, config.height));
+
+        let device = Arc::new(device);
+        let view = Arc::new(View::new(&device, &commands));
+
+        Ok(Context {
+            limits: device.limits(),
+            backend: info.backend,
+            device,
+            commands: Arc::new(commands),
+            surface: Some(surface),
+            config,
+            surface_formats,
+            present_modes,
+            stencil,
+            view,
+            orientation: SurfaceRotation::default(),
+            view_transform: Transform2D::IDENTITY,
+            scale_factor: 1.0,
+            logical_coordinates: false,
+            clear_color: self.clear_color,
+            output_color_space: None,
+            active: true,
+            minimized: false,
+            frame_index: 0,
+            device_generation: 0,
+            dirty: dirty_from_start(),
+            post: OnceLock::new(),
+            mips: OnceLock::new(),
+            checker: OnceLock::new(),
+            #[cfg(feature = "video")]
+            yuv: OnceLock::new(),
+            #[cfg(feature = "postfx")]
+            bloom: OnceLock::new(),
+            #[cfg(feature = "timing")]
+            timing: Default::default(),
+        })
+    }
+
+    async fn select_adapter(
+        &self,
+        instance: &Instance,
+        backends: Backends,
+        surface: &Surface,
+    ) -> Result<Adapter, ContextError> {
+        if let Some(index) = self.adapter_index {
+            let mut adapters: Vec<_> = instance.enumerate_adapters(backends).collect();
+            let count = adapters.len();
+            if index >= count {
+                return Err(ContextError::NoAdapterAtIndex { index, count });
+            }
+            let adapter = adapters.swap_remove(index);
+            if !adapter.is_surface_supported(surface) {
+                return Err(ContextError::IncompatibleAdapter(adapter.get_info().name));
+            }
+            return Ok(adapter);
+        }
+        if let Some(name) = &self.adapter_name {
+            let found = instance.enumerate_adapters(backends).find(|adapter| {
+                adapter.is_surface_supported(surface)
+                    && adapter.get_info().name.to_lowercase().contains(name)
+            });
+            match found {
+                Some(adapter) => return Ok(adapter),
+                None if self.require_adapter_name => {
+                    return Err(ContextError::NoMatchingAdapter(name.clone()))
+                }
+                None => info!("No adapter matches {name:?}, falling back to the default choice"),
+            }
+        }
+        instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                force_fallback_adapter: false,
+                compatible_surface: Some(surface),
+            })
+            .await
+            .ok_or(ContextError::NoAdapter)
+    }
+}
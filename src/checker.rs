@@ -0,0 +1,157 @@
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferUsages,
+    Color, ColorTargetState, ColorWrites, CommandEncoderDescriptor, FragmentState, LoadOp,
+    MultisampleState, Operations, PipelineLayoutDescriptor, PrimitiveState,
+    RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureView, VertexState,
+};
+
+use crate::{util::as_raw_bytes, Context};
+
+/// The pattern `Context::draw_checkerboard` fills a target with.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CheckerSpec {
+    /// Width of a square cell in px.
+    pub cell_size: f32,
+    /// The cell in the target's top left corner, and every other one from
+    /// there. Linear on sRGB targets, like painter colors.
+    pub color_a: [f32; 4],
+    pub color_b: [f32; 4],
+}
+
+impl Default for CheckerSpec {
+    /// 8 px cells of light and mid gray, as image editors draw behind
+    /// transparent pixels.
+    fn default() -> Self {
+        Self {
+            cell_size: 8.0,
+            color_a: [0.8, 0.8, 0.8, 1.0],
+            color_b: [0.5, 0.5, 0.5, 1.0],
+        }
+    }
+}
+
+/// `Spec` in checker.wgsl.
+#[repr(C, align(16))]
+struct Uniforms {
+    color_a: [f32; 4],
+    color_b: [f32; 4],
+    cell_size: f32,
+}
+
+/// Draws `CheckerSpec`s into targets of the context's format.
+pub(crate) struct Checkerboard {
+    pipeline: RenderPipeline,
+    bind_group: BindGroup,
+    uniforms: Buffer,
+}
+
+impl Checkerboard {
+    pub(crate) fn new(rc: &Context) -> Self {
+        let layout = rc
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Checkerboard bind group layout"),
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let uniforms = rc.device.create_buffer(&BufferDescriptor {
+            label: Some("Checkerboard uniforms"),
+            size: std::mem::size_of::<Uniforms>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = rc.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Checkerboard bind group"),
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: uniforms.as_entire_binding(),
+            }],
+        });
+
+        let module = &rc.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Checkerboard shader module"),
+            source: ShaderSource::Wgsl(include_str!("checker.wgsl").into()),
+        });
+
+        let pipeline = rc.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Checkerboard pipeline"),
+            layout: Some(
+                &rc.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Checkerboard pipeline layout"),
+                    bind_group_layouts: &[&layout],
+                    push_constant_ranges: &[],
+                }),
+            ),
+            vertex: VertexState {
+                module,
+                entry_point: "vs",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module,
+                entry_point: "fs",
+                targets: &[Some(ColorTargetState {
+                    format: rc.config.format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            uniforms,
+        }
+    }
+
+    pub(crate) fn run(&self, rc: &Context, target: &TextureView, spec: &CheckerSpec) {
+        let uniforms = Uniforms {
+            color_a: spec.color_a,
+            color_b: spec.color_b,
+            cell_size: spec.cell_size.max(1.0),
+        };
+        rc.commands
+            .write_buffer(&self.uniforms, 0, unsafe { as_raw_bytes(&uniforms) });
+
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        {
+            let mut pass = commands.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Checkerboard pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: Operations {
+                        // Every pixel is written.
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        rc.commands.submit(std::iter::once(commands.finish()));
+    }
+}
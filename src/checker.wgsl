@@ -0,0 +1,33 @@
+struct Spec {
+    color_a: vec4<f32>,
+    color_b: vec4<f32>,
+    // Width of a square cell in px.
+    cell_size: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> spec: Spec;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+}
+
+// One triangle covering the viewport.
+@vertex
+fn vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(2.0 * uv - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The position is in px from the top left, so the pattern starts with
+    // a full color_a cell there.
+    let cell = vec2<i32>(floor(in.position.xy / spec.cell_size));
+    if ((cell.x + cell.y) & 1) == 0 {
+        return spec.color_a;
+    }
+    return spec.color_b;
+}
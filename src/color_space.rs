@@ -0,0 +1,45 @@
+use wgpu::TextureFormat;
+
+/// How whatever reads a context's output should interpret its values. See
+/// `Context::output_color_space`.
+///
+/// Oreb never converts between color spaces: painter colors like
+/// `PainterSettings::fill` are in the output's primaries, and only the
+/// transfer function differs by format. With an sRGB format (`format()`
+/// `.is_srgb()`) colors are linear and the GPU encodes them on write,
+/// after blending. Other 8, 10 and 16-bit formats store colors as given,
+/// so they're taken to be encoded already and blend as such. Float formats
+/// store them as given too, but the consumer reads those as linear.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// sRGB primaries and transfer function, what displays assume without
+    /// being told otherwise.
+    Srgb,
+    /// sRGB primaries, linear light, from float formats. Values may go
+    /// past [0, 1] for colors outside the sRGB gamut and highlights
+    /// brighter than white, as in extended linear sRGB (scRGB), where the
+    /// consumer supports that.
+    Linear,
+    /// Display P3 primaries with the sRGB transfer function, as wide-gamut
+    /// Apple displays and many video pipelines use. Only ever a tag, set
+    /// with `Context::set_output_color_space` by a host that configured its
+    /// target that way.
+    DisplayP3,
+}
+
+impl ColorSpace {
+    /// The color space values written to `format` are read in, unless the
+    /// target is tagged otherwise.
+    pub fn for_format(format: TextureFormat) -> Self {
+        match format {
+            TextureFormat::R16Float
+            | TextureFormat::Rg16Float
+            | TextureFormat::Rgba16Float
+            | TextureFormat::R32Float
+            | TextureFormat::Rg32Float
+            | TextureFormat::Rgba32Float
+            | TextureFormat::Rg11b10Float => ColorSpace::Linear,
+            _ => ColorSpace::Srgb,
+        }
+    }
+}
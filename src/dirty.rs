@@ -0,0 +1,45 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Whether the window's content is out of date, so apps that only change
+/// on input can skip drawing the rest of the time. See
+/// `Context::needs_redraw`.
+///
+/// Clones share the flag, so subsystems that change state can each hold
+/// one and mark the context dirty without reaching it. It's safe to set
+/// from any thread.
+///
+/// With winit, run the loop with `ControlFlow::Wait` so it sleeps between
+/// events, and:
+///
+/// 1. Call `set` (or `Context::mark_dirty`) whenever input or other events
+///    change what's drawn.
+/// 2. On `MainEventsCleared`, call `window.request_redraw()` only if
+///    `Context::needs_redraw()`.
+/// 3. On `RedrawRequested`, draw and present as usual. Acquiring the frame
+///    clears the flag.
+///
+/// Changes made while the frame is being drawn leave the flag set, so
+/// they're picked up on the next pass. Animations keep setting it for as
+/// long as they run.
+#[derive(Debug, Clone, Default)]
+pub struct DirtyFlag(Arc<AtomicBool>);
+
+impl DirtyFlag {
+    pub fn set(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Clears the flag, returning whether it was set. `Context` does this
+    /// on acquiring a frame; hosts presenting frames of their own (see
+    /// `Context::from_raw`) call it when they draw.
+    pub fn take(&self) -> bool {
+        self.0.swap(false, Ordering::AcqRel)
+    }
+}
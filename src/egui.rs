@@ -0,0 +1,88 @@
+//! Drawing rects inside an egui UI.
+//!
+//! egui-wgpu owns the device, queue and render pass. oreb borrows them:
+//!
+//! 1. When the app starts, call [`Resources::install`] with egui's
+//!    `RenderState`. This wraps egui's device and queue in a [`Context`]
+//!    (see [`Context::from_raw`]) and stores it, along with a rect painter,
+//!    in the renderer's `paint_callback_resources`.
+//! 2. Each frame, add the callback from [`rects_callback`] to a `Painter`.
+//!    egui runs its prepare step before the render pass begins, which is
+//!    where geometry and uniforms get uploaded through the shared queue.
+//!    Then the paint step records the draw into egui's render pass with the
+//!    viewport set to the callback's rect.
+//!
+//! egui-wgpu 0.22 (the release that uses this crate's wgpu) expresses
+//! callbacks as a `CallbackFn` rather than a trait.
+//!
+//! There is one painter, so only one rects callback can be drawn per frame.
+//! The painter is single-sampled with no depth attachment. egui's renderer
+//! has to be configured the same way, which is eframe's default.
+
+use std::sync::Arc;
+
+use egui_wgpu::{CallbackFn, RenderState};
+use log::error;
+
+use crate::{
+    rect::{self, PainterSettings, Rect},
+    Context,
+};
+
+/// What oreb keeps in egui's `paint_callback_resources`.
+pub struct Resources {
+    pub context: Context,
+    pub painter: rect::Painter,
+}
+
+impl Resources {
+    pub fn install(render_state: &RenderState) {
+        let mut context = Context::from_raw(
+            render_state.device.clone(),
+            render_state.queue.clone(),
+            render_state.target_format,
+        );
+        context.set_backend(render_state.adapter.get_info().backend);
+        let painter = context.make_rect_painter();
+        render_state
+            .renderer
+            .write()
+            .paint_callback_resources
+            .insert(Resources { context, painter });
+    }
+}
+
+/// Builds a callback that draws `rects` in `viewport`.
+///
+/// Clip space `[-1, 1]` covers `viewport`.
+///
+/// # Panics
+///
+/// The callback panics if [`Resources::install`] hasn't been called.
+pub fn rects_callback(
+    viewport: ::egui::Rect,
+    rects: Vec<Rect>,
+    settings: PainterSettings,
+) -> ::egui::PaintCallback {
+    let callback = CallbackFn::new()
+        .prepare(move |_device, _queue, _encoder, resources| {
+            let Resources { context, painter } = resources
+                .get_mut::<Resources>()
+                .expect("oreb::egui::Resources::install must be called first");
+            painter.set_uniforms(context, &settings);
+            if let Err(e) = painter.set_rects(context, &rects) {
+                error!("Couldn't upload rects: {e}");
+            }
+            Vec::new()
+        })
+        .paint(|_info, pass, resources| {
+            let Resources { painter, .. } = resources
+                .get::<Resources>()
+                .expect("oreb::egui::Resources::install must be called first");
+            painter.paint(pass);
+        });
+    ::egui::PaintCallback {
+        rect: viewport,
+        callback: Arc::new(callback),
+    }
+}
@@ -0,0 +1,109 @@
+use thiserror::Error;
+use wgpu::{
+    CompositeAlphaMode, CreateSurfaceError, PresentMode, RequestDeviceError, TextureFormat,
+};
+
+#[derive(Debug, Error)]
+pub enum GeometryError {
+    #[error(
+        "The {buffer} would need {requested} bytes, over the device's max_buffer_size of {max}"
+    )]
+    TooLarge {
+        /// Which of the painter's buffers it was.
+        buffer: &'static str,
+        requested: u64,
+        max: u64,
+    },
+    #[error(
+        "The chunk would bring the stream to {vertices} vertices and {indexes} indexes, \
+         past the {declared_vertices} and {declared_indexes} declared"
+    )]
+    StreamOverflow {
+        declared_vertices: usize,
+        declared_indexes: usize,
+        vertices: usize,
+        indexes: usize,
+    },
+    #[error(
+        "The stream ended with {vertices} of {declared_vertices} vertices and \
+         {indexes} of {declared_indexes} indexes"
+    )]
+    StreamIncomplete {
+        declared_vertices: usize,
+        declared_indexes: usize,
+        vertices: usize,
+        indexes: usize,
+    },
+    #[error("16-bit indexes can address {max} vertices but {count} were given")]
+    TooManyVertices { count: usize, max: usize },
+    #[error("No stream is in progress. Call begin_streaming first")]
+    NotStreaming,
+    #[error(
+        "Culling the rects on the GPU would bind {requested} bytes, over the device's \
+         max_storage_buffer_binding_size of {max}"
+    )]
+    TooLargeToCull { requested: u64, max: u64 },
+}
+
+/// A `BlendMode::Custom` state painters can't draw with.
+#[derive(Debug, Error)]
+pub enum BlendError {
+    #[error("The {component} blend uses Min or Max, which need both factors to be One")]
+    MinMaxFactors { component: &'static str },
+    #[error(
+        "The {component} blend uses a constant factor, but painters don't set a blend constant"
+    )]
+    ConstantFactor { component: &'static str },
+}
+
+#[derive(Debug, Error)]
+pub enum SceneFileError {
+    #[error("Failed to read or write the scene: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a scene file: the header doesn't start with \"ORSC\"")]
+    BadMagic,
+    /// Written by a newer version of oreb, or corrupt: versions start at 1.
+    #[error("Scene format version {found} isn't one of the 1 to {supported} this version reads")]
+    UnsupportedVersion { found: u8, supported: u8 },
+}
+
+#[derive(Debug, Error)]
+pub enum ContextError {
+    #[error("Failed to create a surface for the window: {0}")]
+    CreateSurface(#[from] CreateSurfaceError),
+    #[error("No suitable adapter was found")]
+    NoAdapter,
+    #[error("No adapter that can draw to the window has a name containing {0:?}")]
+    NoMatchingAdapter(String),
+    #[error("There's no adapter {index}, only {count}")]
+    NoAdapterAtIndex { index: usize, count: usize },
+    #[error("The adapter {0:?} can't draw to the window")]
+    IncompatibleAdapter(String),
+    #[error("No software adapter is available. See Context::headless_software")]
+    NoSoftwareAdapter,
+    #[error("The surface doesn't support {format:?}, only {supported:?}")]
+    UnsupportedFormat {
+        format: TextureFormat,
+        supported: Vec<TextureFormat>,
+    },
+    #[error("The surface doesn't support {mode:?}, only {supported:?}")]
+    UnsupportedPresentMode {
+        mode: PresentMode,
+        supported: Vec<PresentMode>,
+    },
+    #[error("A side of {requested} px is over the device's max texture dimension of {max}")]
+    TextureTooLarge { requested: u32, max: u32 },
+    #[error("Failed to open the device: {0}")]
+    RequestDevice(#[from] RequestDeviceError),
+    /// The adapter reported a surface capability list as empty, which some
+    /// broken drivers do. Holds what it did report.
+    #[error(
+        "The surface can't be configured: the adapter reports formats {formats:?}, \
+         present modes {present_modes:?} and alpha modes {alpha_modes:?}"
+    )]
+    NoCompatibleSurface {
+        formats: Vec<TextureFormat>,
+        present_modes: Vec<PresentMode>,
+        alpha_modes: Vec<CompositeAlphaMode>,
+    },
+}
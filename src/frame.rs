@@ -0,0 +1,129 @@
+use wgpu::{Color, TextureView};
+
+use crate::{graph, point, rect, sprite, Context};
+
+/// Draws several painters into one target in turn, clearing it only for
+/// the first. Made by `Context::begin_frame`.
+///
+/// The first draw clears the color attachment to the frame's clear color,
+/// and the stencil buffer when the context has one, like a painter's
+/// `draw`. Every later draw loads both, like `draw_over`, so masks and
+/// earlier painters survive. Each draw is submitted right away.
+pub struct FrameEncoder<'a> {
+    rc: &'a Context,
+    target: &'a TextureView,
+    clear_color: Color,
+    cleared: bool,
+}
+
+impl<'a> FrameEncoder<'a> {
+    pub(crate) fn new(rc: &'a Context, target: &'a TextureView, clear_color: Color) -> Self {
+        Self {
+            rc,
+            target,
+            clear_color,
+            cleared: false,
+        }
+    }
+
+    pub fn draw_rects(&mut self, painter: &rect::Painter) {
+        self.draw(graph::Node::Rects(painter));
+    }
+
+    pub fn draw_instanced_rects(&mut self, painter: &rect::InstancePainter) {
+        self.draw(graph::Node::InstancedRects(painter));
+    }
+
+    pub fn draw_sprites(&mut self, painter: &sprite::Painter) {
+        self.draw(graph::Node::Sprites(painter));
+    }
+
+    pub fn draw_points(&mut self, painter: &point::Painter) {
+        self.draw(graph::Node::Points(painter));
+    }
+
+    /// Whether a draw has cleared the target yet.
+    pub fn is_cleared(&self) -> bool {
+        self.cleared
+    }
+
+    /// Ends the frame, clearing the target if nothing was drawn so it
+    /// doesn't keep the last frame's image.
+    pub fn finish(self) {
+        if !self.cleared {
+            graph::draw(
+                self.rc,
+                &[graph::Node::Clear(self.clear_color)],
+                self.target,
+            );
+        }
+    }
+
+    fn draw(&mut self, node: graph::Node) {
+        if self.cleared {
+            graph::draw(self.rc, &[node], self.target);
+        } else {
+            graph::draw(
+                self.rc,
+                &[graph::Node::Clear(self.clear_color), node],
+                self.target,
+            );
+            self.cleared = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        point::Point,
+        rect::{Rect, RectInstance},
+        sprite::Sprite,
+        test_util::{stencil_context, Target},
+    };
+
+    #[test]
+    fn draws_each_painter_with_a_stencil() {
+        let Some(rc) = stencil_context(64, 64) else {
+            return;
+        };
+        let target = Target::new(&rc);
+
+        let mut rects = rc.make_rect_painter();
+        rects
+            .set_rects(&rc, &[Rect::new([-0.5, 0.5], [0.5, 0.5], 0.0)])
+            .unwrap();
+        rects.set_uniforms(&rc, &Default::default());
+        let mut instanced = rc.make_rect_instance_painter();
+        instanced
+            .set_instances(&rc, &[RectInstance::new([0.5, 0.5], [0.5, 0.5], 0.0)])
+            .unwrap();
+        instanced.set_uniforms(&rc, &Default::default());
+        let mut sprites = rc.make_sprite_painter();
+        sprites
+            .set_sprites(&rc, &[Sprite::new([-0.5, -0.5], [0.5, 0.5], 0)])
+            .unwrap();
+        let mut points = rc.make_point_painter();
+        points
+            .set_points(&rc, &[Point::new([0.5, -0.5], 8.0)])
+            .unwrap();
+
+        let mut frame = rc.begin_frame(&target.view, wgpu::Color::BLACK);
+        frame.draw_rects(&rects);
+        frame.draw_instanced_rects(&instanced);
+        frame.draw_sprites(&sprites);
+        frame.draw_points(&points);
+        frame.finish();
+        let pixels = target.read(&rc);
+        for (x, y) in [(16, 16), (48, 16), (16, 48), (48, 48)] {
+            assert_eq!(pixels.at(x, y), [255; 4], "Nothing drawn at ({x}, {y})");
+        }
+        assert_eq!(pixels.at(32, 32), [0, 0, 0, 255]);
+
+        // The painters' own draws attach the stencil buffer too.
+        instanced.draw(&rc, &target.view, None).unwrap();
+        sprites.draw(&rc, &target.view, None).unwrap();
+        points.draw(&rc, &target.view, None).unwrap();
+        assert_eq!(target.read(&rc).at(48, 48), [255; 4]);
+    }
+}
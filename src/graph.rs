@@ -0,0 +1,302 @@
+//! Declarative chains of draws and post-processes, for frames that combine
+//! e.g. a backdrop, rects, bloom and color correction.
+//!
+//! A chain is a list of `Node`s run in order. Draw nodes paint over
+//! whatever the chain has drawn so far; post-process nodes read it and
+//! write a new image for the nodes after them. The `RenderGraph` picks
+//! where each node writes and keeps the intermediate textures between
+//! frames.
+//!
+//! Allocation: a chain without post-processes draws straight into the
+//! output and needs nothing. Otherwise the nodes before the first
+//! post-process draw into an intermediate texture, each post-process reads
+//! one and writes the other, ping-ponging, and the last post-process writes
+//! the output, so draws after it (e.g. UI that shouldn't glow) land on the
+//! output directly. That's one intermediate for a single post-process and
+//! two for more, of the context's size and format, made on first use and
+//! remade when either changes.
+
+use wgpu::{
+    Color, CommandEncoderDescriptor, Extent3d, LoadOp, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
+};
+
+use crate::{point, post, rect, sprite, CheckerSpec, Context, DrawOptions, RenderPassBuilder};
+
+/// One step of a chain. See the module docs.
+pub enum Node<'a> {
+    /// Fills the image so far with a color, discarding it. Clears the
+    /// stencil buffer too, when the context has one.
+    Clear(Color),
+    /// Fills the image so far with a checkerboard. See
+    /// `Context::draw_checkerboard`.
+    Checkerboard(CheckerSpec),
+    Rects(&'a rect::Painter),
+    InstancedRects(&'a rect::InstancePainter),
+    Sprites(&'a sprite::Painter),
+    Points(&'a point::Painter),
+    /// See `Context::apply_post_process`.
+    PostProcess(post::PostProcess),
+    /// See `Context::bloom`.
+    #[cfg(feature = "postfx")]
+    Bloom(post::BloomSettings),
+}
+
+impl Node<'_> {
+    fn is_post_process(&self) -> bool {
+        match self {
+            Node::PostProcess(_) => true,
+            #[cfg(feature = "postfx")]
+            Node::Bloom(_) => true,
+            _ => false,
+        }
+    }
+}
+
+struct Intermediate {
+    /// Kept alive for `view`.
+    _texture: wgpu::Texture,
+    view: TextureView,
+}
+
+/// Runs chains of `Node`s and owns their intermediate textures.
+#[derive(Default)]
+pub struct RenderGraph {
+    intermediates: Vec<Intermediate>,
+    /// What the intermediates were made for.
+    key: Option<(u32, u32, TextureFormat)>,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `nodes` in order, ending up in `output`.
+    ///
+    /// `output` must have the context's size and format. Consecutive draw
+    /// nodes share one render pass, and every step is submitted right
+    /// away. Intermediates keep the last frame's image, so chains with a
+    /// post-process should start with `Clear` or `Checkerboard`.
+    pub fn run(&mut self, rc: &Context, nodes: &[Node], output: &TextureView) {
+        let post_count = nodes.iter().filter(|node| node.is_post_process()).count();
+        self.prepare(rc, post_count.min(2));
+
+        let mut posts_left = post_count;
+        // Which intermediate holds the image so far, or `None` for `output`.
+        let mut current = (post_count > 0).then_some(0);
+        let mut i = 0;
+        while i < nodes.len() {
+            let target = match current {
+                Some(index) => &self.intermediates[index].view,
+                None => output,
+            };
+            if nodes[i].is_post_process() {
+                posts_left -= 1;
+                let next = match current {
+                    Some(index) if posts_left > 0 => Some(1 - index),
+                    _ => None,
+                };
+                let next_target = match next {
+                    Some(index) => &self.intermediates[index].view,
+                    None => output,
+                };
+                run_post_process(rc, &nodes[i], target, next_target);
+                current = next;
+                i += 1;
+            } else if let Node::Checkerboard(spec) = &nodes[i] {
+                rc.draw_checkerboard(target, spec);
+                i += 1;
+            } else {
+                let end = nodes[i + 1..]
+                    .iter()
+                    .position(|node| {
+                        node.is_post_process()
+                            || matches!(node, Node::Clear(_) | Node::Checkerboard(_))
+                    })
+                    .map_or(nodes.len(), |n| i + 1 + n);
+                draw(rc, &nodes[i..end], target);
+                i = end;
+            }
+        }
+    }
+
+    /// Size of the intermediates. An estimate; drivers may pad or align
+    /// them.
+    pub fn gpu_memory_bytes(&self) -> usize {
+        match self.key {
+            Some((width, height, format)) => {
+                let texel = format.block_size(None).unwrap_or(4) as usize;
+                self.intermediates.len() * texel * width as usize * height as usize
+            }
+            None => 0,
+        }
+    }
+
+    /// Makes sure there are `count` intermediates of the context's size and
+    /// format.
+    fn prepare(&mut self, rc: &Context, count: usize) {
+        let key = (rc.config.width.max(1), rc.config.height.max(1), rc.format());
+        if self.key != Some(key) {
+            self.intermediates.clear();
+            self.key = Some(key);
+        }
+        while self.intermediates.len() < count {
+            let (width, height, format) = key;
+            let texture = rc.device.create_texture(&TextureDescriptor {
+                label: Some("Render graph intermediate"),
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            self.intermediates.push(Intermediate {
+                _texture: texture,
+                view,
+            });
+        }
+    }
+}
+
+fn run_post_process(rc: &Context, node: &Node, input: &TextureView, output: &TextureView) {
+    match node {
+        Node::PostProcess(process) => rc.apply_post_process(input, output, process),
+        #[cfg(feature = "postfx")]
+        Node::Bloom(settings) => rc.bloom(input, output, settings),
+        _ => unreachable!("Not a post-process"),
+    }
+}
+
+/// Draws `nodes`, which are draws apart from a leading `Clear`, in one
+/// pass.
+pub(crate) fn draw(rc: &Context, nodes: &[Node], target: &TextureView) {
+    let (pass, nodes) = match nodes {
+        [Node::Clear(color), rest @ ..] => {
+            (RenderPassBuilder::new(target).clear_color(*color), rest)
+        }
+        _ => (
+            RenderPassBuilder::new(target)
+                .load(LoadOp::Load)
+                .depth_stencil_options(DrawOptions {
+                    clear_depth_stencil: false,
+                    ..Default::default()
+                }),
+            nodes,
+        ),
+    };
+    for node in nodes {
+        let view = match node {
+            Node::Rects(painter) => &painter.view,
+            Node::InstancedRects(painter) => &painter.view,
+            Node::Sprites(painter) => &painter.view,
+            Node::Points(painter) => &painter.view,
+            _ => unreachable!("Not a draw"),
+        };
+        view.assert_same_device(rc);
+    }
+    let mut commands = rc
+        .device
+        .create_command_encoder(&CommandEncoderDescriptor::default());
+    for node in nodes {
+        if let Node::InstancedRects(painter) = node {
+            painter.record_culling(rc, &mut commands);
+        }
+    }
+    {
+        let mut pass = pass.begin(rc, &mut commands);
+        for node in nodes {
+            match node {
+                Node::Rects(painter) => painter.paint(&mut pass),
+                Node::InstancedRects(painter) => painter.paint(&mut pass),
+                Node::Sprites(painter) => painter.paint(&mut pass),
+                Node::Points(painter) => painter.paint(&mut pass),
+                _ => unreachable!("Not a draw"),
+            }
+        }
+    }
+    rc.commands.submit(std::iter::once(commands.finish()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        point::Point,
+        post::ColorVisionDeficiency,
+        rect::{Rect, RectInstance},
+        sprite::Sprite,
+        test_util::{stencil_context, Target},
+    };
+
+    /// Every node on a context with a stencil buffer, where each pipeline
+    /// has to match the pass's depth/stencil attachment.
+    #[test]
+    fn draws_each_node_with_a_stencil() {
+        let Some(rc) = stencil_context(64, 64) else {
+            return;
+        };
+        let target = Target::new(&rc);
+
+        let mut rects = rc.make_rect_painter();
+        rects
+            .set_rects(&rc, &[Rect::new([-0.5, 0.5], [0.5, 0.5], 0.0)])
+            .unwrap();
+        rects.set_uniforms(&rc, &Default::default());
+        let mut instanced = rc.make_rect_instance_painter();
+        instanced
+            .set_instances(&rc, &[RectInstance::new([0.5, 0.5], [0.5, 0.5], 0.0)])
+            .unwrap();
+        instanced.set_uniforms(&rc, &Default::default());
+        let mut sprites = rc.make_sprite_painter();
+        sprites
+            .set_sprites(&rc, &[Sprite::new([-0.5, -0.5], [0.5, 0.5], 0)])
+            .unwrap();
+        let mut points = rc.make_point_painter();
+        points
+            .set_points(&rc, &[Point::new([0.5, -0.5], 8.0)])
+            .unwrap();
+
+        let mut graph = RenderGraph::new();
+        graph.run(
+            &rc,
+            &[
+                Node::Clear(Color::BLACK),
+                Node::Rects(&rects),
+                Node::InstancedRects(&instanced),
+                Node::Sprites(&sprites),
+                Node::Points(&points),
+                Node::PostProcess(post::PostProcess::simulate_cvd(
+                    ColorVisionDeficiency::Protanopia,
+                )),
+            ],
+            &target.view,
+        );
+        let pixels = target.read(&rc);
+        for (x, y) in [(16, 16), (48, 16), (16, 48), (48, 48)] {
+            assert!(pixels.at(x, y)[1] > 200, "Nothing drawn at ({x}, {y})");
+        }
+        assert_eq!(pixels.at(32, 32), [0, 0, 0, 255]);
+
+        // Draws after a checkerboard load the stencil rather than clear it.
+        graph.run(
+            &rc,
+            &[
+                Node::Checkerboard(CheckerSpec::default()),
+                Node::Rects(&rects),
+                Node::InstancedRects(&instanced),
+                Node::Sprites(&sprites),
+                Node::Points(&points),
+            ],
+            &target.view,
+        );
+        assert_eq!(target.read(&rc).at(16, 16), [255; 4]);
+    }
+}
@@ -1,25 +1,188 @@
+pub mod anim;
+mod array_target;
+pub mod bench;
+mod blend;
+mod builder;
+mod checker;
+mod color_space;
+mod dirty;
+#[cfg(feature = "egui")]
+pub mod egui;
+mod error;
+mod frame;
+pub mod graph;
+mod options;
+mod pass;
+mod ping_pong;
+pub mod point;
+pub mod post;
+mod present;
 pub mod rect;
+mod screenshot;
+pub mod sprite;
+#[cfg(test)]
+mod test_util;
+#[cfg(feature = "timing")]
+mod timing;
+mod transform;
+mod upload;
+mod util;
+mod view;
 
+use std::sync::{Arc, OnceLock};
+
+pub use array_target::ArrayTarget;
+pub use blend::BlendMode;
+pub use builder::ContextBuilder;
+pub use checker::CheckerSpec;
+pub use color_space::ColorSpace;
+pub use dirty::DirtyFlag;
+pub use error::{BlendError, ContextError, GeometryError, SceneFileError};
+pub use frame::FrameEncoder;
+use log::{debug, error, info};
+pub use options::DrawOptions;
+pub use pass::RenderPassBuilder;
+pub use ping_pong::PingPong;
+pub use present::BufferingInfo;
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+pub use screenshot::{Screenshot, ScreenshotFuture, ScreenshotOptions};
+#[cfg(feature = "timing")]
+pub use timing::FrameLatency;
+pub use transform::Transform2D;
+pub use upload::UploadStrategy;
+pub use view::SurfaceRotation;
+use view::View;
 use wgpu::{
-    Backends, Device, DeviceDescriptor, Instance, InstanceDescriptor, Queue, RequestAdapterOptions,
-    Surface, SurfaceConfiguration, SurfaceError, SurfaceTexture, TextureUsages,
+    Adapter, AdapterInfo, Backend, Backends, Color, CompareFunction, DepthStencilState, Device,
+    DeviceDescriptor, DeviceType, Extent3d, Instance, InstanceDescriptor, Limits, PowerPreference,
+    PresentMode, Queue, RequestAdapterOptions, Surface, SurfaceConfiguration, SurfaceError,
+    SurfaceTexture, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    TextureView, TextureViewDescriptor,
 };
 
 /// Rendering context
+///
+/// # Surfaces and textures
+///
+/// A context either owns a surface or renders into textures for a host.
+///
+/// Owning the surface, as contexts from `ContextBuilder` do, oreb acquires
+/// and presents frames: `get_next_frame` or `try_acquire`, then
+/// `present_frame`, or a painter's `present` for both. Resizing, pausing,
+/// formats and present modes are managed here.
+///
+/// Rendering to textures, oreb never touches a surface, so a host engine can
+/// use it as a sub-renderer and composite the result itself. Make the
+/// context with `from_raw` on the host's device, and draw with
+/// `render_to_texture`, or painters' `draw` and `record_into`, into the
+/// host's textures. The host presents when it's ready; oreb's
+/// frame-acquiring calls panic on a context without a surface, so they
+/// can't happen by mistake. A context that owns a surface can render to
+/// textures too, e.g. for thumbnails, without disturbing its frames.
+///
+/// # Threads
+///
+/// `Context` and the painters are `Send` and `Sync`. Methods that only
+/// need `&Context` can be called from any number of threads at once, so
+/// worker threads can each fill their own painter with `set_geometry`,
+/// `set_rects` or `set_sprites` from a shared `&Context`. wgpu's queue is
+/// thread-safe, so uploads don't serialize on anything oreb adds.
+///
+/// A painter's uploads take `&mut self`, so one painter is filled by one
+/// thread at a time. Draws are submitted in the order the calls happen;
+/// join the workers before drawing so the frame sees all their uploads.
+/// `present` and anything else touching the surface takes `&mut Context`
+/// and happens on one thread.
 pub struct Context {
-    /// Handle to the device we'll use to draw
-    device: Device,
+    /// Handle to the device we'll use to draw.
+    /// Shared so a host renderer can hand us its device (see `from_raw`).
+    device: Arc<Device>,
+
+    /// The device's limits, read once since wgpu hands out copies.
+    limits: Limits,
+
+    /// The adapter's graphics API. `Empty` until a `from_raw` host sets it.
+    backend: Backend,
 
     /// Command queue for the selected device.
-    commands: Queue,
+    commands: Arc<Queue>,
 
-    /// Window surface, render target
-    surface: Surface,
+    /// Window surface, render target.
+    /// `None` when the context was created from a host's device with
+    /// `from_raw`. The host owns presentation in that case.
+    surface: Option<Surface>,
 
     /// Configuration data for the surface.
     /// This is reused during `resize` operations.
     config: SurfaceConfiguration,
+
+    /// The formats the surface supports, for `set_format`. Empty without a
+    /// surface.
+    surface_formats: Vec<TextureFormat>,
+
+    /// The present modes the surface supports, for `set_present_mode`.
+    /// Empty without a surface.
+    present_modes: Vec<PresentMode>,
+
+    /// Depth/stencil buffer matching the surface size.
+    /// Only present when built `with_stencil(true)`.
+    stencil: Option<TextureView>,
+
+    /// Uniforms shared by all painters made from this context.
+    view: Arc<View>,
+
+    orientation: SurfaceRotation,
+
+    /// Applied to every painter's geometry. See `set_view_transform`.
+    view_transform: Transform2D,
+
+    /// Physical pixels per logical pixel. See `set_scale_factor`.
+    scale_factor: f32,
+
+    /// Whether geometry is in logical pixels. See `set_logical_coordinates`.
+    logical_coordinates: bool,
+
+    /// What painters clear to when a draw doesn't say.
+    clear_color: Color,
+
+    /// Overrides the format's color space. See `set_output_color_space`.
+    output_color_space: Option<ColorSpace>,
+
+    /// Cleared with `set_active` to stop presenting.
+    active: bool,
+
+    /// Set by a zero-sized `resize`, until a non-zero one.
+    minimized: bool,
+
+    /// Frames presented through `present_frame`.
+    frame_index: u64,
+
+    /// Bumped by `switch_adapter`. See `device_generation`.
+    device_generation: u64,
+
+    /// See `needs_redraw`.
+    dirty: DirtyFlag,
+
+    /// Made on first use by `apply_post_process`.
+    post: OnceLock<post::Pipeline>,
+
+    /// Made on first use by `upload_texture_with_mips`.
+    mips: OnceLock<sprite::mips::Generator>,
+
+    /// Made on first use by `draw_checkerboard`.
+    checker: OnceLock<checker::Checkerboard>,
+
+    /// Made on first use by `SpriteTexture::from_yuv`.
+    #[cfg(feature = "video")]
+    yuv: OnceLock<sprite::yuv::Converter>,
+
+    /// Made on first use by `bloom`.
+    #[cfg(feature = "postfx")]
+    bloom: OnceLock<post::bloom::Bloom>,
+
+    /// Behind a lock since `get_next_frame` takes `&self`.
+    #[cfg(feature = "timing")]
+    timing: std::sync::Mutex<timing::FrameTimer>,
 }
 
 impl Context {
@@ -27,81 +190,1226 @@ impl Context {
     where
         W: HasRawWindowHandle + HasRawDisplayHandle,
     {
+        ContextBuilder::new().build(window, width, height).await
+    }
+
+    /// Like `with_window`, sized to the window's `inner_size()` and with
+    /// its `scale_factor()` (see `set_scale_factor`).
+    ///
+    /// Only with the `winit` feature. The rest of oreb takes any window
+    /// with raw handles and doesn't depend on winit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context can't be made; see `ContextBuilder::try_build`
+    /// for the fallible version.
+    #[cfg(feature = "winit")]
+    pub async fn for_window(window: &winit::window::Window) -> Self {
+        let winit::dpi::PhysicalSize { width, height } = window.inner_size();
+        let mut rc = Self::with_window(window, width, height).await;
+        rc.set_scale_factor(window.scale_factor() as f32);
+        rc
+    }
+
+    pub fn builder() -> ContextBuilder {
+        ContextBuilder::new()
+    }
+
+    /// A context without a window, on the best adapter there is, for
+    /// offscreen rendering and benchmarks (see `bench`).
+    ///
+    /// Like `headless_software` otherwise, and fails with
+    /// `ContextError::NoAdapter` when there's no adapter at all.
+    pub async fn headless(width: u32, height: u32) -> Result<Self, ContextError> {
         let instance = Instance::new(InstanceDescriptor {
             backends: Backends::all(),
             ..Default::default()
         });
-
-        let surface = unsafe { instance.create_surface(window) }.unwrap();
-
         let adapter = instance
             .request_adapter(&RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
+                power_preference: PowerPreference::HighPerformance,
                 force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
+                compatible_surface: None,
             })
             .await
-            .unwrap();
+            .ok_or(ContextError::NoAdapter)?;
+        Self::headless_on(adapter, width, height).await
+    }
 
-        let (device, commands) = adapter
-            .request_device(
-                &DeviceDescriptor {
-                    label: None,
-                    features: Default::default(),
-                    limits: Default::default(),
-                },
-                None,
-            )
+    /// A context without a window, on a software rasterizer, for
+    /// reproducible rendering in tests and CI containers without a GPU.
+    ///
+    /// Painters draw into textures of `format()`, which is
+    /// `Rgba8UnormSrgb`, sized `width` by `height` to match; read them back
+    /// with `read_pixels`. Output from one software rasterizer is
+    /// deterministic, but may differ slightly between rasterizers and their
+    /// versions, so pin the CI image for golden-image tests.
+    ///
+    /// Software adapters ship with:
+    ///
+    /// - Linux: Mesa's llvmpipe (GL) and lavapipe (Vulkan), e.g. from the
+    ///   `mesa-vulkan-drivers` and `libgl1-mesa-dri` packages.
+    /// - Windows: WARP, through DX12, on Windows 10 and later.
+    /// - macOS: none. Metal always runs on the GPU.
+    ///
+    /// Fails with `ContextError::NoSoftwareAdapter` when there isn't one,
+    /// and `ContextError::TextureTooLarge` when a side is over the
+    /// adapter's `max_texture_dimension()`, as the targets couldn't be
+    /// made.
+    pub async fn headless_software(width: u32, height: u32) -> Result<Self, ContextError> {
+        let instance = Instance::new(InstanceDescriptor {
+            backends: Backends::all(),
+            ..Default::default()
+        });
+        // Not every backend answers force_fallback_adapter, e.g. GL lists
+        // llvmpipe as an ordinary CPU adapter. Look for one of those too.
+        let adapter = match instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference: Default::default(),
+                force_fallback_adapter: true,
+                compatible_surface: None,
+            })
             .await
-            .unwrap();
-
-        let config = {
-            let caps = surface.get_capabilities(&adapter);
-            // pick an srgb format if available
-            let format = caps
-                .formats
-                .iter()
-                .filter(|&f| f.is_srgb())
-                .copied()
-                .next()
-                .unwrap_or(caps.formats[0]);
-            SurfaceConfiguration {
-                usage: TextureUsages::RENDER_ATTACHMENT,
-                format,
-                width: width.min(2),
-                height: height.min(2),
-                present_mode: caps.present_modes[0],
-                alpha_mode: caps.alpha_modes[0],
-                view_formats: Default::default(),
-            }
+        {
+            Some(adapter) => adapter,
+            None => instance
+                .enumerate_adapters(Backends::all())
+                .find(|adapter| adapter.get_info().device_type == DeviceType::Cpu)
+                .ok_or(ContextError::NoSoftwareAdapter)?,
         };
-        surface.configure(&device, &config);
+        Self::headless_on(adapter, width, height).await
+    }
+
+    async fn headless_on(adapter: Adapter, width: u32, height: u32) -> Result<Self, ContextError> {
+        let info = adapter.get_info();
+        info!("Using adapter {:?} ({:?})", info.name, info.backend);
+
+        let (device, queue) = adapter
+            .request_device(&DeviceDescriptor::default(), None)
+            .await?;
+        let max = device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(ContextError::TextureTooLarge {
+                requested: width.max(height),
+                max,
+            });
+        }
+        let mut rc = Self::from_raw(
+            Arc::new(device),
+            Arc::new(queue),
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        rc.set_backend(info.backend);
+        rc.resize(width, height);
+        Ok(rc)
+    }
+
+    /// The adapters on every backend, in the order `switch_adapter` and
+    /// `ContextBuilder::adapter_index` count them. Some may not be able to
+    /// draw to a given window, and one GPU can show up once per backend.
+    pub fn available_adapters() -> Vec<AdapterInfo> {
+        let instance = Instance::new(InstanceDescriptor {
+            backends: Backends::all(),
+            ..Default::default()
+        });
+        instance
+            .enumerate_adapters(Backends::all())
+            .map(|adapter| adapter.get_info())
+            .collect()
+    }
+
+    /// Rebuilds the context on the adapter at `index` in
+    /// `available_adapters()`, e.g. to compare an integrated GPU against a
+    /// discrete one. `window` is the one the context was made for.
+    ///
+    /// The device, queue and surface are replaced, so everything made from
+    /// the old device stops working: painters, textures, screenshots in
+    /// flight, and anything a host made on the old device. Recreate
+    /// them afterwards; `device_generation()` changes so code holding them
+    /// can tell. The stencil buffer, sRGB preference, present mode, clear
+    /// color, orientation and scale factor carry over; the present mode
+    /// falls back like `ContextBuilder::present_mode` if the new surface
+    /// lacks it.
+    ///
+    /// Fails like `ContextBuilder::try_build` with `adapter_index`, and
+    /// leaves the context as it was in that case.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context has no surface (see `from_raw`).
+    pub async fn switch_adapter<W>(
+        &mut self,
+        index: usize,
+        window: &W,
+        width: u32,
+        height: u32,
+    ) -> Result<(), ContextError>
+    where
+        W: HasRawWindowHandle + HasRawDisplayHandle,
+    {
+        assert!(
+            self.surface.is_some(),
+            "Only contexts with a surface can switch adapters"
+        );
+        let mut rc = ContextBuilder::new()
+            .adapter_index(index)
+            .with_stencil(self.has_stencil())
+            .prefer_srgb(self.format().is_srgb())
+            .prefer_wide_gamut(self.format() == TextureFormat::Rgba16Float)
+            .present_mode(self.present_mode())
+            .clear_color(self.clear_color)
+            .try_build(window, width, height)
+            .await?;
+        // Keeps a format picked with `set_format` where the new adapter
+        // supports it; the sRGB preference above covers the rest.
+        let _ = rc.set_format(self.format());
+        rc.resize(width, height);
+        rc.set_orientation(self.orientation);
+        rc.scale_factor = self.scale_factor;
+        rc.logical_coordinates = self.logical_coordinates;
+        rc.output_color_space = self.output_color_space;
+        rc.set_view_transform(&self.view_transform);
+        rc.active = self.active;
+        rc.frame_index = self.frame_index;
+        rc.device_generation = self.device_generation + 1;
+        *self = rc;
+        Ok(())
+    }
 
+    /// Whether something changed since the last frame was acquired, for
+    /// skipping redraws of static content (see `DirtyFlag`).
+    ///
+    /// Set by `mark_dirty` or the `dirty_flag()` handle, and by `resize`,
+    /// `reset`, `set_format`, `set_orientation` and `set_view_transform`.
+    /// Contexts start dirty, so the first frame is drawn. The flag is
+    /// cleared when `get_next_frame`, or a painter's `present`, acquires a
+    /// frame.
+    pub fn needs_redraw(&self) -> bool {
+        self.dirty.is_set()
+    }
+
+    pub fn mark_dirty(&self) {
+        self.dirty.set();
+    }
+
+    /// A handle to the flag behind `needs_redraw`, for code that changes
+    /// what's drawn without access to the context.
+    pub fn dirty_flag(&self) -> DirtyFlag {
+        self.dirty.clone()
+    }
+
+    /// Counts `switch_adapter` calls. Painters and other GPU resources
+    /// made when it had another value belong to a device that's gone.
+    pub fn device_generation(&self) -> u64 {
+        self.device_generation
+    }
+
+    /// Wraps a device and queue owned by a host renderer.
+    ///
+    /// The context has no surface. Painters made from it target textures of
+    /// `format`, and the host is responsible for acquiring and presenting
+    /// frames.
+    pub fn from_raw(device: Arc<Device>, queue: Arc<Queue>, format: TextureFormat) -> Self {
+        let view = Arc::new(View::new(&device, &queue));
         Self {
+            limits: device.limits(),
+            backend: Backend::Empty,
             device,
-            commands,
-            surface,
-            config,
+            commands: queue,
+            surface: None,
+            stencil: None,
+            view,
+            orientation: SurfaceRotation::default(),
+            view_transform: Transform2D::IDENTITY,
+            scale_factor: 1.0,
+            logical_coordinates: false,
+            clear_color: Color::BLACK,
+            output_color_space: None,
+            active: true,
+            minimized: false,
+            frame_index: 0,
+            device_generation: 0,
+            dirty: dirty_from_start(),
+            post: OnceLock::new(),
+            mips: OnceLock::new(),
+            checker: OnceLock::new(),
+            #[cfg(feature = "video")]
+            yuv: OnceLock::new(),
+            #[cfg(feature = "postfx")]
+            bloom: OnceLock::new(),
+            #[cfg(feature = "timing")]
+            timing: Default::default(),
+            config: SurfaceConfiguration {
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                format,
+                width: 0,
+                height: 0,
+                present_mode: PresentMode::Fifo,
+                alpha_mode: Default::default(),
+                view_formats: Default::default(),
+            },
+            surface_formats: Vec::new(),
+            present_modes: Vec::new(),
         }
     }
 
+    /// Resizes the surface, and the stencil buffer if there is one, to
+    /// `width` by `height` physical pixels.
+    ///
+    /// A zero side, which winit reports for a minimized window on some
+    /// platforms, marks the context minimized instead (see
+    /// `is_minimized`), keeping the last size; the next non-zero resize
+    /// reconfigures the surface and restores it. So forwarding every
+    /// `WindowEvent::Resized` as is, minimize and restore included, is all
+    /// the wiring it takes:
+    ///
+    /// - `Resized(size)` calls `rc.resize(size.width, size.height)`.
+    /// - `RedrawRequested` draws and presents as usual. While minimized,
+    ///   `get_next_frame` and `try_acquire` return `None` and painters'
+    ///   `present` does nothing, so nothing is drawn and no surface error
+    ///   comes up.
+    /// - Optionally, skip `request_redraw` while `is_minimized()`, so the
+    ///   loop idles until the window comes back.
     pub fn resize(&mut self, width: u32, height: u32) {
-        if width > 0 && height > 0 {
+        self.mark_dirty();
+        self.minimized = width == 0 || height == 0;
+        if !self.minimized {
             self.config.width = width;
             self.config.height = height;
-            self.surface.configure(&self.device, &self.config);
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
+            if self.stencil.is_some() {
+                self.stencil = Some(make_stencil(&self.device, width, height));
+            }
+            if self.logical_coordinates {
+                self.write_view();
+            }
         }
     }
 
+    /// Switches the surface to `format`, e.g. between an sRGB format and a
+    /// float one for HDR, and reconfigures it. The next frame acquired is
+    /// in the new format.
+    ///
+    /// Fails with `ContextError::UnsupportedFormat` if the surface doesn't
+    /// support `format`. A `from_raw` context has no surface to check, so
+    /// any format is taken; that's the format painters made afterwards
+    /// target.
+    ///
+    /// Pipelines the context owns (post-processing, the checkerboard,
+    /// bloom) are remade on their next use. Painters aren't tracked by
+    /// their context, so existing ones keep drawing in the old format,
+    /// which is a validation error on the new frames, until they're passed
+    /// to their `rebuild_for_format`.
+    pub fn set_format(&mut self, format: TextureFormat) -> Result<(), ContextError> {
+        if self.surface.is_some() && !self.surface_formats.contains(&format) {
+            return Err(ContextError::UnsupportedFormat {
+                format,
+                supported: self.surface_formats.clone(),
+            });
+        }
+        if format == self.config.format {
+            return Ok(());
+        }
+        self.config.format = format;
+        self.post.take();
+        self.checker.take();
+        #[cfg(feature = "postfx")]
+        self.bloom.take();
+        self.reset();
+        Ok(())
+    }
+
+    /// The formats `set_format` accepts: those the surface supports. Empty
+    /// without a surface.
+    pub fn surface_formats(&self) -> &[TextureFormat] {
+        &self.surface_formats
+    }
+
     pub fn reset(&self) {
-        self.surface.configure(&self.device, &self.config);
+        self.mark_dirty();
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+
+    /// The next frame to draw into. `None` while the context is paused
+    /// (see `set_active`) or minimized (see `is_minimized`).
+    ///
+    /// Blocks until a swapchain image is free, which can take a while when
+    /// the GPU is behind, and reports a timeout as an error. See
+    /// `try_acquire` for dropping the frame instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context has no surface (see `from_raw`).
+    pub fn get_next_frame(&self) -> Result<Option<SurfaceTexture>, SurfaceError> {
+        let surface = self
+            .surface
+            .as_ref()
+            .expect("Context has no surface to draw to");
+        if !self.active || self.minimized {
+            return Ok(None);
+        }
+        #[cfg(feature = "timing")]
+        let start = std::time::Instant::now();
+        let frame = surface.get_current_texture()?;
+        self.dirty.take();
+        #[cfg(feature = "timing")]
+        self.timing.lock().unwrap().acquired(start);
+        Ok(Some(frame))
+    }
+
+    /// Acquires the next frame, or drops it, returning `None`, where
+    /// `get_next_frame` would wait longer or fail, so a real-time app can
+    /// skip a frame and keep handling input.
+    ///
+    /// wgpu has no acquire that returns straight away. When the GPU is
+    /// behind and every swapchain image is in use, acquisition waits for
+    /// one up to the backend's timeout (about a second on Vulkan); that
+    /// times out here as a dropped frame instead of an error. A lost or
+    /// outdated surface is reconfigured and the frame dropped too, rather
+    /// than retried, so the next call gets a fresh image. Other errors,
+    /// such as running out of memory, are logged and also give `None`;
+    /// call `get_next_frame` to handle them. Paused and minimized contexts
+    /// give `None` as well.
+    ///
+    /// Waiting mostly comes from frames being held: acquire as late as
+    /// possible, after input and uploads, present right after drawing,
+    /// and don't keep a `SurfaceTexture` across frames. A present mode
+    /// with more buffering (see `present_mode_buffering`) leaves more
+    /// images to acquire at the cost of latency.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context has no surface (see `from_raw`).
+    pub fn try_acquire(&mut self) -> Option<SurfaceTexture> {
+        match self.get_next_frame() {
+            Ok(frame) => frame,
+            Err(SurfaceError::Timeout) => {
+                debug!("Dropping a frame: acquiring the surface timed out");
+                None
+            }
+            Err(e @ (SurfaceError::Lost | SurfaceError::Outdated)) => {
+                debug!("Dropping a frame: {e}");
+                self.reset();
+                None
+            }
+            Err(e) => {
+                error!("Dropping a frame: {e}");
+                None
+            }
+        }
+    }
+
+    /// Like `get_next_frame`, but if the surface is lost or outdated it's
+    /// reconfigured and acquisition is retried once.
+    pub(crate) fn acquire_frame(&mut self) -> Result<Option<SurfaceTexture>, SurfaceError> {
+        match self.get_next_frame() {
+            Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+                self.reset();
+                self.get_next_frame()
+            }
+            result => result,
+        }
+    }
+
+    /// Presents `frame` and advances `frame_index`.
+    ///
+    /// Painters' `present` goes through this. Use it instead of
+    /// `SurfaceTexture::present` on frames from `get_next_frame` to keep the
+    /// count.
+    pub fn present_frame(&mut self, frame: SurfaceTexture) {
+        #[cfg(feature = "timing")]
+        let start = std::time::Instant::now();
+        frame.present();
+        #[cfg(feature = "timing")]
+        self.timing.get_mut().unwrap().presented(start);
+        self.frame_index += 1;
+    }
+
+    /// How long the last frame presented with `present_frame` (or a
+    /// painter's `present`) took to acquire, draw and present. `None`
+    /// before the first one. See `FrameLatency` for what the times do and
+    /// don't cover on each backend.
+    ///
+    /// Taking the timestamps costs a few clock reads and an uncontended
+    /// lock per frame, and only happens with the `timing` feature.
+    #[cfg(feature = "timing")]
+    pub fn last_frame_latency(&self) -> Option<FrameLatency> {
+        self.timing.lock().unwrap().last()
+    }
+
+    /// The number of frames presented so far, starting from 0. Use it to
+    /// pick per-frame resources out of a ring buffer, e.g.
+    /// `frame_index % 3` with triple buffering.
+    ///
+    /// Only frames presented with `present_frame` or a painter's `present`
+    /// are counted; presenting a frame from `get_next_frame` directly
+    /// doesn't advance it. Paused and minimized frames aren't counted either.
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    /// Pauses or resumes presenting frames.
+    ///
+    /// While paused, `get_next_frame` returns `None` and painters' `present`
+    /// does nothing, so an app can keep calling them when its window is
+    /// hidden or minimized without doing GPU work. Painters and other GPU
+    /// resources are kept. Contexts start active.
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Whether the last `resize` had a zero side, as for a minimized
+    /// window. Frames aren't acquired until a resize to a real size, which
+    /// reconfigures the surface. Independent of `set_active`: either one
+    /// stops frames.
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    /// Sets what painters' `draw` and `present` clear to when they're
+    /// passed `None` for the clear color, so an app that rarely changes it
+    /// can set it once. Black by default, or what
+    /// `ContextBuilder::clear_color` gave.
+    ///
+    /// A color passed to the draw always wins over this one.
+    pub fn set_clear_color(&mut self, color: Color) {
+        self.clear_color = color;
+    }
+
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
+    }
+
+    /// Rotates everything drawn by this context's painters.
+    ///
+    /// Use this when the display rotates so content follows the device
+    /// without re-laying-out geometry. It's applied after any per-rect
+    /// placement, in clip space, so it composes with whatever coordinates
+    /// the geometry was built in.
+    ///
+    /// This only rotates; it doesn't resize the surface. Platforms usually
+    /// report a resize along with the rotation, which should still go
+    /// through `resize`.
+    pub fn set_orientation(&mut self, rotation: SurfaceRotation) {
+        self.mark_dirty();
+        self.orientation = rotation;
+        self.write_view();
+    }
+
+    pub fn orientation(&self) -> SurfaceRotation {
+        self.orientation
+    }
+
+    /// Transforms the geometry of every painter made from this context, a
+    /// camera shared by all of them. The identity by default.
+    ///
+    /// It's applied after each painter's own `set_transform` and before
+    /// the orientation, and lives in the view uniforms every painter binds
+    /// as group 1 (group 0 holds the painter's own settings, transform and
+    /// textures), so panning or zooming a scene drawn by many painters is
+    /// one small upload, however many painters there are. Keep per-painter
+    /// transforms for placing layers relative to the camera, or leave them
+    /// at the identity.
+    ///
+    /// Like other uploads, it lands before the next submission. Painters'
+    /// draws submit as they go, so it can change between them, but passes
+    /// recorded on a host's encoder with `record_into` all see the value
+    /// set last before the host submits. Post effects, the checkerboard and
+    /// other fullscreen passes ignore it.
+    pub fn set_view_transform(&mut self, transform: &Transform2D) {
+        self.mark_dirty();
+        self.view_transform = *transform;
+        self.write_view();
+    }
+
+    pub fn view_transform(&self) -> Transform2D {
+        self.view_transform
+    }
+
+    /// Places every painter's geometry in logical pixels instead of clip
+    /// space: `[0, 0]` is the surface's top left corner, y points down,
+    /// and `[width, height]` over the scale factor is the bottom right.
+    /// Off by default.
+    ///
+    /// The mapping to clip space is `logical_pixel_transform`, applied after
+    /// the view transform, so a camera set with `set_view_transform` moves
+    /// in logical pixels too. `resize` and `set_scale_factor` keep it up to
+    /// date, so content keeps its logical size and place when the window
+    /// moves to a display with another scale factor, covering more physical
+    /// pixels at the higher one. Rects' `line_width_px` and corner radii
+    /// stay in physical pixels.
+    ///
+    /// With y pointing down, the mapping mirrors, so triangles that are
+    /// counter-clockwise on screen are clockwise in logical coordinates.
+    /// Rect painters need `set_reverse_winding(true)` for `set_rects`, and
+    /// geometry given directly has to be wound clockwise, or it's culled.
+    ///
+    /// A cursor position from winit, in physical pixels, maps to geometry
+    /// coordinates with `physical_to_logical` (ignoring any camera).
+    pub fn set_logical_coordinates(&mut self, logical: bool) {
+        self.mark_dirty();
+        self.logical_coordinates = logical;
+        self.write_view();
     }
 
-    pub fn get_next_frame(&self) -> Result<SurfaceTexture, SurfaceError> {
-        self.surface.get_current_texture()
+    pub fn logical_coordinates(&self) -> bool {
+        self.logical_coordinates
+    }
+
+    /// The mapping from logical pixels to clip space for the current size
+    /// and scale factor. See `set_logical_coordinates`. The identity until
+    /// the context has a size.
+    pub fn logical_pixel_transform(&self) -> Transform2D {
+        let (w, h) = (self.config.width as f32, self.config.height as f32);
+        if w == 0.0 || h == 0.0 {
+            return Transform2D::IDENTITY;
+        }
+        let s = self.scale_factor;
+        Transform2D::from_cols([2.0 * s / w, 0.0], [0.0, -2.0 * s / h], [-1.0, 1.0])
+    }
+
+    /// Uploads the view transform, logical pixel mapping and orientation.
+    fn write_view(&self) {
+        let camera = if self.logical_coordinates {
+            self.view_transform.then(&self.logical_pixel_transform())
+        } else {
+            self.view_transform
+        };
+        self.view.write(&self.commands, self.orientation, &camera);
+    }
+
+    /// Width over height of the surface, or of the size last passed to
+    /// `resize` for contexts made with `from_raw`.
+    ///
+    /// 1 until the context has a size. See `Transform2D::aspect_correct`.
+    pub fn aspect_ratio(&self) -> f32 {
+        if self.config.width == 0 || self.config.height == 0 {
+            1.0
+        } else {
+            self.config.width as f32 / self.config.height as f32
+        }
+    }
+
+    /// Records the display's scale factor, physical pixels per logical
+    /// pixel, for the conversions below and `set_logical_coordinates`. 1
+    /// by default.
+    ///
+    /// With winit, pass `window.scale_factor()` after creating the context
+    /// and the new factor on `WindowEvent::ScaleFactorChanged`, alongside
+    /// the `resize` to `new_inner_size`. The surface is always sized in
+    /// physical pixels, so a window keeping its logical size on a display
+    /// at twice the scale has twice the width and height. Clip space
+    /// geometry stretches with the surface either way; geometry in logical
+    /// coordinates keeps its logical size, and any other painting is
+    /// unaffected by the factor. winit reports
+    /// `CursorMoved` positions in physical pixels, ready for
+    /// `pixel_to_clip`; toolkits that hand out logical positions go
+    /// through `logical_to_physical` first.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `scale_factor` is finite and positive.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        assert!(
+            scale_factor.is_finite() && scale_factor > 0.0,
+            "scale_factor must be finite and positive but was {scale_factor}"
+        );
+        self.scale_factor = scale_factor;
+        if self.logical_coordinates {
+            self.mark_dirty();
+            self.write_view();
+        }
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    pub fn logical_to_physical(&self, p: [f32; 2]) -> [f32; 2] {
+        p.map(|e| e * self.scale_factor)
+    }
+
+    pub fn physical_to_logical(&self, p: [f32; 2]) -> [f32; 2] {
+        p.map(|e| e / self.scale_factor)
+    }
+
+    /// Maps a position in physical pixels to clip space, where rects are
+    /// placed, using the current surface size.
+    ///
+    /// Pixels count from the top left corner with y pointing down, while
+    /// clip space y points up, so y flips: `[0, 0]` maps to `[-1, 1]`,
+    /// `[width, height]` to `[1, -1]` and the center of the surface to the
+    /// origin. Pixel centers are at half-integer positions.
+    ///
+    /// Painter transforms, `set_view_transform` and `set_orientation`
+    /// aren't undone; invert those separately when they're in use. Returns
+    /// the input unchanged until the context has a size.
+    pub fn pixel_to_clip(&self, p: [f32; 2]) -> [f32; 2] {
+        let (w, h) = (self.config.width as f32, self.config.height as f32);
+        if w == 0.0 || h == 0.0 {
+            return p;
+        }
+        [2.0 * p[0] / w - 1.0, 1.0 - 2.0 * p[1] / h]
+    }
+
+    /// The inverse of `pixel_to_clip`.
+    pub fn clip_to_pixel(&self, p: [f32; 2]) -> [f32; 2] {
+        let (w, h) = (self.config.width as f32, self.config.height as f32);
+        if w == 0.0 || h == 0.0 {
+            return p;
+        }
+        [0.5 * (p[0] + 1.0) * w, 0.5 * (1.0 - p[1]) * h]
+    }
+
+    /// Bytes held by resources the context owns: the stencil buffer and
+    /// shared uniforms. An estimate for diagnostics.
+    ///
+    /// Painters and textures made from the context aren't tracked by it and
+    /// report their own `gpu_memory_bytes`. The surface's swapchain images
+    /// belong to the platform and aren't counted.
+    pub fn gpu_memory_bytes(&self) -> usize {
+        // Depth24PlusStencil8 is usually stored as 32 bits per texel.
+        let stencil = if self.has_stencil() {
+            4 * self.config.width as usize * self.config.height as usize
+        } else {
+            0
+        };
+        #[cfg(feature = "postfx")]
+        let stencil = stencil + self.bloom.get().map_or(0, |b| b.gpu_memory_bytes());
+        stencil + self.view.gpu_memory_bytes()
+    }
+
+    /// What the device supports. Painters check geometry against
+    /// `max_buffer_size` and fail with `GeometryError::TooLarge` rather
+    /// than hitting a wgpu validation error; check here to size geometry
+    /// before building it.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// The largest width or height a texture can have on this device, e.g.
+    /// for render targets and exports. Often 8192 or 16384; wgpu only
+    /// guarantees 2048 on downlevel devices.
+    pub fn max_texture_dimension(&self) -> u32 {
+        self.limits.max_texture_dimension_2d
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        self.config.present_mode
+    }
+
+    /// Switches the surface to `mode` and reconfigures it, e.g. to
+    /// `Immediate` to measure uncapped frame rates.
+    ///
+    /// Fails with `ContextError::UnsupportedPresentMode` if the surface
+    /// doesn't support `mode`, leaving the mode as it was, rather than
+    /// staying vsynced without saying so. The `Auto*` modes are always
+    /// accepted. See `BufferingInfo` for which platforms have which modes.
+    /// A `from_raw` context has no surface, so only records the mode.
+    pub fn set_present_mode(&mut self, mode: PresentMode) -> Result<(), ContextError> {
+        if self.surface.is_some() && !present::is_supported(&self.present_modes, mode) {
+            return Err(ContextError::UnsupportedPresentMode {
+                mode,
+                supported: self.present_modes.clone(),
+            });
+        }
+        if mode != self.config.present_mode {
+            self.config.present_mode = mode;
+            self.reset();
+        }
+        Ok(())
+    }
+
+    /// The present modes `set_present_mode` accepts besides the `Auto*`
+    /// ones: those the surface supports. Empty without a surface.
+    pub fn supported_present_modes(&self) -> &[PresentMode] {
+        &self.present_modes
+    }
+
+    /// How the surface is buffered under its present mode. Pick the mode
+    /// with `ContextBuilder::present_mode`.
+    ///
+    /// For contexts from `from_raw`, which have no surface, this describes
+    /// `Fifo`.
+    pub fn present_mode_buffering(&self) -> BufferingInfo {
+        BufferingInfo::new(self.config.present_mode)
+    }
+
+    /// The graphics API the context's adapter runs on. Painters pick their
+    /// shader variant by it (see `rect::ShaderVariant`).
+    ///
+    /// `Backend::Empty` for `from_raw` contexts, which don't see the
+    /// adapter, until the host calls `set_backend`.
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Tells a `from_raw` context which graphics API its device runs on,
+    /// e.g. from the host's `Adapter::get_info()`. Affects painters made
+    /// afterwards.
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    /// The format painters made from this context render to: the surface's,
+    /// or the one given to `from_raw`.
+    pub fn format(&self) -> TextureFormat {
+        self.config.format
+    }
+
+    /// How consumers of the output, e.g. a compositor or video encoder,
+    /// should read it, and so which space painter colors are in. See
+    /// `ColorSpace`.
+    ///
+    /// Derived from `format()` unless tagged with `set_output_color_space`.
+    pub fn output_color_space(&self) -> ColorSpace {
+        self.output_color_space
+            .unwrap_or_else(|| ColorSpace::for_format(self.format()))
+    }
+
+    /// Tags the output as being in `color_space`, or goes back to deriving
+    /// it from the format for `None`, e.g. for a host whose target feeds a
+    /// Display P3 encoder.
+    ///
+    /// Only the tag changes: nothing is converted, and surfaces aren't
+    /// reconfigured, so it's up to the host to make the tag true and to
+    /// give painters colors in that space.
+    pub fn set_output_color_space(&mut self, color_space: impl Into<Option<ColorSpace>>) {
+        self.output_color_space = color_space.into();
+    }
+
+    pub fn has_stencil(&self) -> bool {
+        self.stencil.is_some()
     }
 
     pub fn make_rect_painter(&self) -> rect::Painter {
         rect::Painter::new(self)
     }
+
+    pub fn make_rect_instance_painter(&self) -> rect::InstancePainter {
+        rect::InstancePainter::new(self)
+    }
+
+    pub fn make_sprite_painter(&self) -> sprite::Painter {
+        sprite::Painter::new(self)
+    }
+
+    /// A painter for many small squares or dots, e.g. scatter plots and
+    /// particles.
+    pub fn make_point_painter(&self) -> point::Painter {
+        point::Painter::new(self)
+    }
+
+    /// Starts drawing a frame of several painters into `target`, which
+    /// must have the context's format. The first draw clears it to
+    /// `clear_color`, or the context's `clear_color()` for `None`, and the
+    /// rest draw over it. See `FrameEncoder`.
+    pub fn begin_frame<'a>(
+        &'a self,
+        target: &'a TextureView,
+        clear_color: impl Into<Option<Color>>,
+    ) -> FrameEncoder<'a> {
+        let clear_color = clear_color.into().unwrap_or(self.clear_color());
+        FrameEncoder::new(self, target, clear_color)
+    }
+
+    /// Draws a frame into `target` with `draw`, for hosts that composite
+    /// and present it themselves. See "Surfaces and textures" on `Context`.
+    ///
+    /// Like `begin_frame`, the first draw clears `target` to `clear_color`
+    /// (or the context's `clear_color()` for `None`), and it's cleared if
+    /// `draw` draws nothing. The surface, if any, isn't touched and
+    /// `frame_index` doesn't advance. The flag behind `needs_redraw` is
+    /// cleared first, as acquiring a surface frame would, so changes made
+    /// while drawing leave it set. Returns what `draw` does.
+    pub fn render_to_texture<R>(
+        &self,
+        target: &TextureView,
+        clear_color: impl Into<Option<Color>>,
+        draw: impl FnOnce(&mut FrameEncoder) -> R,
+    ) -> R {
+        self.dirty.take();
+        let mut frame = self.begin_frame(target, clear_color);
+        let result = draw(&mut frame);
+        frame.finish();
+        result
+    }
+
+    /// Runs `process` over `input`, writing the result to `output`.
+    ///
+    /// `output` must have the context's format and the same size as
+    /// `input`, and they must be different textures. `input` needs
+    /// `TEXTURE_BINDING` usage. The work is submitted right away.
+    pub fn apply_post_process(
+        &self,
+        input: &TextureView,
+        output: &TextureView,
+        process: &post::PostProcess,
+    ) {
+        self.post
+            .get_or_init(|| post::Pipeline::new(self))
+            .run(self, input, output, process);
+    }
+
+    /// Fills `target` with a checkerboard, the usual backdrop for showing
+    /// transparency, e.g. before drawing translucent rects over it with
+    /// `LoadOp::Load`.
+    ///
+    /// Cells are counted in px from the target's top left corner, so the
+    /// pattern stays put under any painter transform. `target` must have
+    /// the context's format. The work is submitted right away.
+    pub fn draw_checkerboard(&self, target: &TextureView, spec: &CheckerSpec) {
+        self.checker
+            .get_or_init(|| checker::Checkerboard::new(self))
+            .run(self, target, spec);
+    }
+
+    /// Shorthand for `apply_post_process` with
+    /// `PostProcess::simulate_cvd(kind)`.
+    ///
+    /// The simulation is done on linear color. Use an sRGB `input`, which
+    /// decodes to linear when read, or one that holds linear values.
+    pub fn simulate_cvd(
+        &self,
+        input: &TextureView,
+        output: &TextureView,
+        kind: post::ColorVisionDeficiency,
+    ) {
+        self.apply_post_process(input, output, &post::PostProcess::simulate_cvd(kind));
+    }
+
+    /// Adds a glow around the bright parts of `input`, writing the result
+    /// to `output`.
+    ///
+    /// Color above `settings.threshold` is blurred by repeatedly halving it
+    /// and scaling it back up, then added to the image. `input` and
+    /// `output` must be the context's size, and otherwise follow the rules
+    /// of `apply_post_process`. Use an sRGB `input` so thresholds apply to
+    /// linear color. The work is submitted right away.
+    ///
+    /// The intermediate textures are made on first use and remade when the
+    /// context is resized. They're half-float and add up to about two
+    /// thirds of a 32-bit image of the context's size. Each call does
+    /// `2 * radius` passes, all but the last at half size or less, so
+    /// the cost is a few fullscreen copies' worth of fill rate.
+    #[cfg(feature = "postfx")]
+    pub fn bloom(&self, input: &TextureView, output: &TextureView, settings: &post::BloomSettings) {
+        self.bloom
+            .get_or_init(|| post::bloom::Bloom::new(self))
+            .run(self, input, output, settings);
+    }
+
+    /// Copies `texture` back to the CPU, blocking until it's done.
+    ///
+    /// `texture` needs `COPY_SRC` usage. Surface frames only have it if the
+    /// surface was configured with it.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless the format is 8-bit rgba or bgra, sRGB or not.
+    pub fn read_pixels(&self, texture: &Texture, options: &ScreenshotOptions) -> Screenshot {
+        screenshot::read_pixels(self, texture, options)
+    }
+
+    /// Like `read_pixels`, but doesn't block: the copy is submitted right
+    /// away and the returned future resolves with the pixels once it's
+    /// done, so an animation can keep running while a screenshot is taken.
+    /// See `ScreenshotFuture` for what drives it.
+    ///
+    /// `texture` can be drawn to again straight away; the copy sees what
+    /// was submitted before this call.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless the format is 8-bit rgba or bgra, sRGB or not.
+    pub fn read_pixels_async(
+        &self,
+        texture: &Texture,
+        options: &ScreenshotOptions,
+    ) -> ScreenshotFuture {
+        screenshot::read_pixels_async(self, texture, options)
+    }
+
+    /// Makes a texture with `layers` layers of `width` by `height` for
+    /// painters to draw into one layer at a time (see `ArrayTarget`).
+    ///
+    /// Painters only draw into targets of the format they were made for,
+    /// which is `format()`, so pass that unless the layers are for
+    /// something else. The texture can also be sampled and copied from.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `layers` is 0 or over the device's
+    /// `max_texture_array_layers`, or a side is over
+    /// `max_texture_dimension_2d`.
+    pub fn create_array_render_target(
+        &self,
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: TextureFormat,
+    ) -> ArrayTarget {
+        let max_layers = self.limits.max_texture_array_layers;
+        assert!(
+            (1..=max_layers).contains(&layers),
+            "{layers} layers isn't in the device's range of 1 to {max_layers}"
+        );
+        let max = self.max_texture_dimension();
+        assert!(
+            width <= max && height <= max,
+            "A {width}x{height} texture is over the device's max_texture_dimension_2d of {max}"
+        );
+        ArrayTarget::new(self, width, height, layers, format)
+    }
+
+    /// Uploads an image for use with sprite painters.
+    ///
+    /// `rgba` is tightly packed 8-bit sRGB with straight alpha, row-major
+    /// starting from the top-left.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rgba` doesn't hold `width * height` pixels, or if either
+    /// side is over the device's `max_texture_dimension_2d`.
+    pub fn upload_texture(&self, width: u32, height: u32, rgba: &[u8]) -> sprite::SpriteTexture {
+        self.upload_texture_with(width, height, rgba, &Default::default())
+    }
+
+    /// Like `upload_texture`, but also generates a full mip chain so sprites
+    /// drawn smaller than the image don't alias.
+    ///
+    /// Each level is a box filter of the one before, down to 1x1.
+    /// Non-power-of-two sizes get mips too: odd dimensions round down, and
+    /// the texels that don't pair up are averaged into the last row or
+    /// column.
+    /// Pick how levels are blended with `SamplerDesc::mipmap_filter`. Costs
+    /// about a third more memory.
+    pub fn upload_texture_with_mips(
+        &self,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> sprite::SpriteTexture {
+        let options = sprite::TextureOptions {
+            mips: true,
+            ..Default::default()
+        };
+        self.upload_texture_with(width, height, rgba, &options)
+    }
+
+    /// Like `upload_texture`, with mips and premultiplication picked by
+    /// `options`. See `TextureOptions`.
+    pub fn upload_texture_with(
+        &self,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+        options: &sprite::TextureOptions,
+    ) -> sprite::SpriteTexture {
+        sprite::SpriteTexture::new(self, width, height, rgba, options)
+    }
+}
+
+// Keeps the thread-safety documented on `Context` from regressing.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Context>();
+    assert_send_sync::<rect::Painter>();
+    assert_send_sync::<rect::InstancePainter>();
+    assert_send_sync::<sprite::Painter>();
+    assert_send_sync::<sprite::SpriteTexture>();
+    assert_send_sync::<point::Painter>();
+};
+
+/// Format of the buffer backing stencil masks.
+pub(crate) const STENCIL_FORMAT: TextureFormat = TextureFormat::Depth24PlusStencil8;
+
+/// For pipelines that don't mask: passes have the context's depth/stencil
+/// attachment if it has one, and this leaves it alone.
+pub(crate) fn ignore_depth_stencil(rc: &Context) -> Option<DepthStencilState> {
+    rc.has_stencil().then(|| DepthStencilState {
+        format: STENCIL_FORMAT,
+        depth_write_enabled: false,
+        depth_compare: CompareFunction::Always,
+        stencil: Default::default(),
+        bias: Default::default(),
+    })
+}
+
+/// Contexts start out needing a frame.
+pub(crate) fn dirty_from_start() -> DirtyFlag {
+    let dirty = DirtyFlag::default();
+    dirty.set();
+    dirty
+}
+
+fn make_stencil(device: &Device, width: u32, height: u32) -> TextureView {
+    device
+        .create_texture(&TextureDescriptor {
+            label: Some("Stencil buffer"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: STENCIL_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&TextureViewDescriptor::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rect::Rect,
+        test_util::{context, other_format, stored, Target},
+    };
+
+    #[test]
+    fn painters_fill_on_two_threads() {
+        let Some(rc) = context(64, 32) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        // Both uploads start together.
+        let barrier = std::sync::Barrier::new(2);
+        let fill = |center: [f32; 2]| {
+            let mut painter = rc.make_rect_painter();
+            barrier.wait();
+            painter
+                .set_rects(&rc, &[Rect::new(center, [0.5, 1.0], 0.0)])
+                .unwrap();
+            painter.set_uniforms(&rc, &Default::default());
+            painter
+        };
+        let (left, right) = std::thread::scope(|scope| {
+            let left = scope.spawn(|| fill([-0.5, 0.0]));
+            let right = scope.spawn(|| fill([0.5, 0.0]));
+            (left.join().unwrap(), right.join().unwrap())
+        });
+
+        let mut frame = rc.begin_frame(&target.view, Color::BLACK);
+        frame.draw_rects(&left);
+        frame.draw_rects(&right);
+        frame.finish();
+        let pixels = target.read(&rc);
+        assert_eq!(pixels.at(16, 16), [255; 4]);
+        assert_eq!(pixels.at(48, 16), [255; 4]);
+        assert_eq!(pixels.at(32, 16), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn pixel_and_clip_at_corners_and_center() {
+        let Some(rc) = context(200, 100) else {
+            return;
+        };
+        for (pixel, clip) in [
+            ([0.0, 0.0], [-1.0, 1.0]),
+            ([200.0, 0.0], [1.0, 1.0]),
+            ([0.0, 100.0], [-1.0, -1.0]),
+            ([200.0, 100.0], [1.0, -1.0]),
+            ([100.0, 50.0], [0.0, 0.0]),
+            ([150.0, 25.0], [0.5, 0.5]),
+        ] {
+            assert_eq!(rc.pixel_to_clip(pixel), clip);
+            assert_eq!(rc.clip_to_pixel(clip), pixel);
+        }
+    }
+
+    #[test]
+    fn set_format_renders_the_next_frame_in_the_new_format() {
+        let Some(mut rc) = context(32, 32) else {
+            return;
+        };
+        let mut painter = rc.make_rect_painter();
+        painter
+            .set_rects(&rc, &[Rect::new([0.0, 0.0], [1.0, 1.0], 0.0)])
+            .unwrap();
+        painter.set_uniforms(&rc, &Default::default());
+        // The checkerboard's pipeline is made in the old format here.
+        rc.draw_checkerboard(&Target::new(&rc).view, &Default::default());
+
+        let format = other_format(&rc);
+        rc.set_format(format).unwrap();
+        painter.rebuild_for_format(&rc);
+        let target = Target::new(&rc);
+        rc.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        rc.draw_checkerboard(&target.view, &Default::default());
+        painter.draw_over(&rc, &target.view).unwrap();
+        let pixels = target.read(&rc);
+        let error = async_std::task::block_on(rc.device.pop_error_scope());
+        assert!(error.is_none(), "{error:?}");
+
+        assert_eq!(pixels.at(16, 16), [255; 4]);
+        // The corner is outside the rect's quad, on the light gray cell.
+        let gray = stored(format, 0.8).round() as u8;
+        assert_eq!(pixels.at(0, 0), [gray, gray, gray, 255]);
+    }
+
+    #[test]
+    fn scale_factor_change_keeps_logical_layout() {
+        let Some(mut rc) = context(64, 64) else {
+            return;
+        };
+        rc.set_logical_coordinates(true);
+        let mut painter = rc.make_rect_painter();
+        painter.set_reverse_winding(true);
+        // Logical pixels 8 to 24 on both axes.
+        painter
+            .set_rects(&rc, &[Rect::new([16.0, 16.0], [16.0, 16.0], 0.0)])
+            .unwrap();
+        painter.set_uniforms(&rc, &Default::default());
+        let target = Target::new(&rc);
+        painter.draw(&rc, &target.view, Color::BLACK).unwrap();
+        let pixels = target.read(&rc);
+        assert_eq!(pixels.at(16, 16), [255; 4]);
+        assert_eq!(pixels.at(28, 28), [0, 0, 0, 255]);
+
+        // Moving to a display at twice the scale, as winit reports it.
+        rc.set_scale_factor(2.0);
+        rc.resize(128, 128);
+        let target = Target::new(&rc);
+        painter.draw(&rc, &target.view, Color::BLACK).unwrap();
+        let pixels = target.read(&rc);
+        for p in [24, 32, 40] {
+            assert_eq!(pixels.at(p, p), [255; 4], "at {p}");
+        }
+        for p in [12, 52] {
+            assert_eq!(pixels.at(p, p), [0, 0, 0, 255], "at {p}");
+        }
+        assert_eq!(rc.logical_to_physical([8.0, 24.0]), [16.0, 48.0]);
+    }
+
+    #[test]
+    fn oversized_headless_context_is_texture_too_large() {
+        let Some(rc) = context(1, 1) else {
+            return;
+        };
+        let max = rc.max_texture_dimension();
+        // GL fails to open a second display while this one's open.
+        drop(rc);
+        let result = async_std::task::block_on(Context::headless_software(max + 1, 16));
+        assert!(
+            matches!(
+                result,
+                Err(ContextError::TextureTooLarge { requested, max: m })
+                    if requested == max + 1 && m == max
+            ),
+            "{:?}",
+            result.err()
+        );
+    }
 }
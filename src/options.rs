@@ -0,0 +1,29 @@
+/// Extra controls for a draw, beyond the color attachment's load op.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DrawOptions {
+    /// Whether to clear the depth/stencil buffer at the start of the pass.
+    /// When false its contents are kept.
+    ///
+    /// Clearing is independent of the color load op, so e.g. loading color
+    /// while clearing depth layers UI over an existing image.
+    ///
+    /// Only meaningful when the context has a depth/stencil buffer (see
+    /// `ContextBuilder::with_stencil`).
+    pub clear_depth_stencil: bool,
+    /// The depth buffer is cleared to this. Must be within `[0, 1]`.
+    /// Defaults to 1, the far plane.
+    pub depth_clear: f32,
+    /// The stencil buffer is cleared to this. Defaults to 0, which is
+    /// outside any mask.
+    pub stencil_clear: u32,
+}
+
+impl Default for DrawOptions {
+    fn default() -> Self {
+        Self {
+            clear_depth_stencil: true,
+            depth_clear: 1.0,
+            stencil_clear: 0,
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use wgpu::{
+    Color, CommandEncoder, LoadOp, Operations, RenderPass, RenderPassColorAttachment,
+    RenderPassDepthStencilAttachment, RenderPassDescriptor, TextureView,
+};
+
+use crate::{Context, DrawOptions};
+
+/// Configures the render pass a painter records into, for when
+/// `record_into`'s load op isn't enough. See `rect::Painter::record_with`.
+///
+/// The defaults match `rect::Painter::draw`: one color attachment cleared
+/// to black and stored, plus the context's depth/stencil buffer (if it has
+/// one), cleared per `DrawOptions::default()`.
+#[derive(Debug, Clone)]
+pub struct RenderPassBuilder<'a> {
+    label: Option<&'a str>,
+    target: &'a TextureView,
+    resolve_target: Option<&'a TextureView>,
+    load: LoadOp<Color>,
+    store: bool,
+    depth_stencil: DepthStencilTarget<'a>,
+    options: DrawOptions,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DepthStencilTarget<'a> {
+    Context,
+    View(&'a TextureView),
+    None,
+}
+
+impl<'a> RenderPassBuilder<'a> {
+    pub fn new(target: &'a TextureView) -> Self {
+        Self {
+            label: None,
+            target,
+            resolve_target: None,
+            load: LoadOp::Clear(Color::BLACK),
+            store: true,
+            depth_stencil: DepthStencilTarget::Context,
+            options: DrawOptions::default(),
+        }
+    }
+
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// How the color attachment starts out.
+    pub fn load(mut self, load: LoadOp<Color>) -> Self {
+        self.load = load;
+        self
+    }
+
+    /// Shorthand for `load(LoadOp::Clear(color))`.
+    pub fn clear_color(self, color: Color) -> Self {
+        self.load(LoadOp::Clear(color))
+    }
+
+    /// Whether the color attachment's contents are kept after the pass.
+    /// True by default. With an MSAA target that's only resolved, storing
+    /// can be skipped.
+    pub fn store(mut self, store: bool) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Resolves a multisampled `target` into `resolve`.
+    ///
+    /// The painters' own pipelines are single-sampled, so this is for
+    /// passes that only `paint` pipelines of your own.
+    pub fn resolve_target(mut self, resolve: &'a TextureView) -> Self {
+        self.resolve_target = Some(resolve);
+        self
+    }
+
+    /// Uses `view` as the depth/stencil attachment instead of the
+    /// context's. It has to have the context's depth/stencil format.
+    pub fn depth_stencil(mut self, view: &'a TextureView) -> Self {
+        self.depth_stencil = DepthStencilTarget::View(view);
+        self
+    }
+
+    /// Leaves out the depth/stencil attachment, even if the context has
+    /// one. Painters can't mask in a pass like that, and a context with a
+    /// stencil buffer made pipelines that expect one, so this only suits
+    /// contexts without one or your own pipelines.
+    pub fn without_depth_stencil(mut self) -> Self {
+        self.depth_stencil = DepthStencilTarget::None;
+        self
+    }
+
+    /// Whether and to what the depth/stencil attachment is cleared.
+    pub fn depth_stencil_options(mut self, options: DrawOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Begins the pass on `encoder`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the depth clear value is outside `[0, 1]`.
+    pub fn begin<'e>(&'e self, rc: &'e Context, encoder: &'e mut CommandEncoder) -> RenderPass<'e> {
+        let options = &self.options;
+        assert!(
+            (0.0..=1.0).contains(&options.depth_clear),
+            "depth_clear must be within [0, 1] but was {}",
+            options.depth_clear
+        );
+        let (depth, stencil) = if options.clear_depth_stencil {
+            (
+                LoadOp::Clear(options.depth_clear),
+                LoadOp::Clear(options.stencil_clear),
+            )
+        } else {
+            (LoadOp::Load, LoadOp::Load)
+        };
+        let depth_stencil_view = match self.depth_stencil {
+            DepthStencilTarget::Context => rc.stencil.as_ref(),
+            DepthStencilTarget::View(view) => Some(view),
+            DepthStencilTarget::None => None,
+        };
+        encoder.begin_render_pass(&RenderPassDescriptor {
+            label: self.label,
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: self.target,
+                resolve_target: self.resolve_target,
+                ops: Operations {
+                    load: self.load,
+                    store: self.store,
+                },
+            })],
+            depth_stencil_attachment: depth_stencil_view.map(|view| {
+                RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(Operations {
+                        load: depth,
+                        store: true,
+                    }),
+                    stencil_ops: Some(Operations {
+                        load: stencil,
+                        store: true,
+                    }),
+                }
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{stencil_context, Target};
+
+    #[test]
+    fn depth_clear_outside_unit_range_panics() {
+        let Some(rc) = stencil_context(8, 8) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let mut commands = rc.device.create_command_encoder(&Default::default());
+        let pass = RenderPassBuilder::new(&target.view).depth_stencil_options(DrawOptions {
+            depth_clear: 1.5,
+            ..Default::default()
+        });
+        let begun = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            pass.begin(&rc, &mut commands);
+        }));
+        assert!(begun.is_err());
+    }
+
+    #[test]
+    fn clears_stencil_to_stencil_clear() {
+        let Some(rc) = stencil_context(32, 32) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let mut painter = rc.make_rect_painter();
+        painter
+            .set_rects(&rc, &[crate::rect::Rect::new([0.0, 0.0], [2.0, 2.0], 0.0)])
+            .unwrap();
+        painter.set_uniforms(&rc, &Default::default());
+        let empty = rc.make_rect_painter();
+        // Masked draws pass where the stencil holds the mask's value.
+        for (stencil_clear, expected) in [(0, [0, 0, 0, 255]), (1, [255; 4])] {
+            empty.draw_with(
+                &rc,
+                &target.view,
+                LoadOp::Clear(Color::BLACK),
+                &DrawOptions {
+                    stencil_clear,
+                    ..Default::default()
+                },
+            );
+            painter.draw_masked(&rc, &target.view).unwrap();
+            assert_eq!(target.read(&rc).at(16, 16), expected);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use wgpu::{
+    Extent3d, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    TextureView, TextureViewDescriptor,
+};
+
+use crate::Context;
+
+/// Two same-sized textures that trade places, for multi-pass effects such
+/// as blurs and feedback where each pass reads the last one's result.
+///
+/// `current()` holds the latest image and `next()` is where the pass
+/// reading it writes; `swap()` afterwards makes that the current one. A
+/// chain of post-processes on a frame goes: draw the scene into `next()`
+/// and `swap()`, then for each process,
+/// `rc.apply_post_process(pp.current(), pp.next(), &process)` and
+/// `swap()`, ending with the result in `current()`, ready to draw onto the
+/// frame as a sprite texture or process once more into the frame itself.
+/// `RenderGraph` does this bookkeeping for chains of oreb's own nodes.
+///
+/// Both textures can be drawn into, sampled and copied from. Neither is
+/// cleared on creation or `swap`, so a pass that doesn't cover its whole
+/// target should start by clearing it.
+pub struct PingPong {
+    targets: [Target; 2],
+    /// Index of `current()` in `targets`.
+    current: usize,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+}
+
+struct Target {
+    texture: Texture,
+    view: TextureView,
+}
+
+impl Target {
+    fn new(rc: &Context, width: u32, height: u32, format: TextureFormat) -> Self {
+        let texture = rc.device.create_texture(&TextureDescriptor {
+            label: Some("Ping-pong target"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        Self { texture, view }
+    }
+}
+
+impl PingPong {
+    /// Makes both textures. Post-processes and painters write the context's
+    /// `format()`, so pass that unless the targets are for something else.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a side is 0 or over the context's `max_texture_dimension`.
+    pub fn new(rc: &Context, width: u32, height: u32, format: TextureFormat) -> Self {
+        check_size(rc, width, height);
+        Self {
+            targets: [0; 2].map(|_| Target::new(rc, width, height, format)),
+            current: 0,
+            width,
+            height,
+            format,
+        }
+    }
+
+    /// The latest result, to read from.
+    pub fn current(&self) -> &TextureView {
+        &self.targets[self.current].view
+    }
+
+    /// The other texture, to write the next result into.
+    pub fn next(&self) -> &TextureView {
+        &self.targets[1 - self.current].view
+    }
+
+    /// Makes `next()` the current texture, after a pass wrote into it.
+    pub fn swap(&mut self) {
+        self.current = 1 - self.current;
+    }
+
+    /// `current()`'s texture, for reading it back with
+    /// `Context::read_pixels`, or making other views.
+    pub fn current_texture(&self) -> &Texture {
+        &self.targets[self.current].texture
+    }
+
+    /// Remakes both textures at `width` by `height` if that's a different
+    /// size, so it can be called every frame with the context's size. Their
+    /// contents are lost then.
+    ///
+    /// # Panics
+    ///
+    /// Panics like `new`.
+    pub fn resize(&mut self, rc: &Context, width: u32, height: u32) {
+        if (width, height) != (self.width, self.height) {
+            *self = Self::new(rc, width, height, self.format);
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// Size of both textures. An estimate; drivers may pad or align them.
+    pub fn gpu_memory_bytes(&self) -> usize {
+        let texel = self.format.block_size(None).unwrap_or(4) as usize;
+        2 * texel * self.width as usize * self.height as usize
+    }
+}
+
+fn check_size(rc: &Context, width: u32, height: u32) {
+    let max = rc.max_texture_dimension();
+    assert!(
+        (1..=max).contains(&width) && (1..=max).contains(&height),
+        "A {width}x{height} ping-pong target isn't within the device's 1 to {max} pixels a side"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::Color;
+
+    use super::*;
+    use crate::{test_util::context, RenderPassBuilder, ScreenshotOptions};
+
+    fn clear(rc: &Context, view: &TextureView, color: Color) {
+        let mut commands = rc.device.create_command_encoder(&Default::default());
+        RenderPassBuilder::new(view)
+            .clear_color(color)
+            .begin(rc, &mut commands);
+        rc.commands.submit(std::iter::once(commands.finish()));
+    }
+
+    fn current_pixel(rc: &Context, pp: &PingPong) -> [u8; 4] {
+        let options = ScreenshotOptions {
+            straight_alpha: false,
+            ..Default::default()
+        };
+        let shot = rc.read_pixels(pp.current_texture(), &options);
+        shot.rgba[..4].try_into().unwrap()
+    }
+
+    #[test]
+    fn swap_alternates_between_the_two_textures() {
+        let Some(rc) = context(4, 4) else {
+            return;
+        };
+        let mut pp = PingPong::new(&rc, 4, 4, rc.format());
+        clear(&rc, pp.next(), Color::RED);
+        pp.swap();
+        clear(&rc, pp.next(), Color::BLUE);
+        for expected in [[0, 0, 255, 255], [255, 0, 0, 255], [0, 0, 255, 255]] {
+            pp.swap();
+            assert_eq!(current_pixel(&rc, &pp), expected);
+        }
+    }
+
+    #[test]
+    fn resize_remakes_both_textures() {
+        let Some(rc) = context(4, 4) else {
+            return;
+        };
+        let mut pp = PingPong::new(&rc, 4, 4, rc.format());
+        clear(&rc, pp.next(), Color::RED);
+        pp.swap();
+        // The same size keeps the textures and what's in them.
+        pp.resize(&rc, 4, 4);
+        assert_eq!(current_pixel(&rc, &pp), [255, 0, 0, 255]);
+
+        pp.resize(&rc, 16, 8);
+        assert_eq!(pp.size(), (16, 8));
+        for _ in 0..2 {
+            let size = pp.current_texture().size();
+            assert_eq!((size.width, size.height), (16, 8));
+            pp.swap();
+        }
+        assert_eq!(pp.gpu_memory_bytes(), 2 * 4 * 16 * 8);
+    }
+}
@@ -0,0 +1,406 @@
+use std::sync::Arc;
+
+use log::debug;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    vertex_attr_array, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendState, Buffer,
+    BufferBindingType, BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites,
+    CommandEncoderDescriptor, Face, FragmentState, FrontFace, MultisampleState,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPass,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    SurfaceError, TextureFormat, TextureView, TextureViewDescriptor, VertexAttribute,
+    VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::{
+    ignore_depth_stencil, transform,
+    upload::Uploader,
+    util::{as_raw_bytes, as_u8_slice, grow, shrink},
+    view::View,
+    Context, GeometryError, RenderPassBuilder, Transform2D, UploadStrategy,
+};
+
+/// A point drawn by a `Painter`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Point {
+    /// In clip space, before the painter's transform.
+    pub position: [f32; 2],
+    /// Width in px. Points stay this size on screen however the painter's
+    /// transform zooms.
+    pub size_px: f32,
+    /// Premultiplied, like the rect painters' colors.
+    pub color: [f32; 4],
+}
+
+impl Point {
+    /// An opaque white point.
+    pub fn new(position: [f32; 2], size_px: f32) -> Self {
+        Self {
+            position,
+            size_px,
+            color: [1.0; 4],
+        }
+    }
+
+    const ATTRS: [VertexAttribute; 3] = vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32,
+        2 => Float32x4
+    ];
+
+    fn layout<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as _,
+            step_mode: VertexStepMode::Instance,
+            attributes: &Self::ATTRS,
+        }
+    }
+}
+
+/// The outline every point of a painter is drawn with.
+#[repr(u32)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum PointShape {
+    #[default]
+    Square,
+    /// A disc with an antialiased edge.
+    Round,
+}
+
+/// `Settings` in painter.wgsl.
+#[repr(C, align(16))]
+struct Uniforms {
+    viewport_px: [f32; 2],
+    shape: PointShape,
+}
+
+/// Draws points as instances of one quad, all in a single draw call.
+///
+/// Points are sized in px, so the painter needs the target's size. `draw`
+/// and `present` take it from the context; call `set_viewport_size` before
+/// `paint` into targets of another size, and after resizes.
+pub struct Painter {
+    pipeline: RenderPipeline,
+    /// The color format the pipeline was made for.
+    format: TextureFormat,
+    /// The context's view uniforms, bound as group 1.
+    pub(crate) view: Arc<View>,
+    layout: BindGroupLayout,
+    bind_group: BindGroup,
+    uniforms: Buffer,
+    transform: Buffer,
+    shape: PointShape,
+    viewport_px: [f32; 2],
+    points: Buffer,
+    point_count: usize,
+    uploader: Uploader,
+}
+
+impl Painter {
+    pub(crate) fn new(rc: &Context) -> Self {
+        let layout = rc
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Point painter bind group layout"),
+                entries: &[
+                    // Settings
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT | ShaderStages::VERTEX,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Transform
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::VERTEX,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let uniforms = rc.device.create_buffer(&BufferDescriptor {
+            label: Some("Point painter uniforms"),
+            size: std::mem::size_of::<Uniforms>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let transform = transform::make_buffer(rc, &Transform2D::IDENTITY);
+
+        let bind_group = rc.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Point painter bind group"),
+            layout: &layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniforms.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: transform.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline = make_pipeline(rc, &layout);
+
+        // Grows as needed in `set_points`.
+        let points = rc.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Point buffer"),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            contents: &[0; 256 * std::mem::size_of::<Point>()],
+        });
+
+        let painter = Self {
+            pipeline,
+            format: rc.format(),
+            view: rc.view.clone(),
+            layout,
+            bind_group,
+            uniforms,
+            transform,
+            shape: PointShape::default(),
+            viewport_px: viewport_px(rc),
+            points,
+            point_count: 0,
+            uploader: Uploader::new(UploadStrategy::default()),
+        };
+        painter.write_uniforms(rc);
+        painter
+    }
+
+    /// Remakes the painter's pipeline for the context's current `format()`.
+    /// See `rect::Painter::rebuild_for_format`.
+    pub fn rebuild_for_format(&mut self, rc: &Context) {
+        if self.format != rc.format() {
+            self.pipeline = make_pipeline(rc, &self.layout);
+            self.format = rc.format();
+        }
+    }
+
+    pub fn set_shape(&mut self, rc: &Context, shape: PointShape) {
+        self.shape = shape;
+        self.write_uniforms(rc);
+    }
+
+    pub fn shape(&self) -> PointShape {
+        self.shape
+    }
+
+    /// The size in px of the targets the painter draws into.
+    pub fn set_viewport_size(&mut self, rc: &Context, width: u32, height: u32) {
+        self.viewport_px = [width, height].map(|e| e.max(1) as f32);
+        self.write_uniforms(rc);
+    }
+
+    fn write_uniforms(&self, rc: &Context) {
+        let uniforms = Uniforms {
+            viewport_px: self.viewport_px,
+            shape: self.shape,
+        };
+        rc.commands
+            .write_buffer(&self.uniforms, 0, unsafe { as_raw_bytes(&uniforms) });
+    }
+
+    /// Transforms the painter's point positions before the context's
+    /// orientation is applied. The identity by default. Sizes aren't
+    /// affected.
+    pub fn set_transform(&self, rc: &Context, transform: &Transform2D) {
+        transform::write_buffer(rc, &self.transform, transform);
+    }
+
+    /// Selects how `set_points` copies points to the GPU.
+    pub fn set_upload_strategy(&mut self, strategy: UploadStrategy) {
+        if strategy != self.uploader.strategy() {
+            self.uploader = Uploader::new(strategy);
+        }
+    }
+
+    pub fn upload_strategy(&self) -> UploadStrategy {
+        self.uploader.strategy()
+    }
+
+    /// Bytes held in the painter's point and uniform buffers. An estimate
+    /// for diagnostics.
+    pub fn gpu_memory_bytes(&self) -> usize {
+        [&self.points, &self.uniforms, &self.transform]
+            .iter()
+            .map(|b| b.size() as usize)
+            .sum()
+    }
+
+    /// Reallocates the point buffer to fit the current points. See
+    /// `rect::Painter::shrink_to_fit`.
+    pub fn shrink_to_fit(&mut self, rc: &Context) {
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        shrink(
+            rc,
+            &mut commands,
+            &mut self.points,
+            self.point_count * std::mem::size_of::<Point>(),
+            "Point buffer",
+        );
+        rc.commands.submit(std::iter::once(commands.finish()));
+    }
+
+    /// Uploads points for drawing. Later points are drawn over earlier ones.
+    ///
+    /// Fails if the point buffer would be larger than the device's
+    /// `max_buffer_size`. The painter draws nothing until the next
+    /// successful call in that case.
+    pub fn set_points(&mut self, rc: &Context, points: &[Point]) -> Result<(), GeometryError> {
+        self.point_count = 0;
+        grow(
+            rc,
+            &mut self.points,
+            std::mem::size_of_val(points),
+            "Point buffer",
+            BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        )?;
+        self.point_count = points.len();
+        self.uploader
+            .write(rc, &[(&self.points, 0, unsafe { as_u8_slice(points) })]);
+        Ok(())
+    }
+
+    /// Draws into `view`, which is taken to be the size of the context's
+    /// surface (see `Context::resize`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rc` isn't on the device the painter was made with.
+    pub fn draw(
+        &mut self,
+        rc: &Context,
+        view: &TextureView,
+        clear_color: impl Into<Option<Color>>,
+    ) -> Result<(), SurfaceError> {
+        self.view.assert_same_device(rc);
+        let clear_color = clear_color.into().unwrap_or(rc.clear_color());
+        let size = viewport_px(rc);
+        if size != self.viewport_px {
+            self.viewport_px = size;
+            self.write_uniforms(rc);
+        }
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        {
+            let builder = RenderPassBuilder::new(view).clear_color(clear_color);
+            let mut pass = builder.begin(rc, &mut commands);
+            self.paint(&mut pass);
+        }
+        rc.commands.submit(std::iter::once(commands.finish()));
+        Ok(())
+    }
+
+    /// Acquires the next frame, draws into it, and presents it.
+    ///
+    /// If the surface is lost or outdated it's reconfigured and acquisition
+    /// is retried once before giving up. Does nothing while the context is
+    /// paused (see `Context::set_active`) or minimized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context has no surface (see `Context::from_raw`).
+    pub fn present(
+        &mut self,
+        rc: &mut Context,
+        clear_color: impl Into<Option<Color>>,
+    ) -> Result<(), SurfaceError> {
+        let Some(frame) = rc.acquire_frame()? else {
+            return Ok(());
+        };
+        let view = frame.texture.create_view(&TextureViewDescriptor::default());
+        self.draw(rc, &view, clear_color)?;
+        rc.present_frame(frame);
+        Ok(())
+    }
+
+    /// Records the draw into a render pass someone else began.
+    ///
+    /// All the points go out in one draw call.
+    pub fn paint<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        if self.point_count == 0 {
+            // Empty buffer slices aren't allowed.
+            return;
+        }
+        debug!("point count {}", self.point_count);
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_bind_group(1, &self.view.bind_group, &[]);
+        pass.set_vertex_buffer(
+            0,
+            self.points
+                .slice(..(std::mem::size_of::<Point>() * self.point_count) as u64),
+        );
+        pass.draw(0..6, 0..self.point_count as u32);
+    }
+}
+
+/// The context's size in px, clamped away from 0 so the shader can divide
+/// by it.
+fn viewport_px(rc: &Context) -> [f32; 2] {
+    [rc.config.width, rc.config.height].map(|e| e.max(1) as f32)
+}
+
+fn make_pipeline(rc: &Context, layout: &BindGroupLayout) -> RenderPipeline {
+    let module = &rc.device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Point painter shader module"),
+        source: ShaderSource::Wgsl(include_str!("painter.wgsl").into()),
+    });
+
+    rc.device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Point Painter Render Pipeline"),
+        layout: Some(
+            &rc.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Point Painter Render Pipeline Layout"),
+                bind_group_layouts: &[layout, &rc.view.layout],
+                push_constant_ranges: &[],
+            }),
+        ),
+        vertex: VertexState {
+            module,
+            entry_point: "vs",
+            buffers: &[Point::layout()],
+        },
+        fragment: Some(FragmentState {
+            module,
+            entry_point: "fs",
+            targets: &[Some(ColorTargetState {
+                format: rc.config.format,
+                blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Back),
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: ignore_depth_stencil(rc),
+        multisample: MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
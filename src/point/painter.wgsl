@@ -0,0 +1,85 @@
+struct Settings {
+    // The target's size, for sizing points in px.
+    viewport_px: vec2<f32>,
+    // See PointShape in mod.rs. 0 is Square, 1 is Round.
+    shape: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> settings: Settings;
+
+// The painter's transform. See transform.rs.
+@group(0) @binding(1)
+var<uniform> transform: mat4x4<f32>;
+
+struct View {
+    // Applied to clip space positions. See view.rs.
+    transform: mat4x4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> view: View;
+
+struct InstanceInput {
+    @location(0) position: vec2<f32>,
+    @location(1) size_px: f32,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Spans [-1,1] across the point.
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) color: vec4<f32>,
+    @location(2) @interpolate(flat) radius_px: f32,
+}
+
+@vertex
+fn vs(
+    @builtin(vertex_index) vertex_index: u32,
+    point: InstanceInput,
+) -> VertexOutput {
+    // Two counter-clockwise triangles covering the point.
+    var corners = array<vec2<f32>, 6>(
+        vec2(-1.0, -1.0),
+        vec2(1.0, -1.0),
+        vec2(1.0, 1.0),
+        vec2(-1.0, -1.0),
+        vec2(1.0, 1.0),
+        vec2(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    // Half a pixel of margin on each side leaves room for antialiasing.
+    let radius_px = 0.5 * point.size_px;
+    let extent_px = radius_px + 0.5;
+
+    var out: VertexOutput;
+    let center = view.transform * transform * vec4<f32>(point.position, 0.0, 1.0);
+    // Offsetting after both transforms keeps points square and upright on
+    // screen whatever the painter's transform or the surface orientation.
+    // Clip space spans 2 units across the viewport.
+    let offset = corner * extent_px * 2.0 / settings.viewport_px;
+    out.position = center + vec4(offset * center.w, 0.0, 0.0);
+    out.uv = corner * extent_px / max(radius_px, 1e-20);
+    out.color = point.color;
+    out.radius_px = radius_px;
+    return out;
+}
+
+@fragment
+fn fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Signed distance to the edge in px.
+    var d: f32;
+    if settings.shape == 0u {
+        let q = abs(in.uv);
+        d = (max(q.x, q.y) - 1.0) * in.radius_px;
+    } else {
+        d = (length(in.uv) - 1.0) * in.radius_px;
+    }
+    let coverage = saturate(0.5 - d);
+    if coverage <= 0.0 {
+        discard;
+    }
+    // Output is premultiplied, so coverage scales every channel.
+    return in.color * coverage;
+}
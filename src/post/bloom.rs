@@ -0,0 +1,361 @@
+use std::sync::Mutex;
+
+use wgpu::{
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendComponent,
+    BlendFactor, BlendOperation, BlendState, Buffer, BufferBindingType, BufferDescriptor,
+    BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoder, CommandEncoderDescriptor,
+    Extent3d, FilterMode, FragmentState, LoadOp, MultisampleState, Operations, PipelineLayout,
+    PipelineLayoutDescriptor, PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor,
+    RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor,
+    ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+use crate::{util::as_raw_bytes, Context};
+
+/// Options for `Context::bloom`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BloomSettings {
+    /// Only color brighter than this glows, measured by its largest linear
+    /// channel. Use 0 to make everything glow, or a value just under 1 for
+    /// only the brightest pixels.
+    pub threshold: f32,
+    /// How much glow is added back. 0 leaves the image unchanged.
+    pub intensity: f32,
+    /// How many times the bright parts are halved in size and blurred.
+    /// Each step roughly doubles how far the glow reaches. Limited by the
+    /// image size, and at least 1.
+    pub radius: u32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 0.8,
+            intensity: 1.0,
+            radius: 5,
+        }
+    }
+}
+
+/// Half float, so the blurred glow keeps detail in dark areas and can go
+/// past 1 where bright pixels overlap.
+const LEVEL_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+#[repr(C, align(16))]
+struct Uniforms {
+    threshold: f32,
+    scale: f32,
+}
+
+/// A chain of textures, each half the size of the one before. Level 0 is
+/// half the size of the input.
+struct Levels {
+    width: u32,
+    height: u32,
+    views: Vec<TextureView>,
+}
+
+impl Levels {
+    fn new(rc: &Context, width: u32, height: u32) -> Self {
+        // Stop before either side would go below 1.
+        let count = 32 - (width.min(height) / 2).max(1).leading_zeros();
+        let views = (1..=count)
+            .map(|level| {
+                rc.device
+                    .create_texture(&TextureDescriptor {
+                        label: Some("Bloom level"),
+                        size: Extent3d {
+                            width: (width >> level).max(1),
+                            height: (height >> level).max(1),
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: TextureDimension::D2,
+                        format: LEVEL_FORMAT,
+                        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                        view_formats: &[],
+                    })
+                    .create_view(&TextureViewDescriptor::default())
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            views,
+        }
+    }
+
+    fn gpu_memory_bytes(&self) -> usize {
+        (1..=self.views.len())
+            .map(|level| {
+                let (w, h) = (self.width >> level, self.height >> level);
+                8 * w.max(1) as usize * h.max(1) as usize
+            })
+            .sum()
+    }
+}
+
+/// Bright-pass, blur and composite pipelines, and the intermediate
+/// textures they share.
+pub(crate) struct Bloom {
+    layout: BindGroupLayout,
+    prefilter: RenderPipeline,
+    down: RenderPipeline,
+    up: RenderPipeline,
+    composite: RenderPipeline,
+    sampler: Sampler,
+    uniforms: Buffer,
+    /// Sized for the context's surface. Remade when that changes.
+    levels: Mutex<Option<Levels>>,
+}
+
+impl Bloom {
+    pub(crate) fn new(rc: &Context) -> Self {
+        let texture_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let layout = rc
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Bloom bind group layout"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    texture_entry(1),
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    texture_entry(3),
+                ],
+            });
+        let pipeline_layout = rc.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Bloom pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let module = rc.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Bloom shader module"),
+            source: ShaderSource::Wgsl(include_str!("bloom.wgsl").into()),
+        });
+
+        let additive = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+        let make = |entry_point, format, blend| {
+            make_pipeline(rc, &pipeline_layout, &module, entry_point, format, blend)
+        };
+        let prefilter = make("fs_prefilter", LEVEL_FORMAT, None);
+        let down = make("fs_down", LEVEL_FORMAT, None);
+        let up = make(
+            "fs_up",
+            LEVEL_FORMAT,
+            Some(BlendState {
+                color: additive,
+                alpha: additive,
+            }),
+        );
+        let composite = make("fs_composite", rc.config.format, None);
+
+        let sampler = rc.device.create_sampler(&SamplerDescriptor {
+            label: Some("Bloom sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniforms = rc.device.create_buffer(&BufferDescriptor {
+            label: Some("Bloom uniforms"),
+            size: std::mem::size_of::<Uniforms>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            layout,
+            prefilter,
+            down,
+            up,
+            composite,
+            sampler,
+            uniforms,
+            levels: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn gpu_memory_bytes(&self) -> usize {
+        self.levels
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(0, Levels::gpu_memory_bytes)
+    }
+
+    pub(crate) fn run(
+        &self,
+        rc: &Context,
+        input: &TextureView,
+        output: &TextureView,
+        settings: &BloomSettings,
+    ) {
+        let (width, height) = (rc.config.width, rc.config.height);
+        let mut levels = self.levels.lock().unwrap();
+        if !matches!(&*levels, Some(l) if (l.width, l.height) == (width, height)) {
+            *levels = Some(Levels::new(rc, width, height));
+        }
+        let views = &levels.as_ref().unwrap().views;
+        let count = (settings.radius as usize).clamp(1, views.len());
+        let views = &views[..count];
+
+        rc.commands.write_buffer(&self.uniforms, 0, unsafe {
+            as_raw_bytes(&Uniforms {
+                threshold: settings.threshold,
+                scale: settings.intensity / count as f32,
+            })
+        });
+
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        let reading = |source| self.bind_group(rc, input, source);
+        let clear = LoadOp::Clear(Color::TRANSPARENT);
+        pass(
+            &mut commands,
+            &self.prefilter,
+            &reading(input),
+            &views[0],
+            clear,
+        );
+        for pair in views.windows(2) {
+            pass(
+                &mut commands,
+                &self.down,
+                &reading(&pair[0]),
+                &pair[1],
+                clear,
+            );
+        }
+        for pair in views.windows(2).rev() {
+            pass(
+                &mut commands,
+                &self.up,
+                &reading(&pair[1]),
+                &pair[0],
+                LoadOp::Load,
+            );
+        }
+        pass(
+            &mut commands,
+            &self.composite,
+            &reading(&views[0]),
+            output,
+            clear,
+        );
+        rc.commands.submit(std::iter::once(commands.finish()));
+    }
+
+    /// Binds `source` as the texture a pass reads.
+    fn bind_group(&self, rc: &Context, input: &TextureView, source: &TextureView) -> BindGroup {
+        rc.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Bloom bind group"),
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniforms.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(source),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(input),
+                },
+            ],
+        })
+    }
+}
+
+/// Draws a fullscreen triangle into `target`.
+fn pass(
+    commands: &mut CommandEncoder,
+    pipeline: &RenderPipeline,
+    bind_group: &BindGroup,
+    target: &TextureView,
+    load: LoadOp<Color>,
+) {
+    let mut pass = commands.begin_render_pass(&RenderPassDescriptor {
+        label: Some("Bloom pass"),
+        color_attachments: &[Some(RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: Operations { load, store: true },
+        })],
+        depth_stencil_attachment: None,
+    });
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.draw(0..3, 0..1);
+}
+
+fn make_pipeline(
+    rc: &Context,
+    layout: &PipelineLayout,
+    module: &ShaderModule,
+    entry_point: &str,
+    format: TextureFormat,
+    blend: Option<BlendState>,
+) -> RenderPipeline {
+    rc.device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Bloom pipeline"),
+        layout: Some(layout),
+        vertex: VertexState {
+            module,
+            entry_point: "vs",
+            buffers: &[],
+        },
+        fragment: Some(FragmentState {
+            module,
+            entry_point,
+            targets: &[Some(ColorTargetState {
+                format,
+                blend,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,88 @@
+struct Settings {
+    threshold: f32,
+    // Intensity divided by the number of levels, which all add up.
+    scale: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> settings: Settings;
+
+// The level read by this pass. For the first pass, the input image.
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+
+@group(0) @binding(2)
+var bilinear: sampler;
+
+// The image bloom is applied to. Only read by `fs_composite`.
+@group(0) @binding(3)
+var input: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// One triangle covering the viewport.
+@vertex
+fn vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(2.0 * uv - 1.0, 0.0, 1.0);
+    out.uv = vec2(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// Four bilinear taps half a target texel from the center: a 4x4 box of
+// source texels, since the source is twice the size.
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let d = 0.5 / vec2<f32>(textureDimensions(source));
+    return 0.25 * (
+        textureSample(source, bilinear, uv + vec2(-d.x, -d.y)).rgb +
+        textureSample(source, bilinear, uv + vec2(d.x, -d.y)).rgb +
+        textureSample(source, bilinear, uv + vec2(-d.x, d.y)).rgb +
+        textureSample(source, bilinear, uv + vec2(d.x, d.y)).rgb
+    );
+}
+
+// Keeps the part of the color brighter than the threshold. Loads from an
+// sRGB input are decoded to linear.
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = downsample(in.uv);
+    let brightness = max(color.r, max(color.g, color.b));
+    let excess = max(brightness - settings.threshold, 0.0);
+    return vec4(color * excess / max(brightness, 1e-4), 1.0);
+}
+
+@fragment
+fn fs_down(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(downsample(in.uv), 1.0);
+}
+
+// A 3x3 tent over the smaller level, added onto the larger one by blending.
+@fragment
+fn fs_up(in: VertexOutput) -> @location(0) vec4<f32> {
+    let d = 1.0 / vec2<f32>(textureDimensions(source));
+    var color = 4.0 * textureSample(source, bilinear, in.uv).rgb;
+    color += 2.0 * textureSample(source, bilinear, in.uv + vec2(d.x, 0.0)).rgb;
+    color += 2.0 * textureSample(source, bilinear, in.uv - vec2(d.x, 0.0)).rgb;
+    color += 2.0 * textureSample(source, bilinear, in.uv + vec2(0.0, d.y)).rgb;
+    color += 2.0 * textureSample(source, bilinear, in.uv - vec2(0.0, d.y)).rgb;
+    color += textureSample(source, bilinear, in.uv + d).rgb;
+    color += textureSample(source, bilinear, in.uv - d).rgb;
+    color += textureSample(source, bilinear, in.uv + vec2(d.x, -d.y)).rgb;
+    color += textureSample(source, bilinear, in.uv + vec2(-d.x, d.y)).rgb;
+    return vec4(color / 16.0, 1.0);
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(input, vec2<i32>(in.position.xy), 0);
+    let glow = settings.scale * textureSample(source, bilinear, in.uv).rgb;
+    // Glow is light, so it's added to premultiplied color as is and makes
+    // what it lands on more opaque.
+    let rgb = color.rgb + glow;
+    let alpha = max(color.a, min(max(glow.r, max(glow.g, glow.b)), 1.0));
+    return vec4(min(rgb, vec3(alpha)), alpha);
+}
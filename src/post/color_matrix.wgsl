@@ -0,0 +1,32 @@
+struct Settings {
+    // Applied to linear rgb. See post/mod.rs.
+    matrix: mat3x3<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> settings: Settings;
+
+@group(0) @binding(1)
+var input: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+}
+
+// One triangle covering the viewport.
+@vertex
+fn vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(2.0 * uv - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Input and output are the same size, so this is a 1:1 copy of texels.
+    // Loads from an sRGB texture are decoded to linear.
+    let color = textureLoad(input, vec2<i32>(in.position.xy), 0);
+    // The matrix is linear, so it works the same on premultiplied color.
+    return vec4(saturate(settings.matrix * color.rgb), color.a);
+}
@@ -0,0 +1,232 @@
+use wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType,
+    BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoderDescriptor,
+    FragmentState, LoadOp, MultisampleState, Operations, PipelineLayoutDescriptor, PrimitiveState,
+    RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureSampleType, TextureView,
+    TextureViewDimension, VertexState,
+};
+
+use crate::{util::as_raw_bytes, Context};
+
+#[cfg(feature = "postfx")]
+pub(crate) mod bloom;
+
+#[cfg(feature = "postfx")]
+pub use bloom::BloomSettings;
+
+/// A kind of dichromatic color vision to simulate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorVisionDeficiency {
+    /// No working long-wavelength (red) cones.
+    Protanopia,
+    /// No working medium-wavelength (green) cones.
+    Deuteranopia,
+    /// No working short-wavelength (blue) cones.
+    Tritanopia,
+}
+
+/// A fullscreen pass over a rendered image. Run it with
+/// `Context::apply_post_process`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PostProcess {
+    /// Row-major, applied to linear rgb.
+    matrix: [[f32; 3]; 3],
+}
+
+// Linear rgb to LMS cone responses and back, from Viénot, Brettel and
+// Mollon, "Digital video colourmaps for checking the legibility of displays
+// by dichromats" (1999).
+const RGB_TO_LMS: [[f32; 3]; 3] = [
+    [17.8824, 43.5161, 4.11935],
+    [3.45565, 27.1554, 3.86714],
+    [0.0299566, 0.184309, 1.46709],
+];
+
+const LMS_TO_RGB: [[f32; 3]; 3] = [
+    [0.08094445, -0.1305044, 0.1167211],
+    [-0.01024853, 0.05401933, -0.1136147],
+    [-0.0003652969, -0.004121615, 0.6935114],
+];
+
+fn mul(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    std::array::from_fn(|r| std::array::from_fn(|c| (0..3).map(|k| a[r][k] * b[k][c]).sum()))
+}
+
+impl PostProcess {
+    /// Shows the image as someone with the color vision deficiency `kind`
+    /// would see it, for checking that UI colors stay distinguishable.
+    ///
+    /// Colors are converted to LMS cone space, the missing cone's response
+    /// is replaced by one reconstructed from the other two, and the result
+    /// is converted back. The transform is only meaningful on linear color,
+    /// so the input should be an sRGB texture (which decodes on load) or
+    /// otherwise hold linear values.
+    pub fn simulate_cvd(kind: ColorVisionDeficiency) -> Self {
+        // The missing response as a combination of the others.
+        let project = match kind {
+            ColorVisionDeficiency::Protanopia => {
+                [[0.0, 2.02344, -2.52581], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]
+            }
+            ColorVisionDeficiency::Deuteranopia => {
+                [[1.0, 0.0, 0.0], [0.494207, 0.0, 1.24827], [0.0, 0.0, 1.0]]
+            }
+            ColorVisionDeficiency::Tritanopia => {
+                [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [-0.395913, 0.801109, 0.0]]
+            }
+        };
+        Self {
+            matrix: mul(&LMS_TO_RGB, &mul(&project, &RGB_TO_LMS)),
+        }
+    }
+}
+
+/// A `mat3x3<f32>`: three columns padded to 16 bytes.
+#[repr(C, align(16))]
+struct Uniforms {
+    columns: [[f32; 4]; 3],
+}
+
+impl From<&PostProcess> for Uniforms {
+    fn from(process: &PostProcess) -> Self {
+        let m = process.matrix;
+        Self {
+            columns: std::array::from_fn(|c| [m[0][c], m[1][c], m[2][c], 0.0]),
+        }
+    }
+}
+
+/// Runs `PostProcess`es into targets of the context's format.
+pub(crate) struct Pipeline {
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    uniforms: Buffer,
+}
+
+impl Pipeline {
+    pub(crate) fn new(rc: &Context) -> Self {
+        let layout = rc
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Post process bind group layout"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: false },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let uniforms = rc.device.create_buffer(&BufferDescriptor {
+            label: Some("Post process uniforms"),
+            size: std::mem::size_of::<Uniforms>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let module = &rc.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Post process shader module"),
+            source: ShaderSource::Wgsl(include_str!("color_matrix.wgsl").into()),
+        });
+
+        let pipeline = rc.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Post process pipeline"),
+            layout: Some(
+                &rc.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Post process pipeline layout"),
+                    bind_group_layouts: &[&layout],
+                    push_constant_ranges: &[],
+                }),
+            ),
+            vertex: VertexState {
+                module,
+                entry_point: "vs",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module,
+                entry_point: "fs",
+                targets: &[Some(ColorTargetState {
+                    format: rc.config.format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            layout,
+            uniforms,
+        }
+    }
+
+    pub(crate) fn run(
+        &self,
+        rc: &Context,
+        input: &TextureView,
+        output: &TextureView,
+        process: &PostProcess,
+    ) {
+        rc.commands.write_buffer(&self.uniforms, 0, unsafe {
+            as_raw_bytes(&Uniforms::from(process))
+        });
+        let bind_group = rc.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Post process bind group"),
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniforms.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(input),
+                },
+            ],
+        });
+
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        {
+            let mut pass = commands.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Post process pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        rc.commands.submit(std::iter::once(commands.finish()));
+    }
+}
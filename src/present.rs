@@ -0,0 +1,60 @@
+use wgpu::PresentMode;
+
+/// How many images the surface cycles through, as implied by its present
+/// mode. See `Context::present_mode_buffering`.
+///
+/// wgpu doesn't expose the swapchain's actual image count or frame latency,
+/// so these are the usual values, not measurements:
+///
+/// - `Fifo` queues frames behind the display's refresh: effectively double
+///   buffered, with about one frame of latency on top of the one being
+///   drawn. Supported everywhere.
+/// - `FifoRelaxed` is `Fifo`, but a late frame is shown right away and may
+///   tear.
+/// - `Mailbox` replaces the queued frame with each newer one instead of
+///   waiting: effectively triple buffered, lower latency without tearing.
+///   Common on Vulkan and DX12, unavailable on Metal and most GL setups.
+/// - `Immediate` shows frames as soon as they're presented and tears.
+///
+/// Metal only ever offers `Fifo` and, on macOS, `Immediate`, which turns
+/// off the layer's display sync. iOS is always vsynced. Even with
+/// `Immediate`, a windowed app on macOS goes through the compositor, which
+/// may still hold frames to the display's refresh; frame rates are
+/// reliably uncapped in fullscreen. Check
+/// `Context::supported_present_modes` for what the surface offers.
+///
+/// Drivers may allocate more images than listed here, particularly on
+/// Vulkan and DX12, where the minimum swapchain length varies.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BufferingInfo {
+    pub present_mode: PresentMode,
+    /// Images the surface cycles through. `None` for the `Auto*` modes,
+    /// where wgpu picks the underlying mode when the surface is configured.
+    pub buffer_count: Option<u32>,
+    /// Whether presenting waits for the display's refresh, so frames never
+    /// tear. `FifoRelaxed` only tears when a frame is late, so it counts.
+    pub vsync: bool,
+}
+
+impl BufferingInfo {
+    pub(crate) fn new(present_mode: PresentMode) -> Self {
+        let (buffer_count, vsync) = match present_mode {
+            PresentMode::Fifo | PresentMode::FifoRelaxed => (Some(2), true),
+            PresentMode::Mailbox => (Some(3), true),
+            PresentMode::Immediate => (Some(2), false),
+            PresentMode::AutoVsync => (None, true),
+            PresentMode::AutoNoVsync => (None, false),
+        };
+        Self {
+            present_mode,
+            buffer_count,
+            vsync,
+        }
+    }
+}
+
+/// Whether a surface offering `modes` can be configured with `mode`. The
+/// `Auto*` modes always can: wgpu resolves them to one the surface has.
+pub(crate) fn is_supported(modes: &[PresentMode], mode: PresentMode) -> bool {
+    matches!(mode, PresentMode::AutoVsync | PresentMode::AutoNoVsync) || modes.contains(&mode)
+}
@@ -0,0 +1,104 @@
+use wgpu::{BindGroup, BindGroupLayout, Buffer, BufferDescriptor, BufferUsages};
+
+use super::{styles::settings_bind_group, PainterSettings, SettingsUniform, Vertex};
+use crate::{
+    util::{as_raw_bytes, grow},
+    Context, GeometryError,
+};
+
+/// One draw of `Painter::draw_batches`: triangles and the settings to draw
+/// them in.
+#[derive(Debug, Copy, Clone)]
+pub struct Batch<'a> {
+    pub vertices: &'a [Vertex],
+    /// Into this batch's `vertices`.
+    pub indexes: &'a [u32],
+    pub settings: PainterSettings,
+}
+
+/// What a draw recorded.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct DrawStats {
+    /// Batches with no indexes aren't drawn, so aren't counted.
+    pub draw_calls: usize,
+    pub vertices: usize,
+    pub indexes: usize,
+}
+
+/// A slot of settings per batch, like `Styles` but sized to the largest
+/// batch list drawn so far.
+pub(super) struct BatchSettings {
+    buffer: Buffer,
+    /// See `Styles::stride`.
+    stride: u64,
+    /// One per slot the buffer holds.
+    bind_groups: Vec<BindGroup>,
+}
+
+const LABEL: &str = "Painter batch settings";
+const USAGE: BufferUsages = BufferUsages::UNIFORM.union(BufferUsages::COPY_DST);
+
+impl BatchSettings {
+    pub(super) fn new(rc: &Context) -> Self {
+        let size = std::mem::size_of::<SettingsUniform>() as u64;
+        let stride = size.next_multiple_of(rc.limits().min_uniform_buffer_offset_alignment as u64);
+        let buffer = rc.device.create_buffer(&BufferDescriptor {
+            label: Some(LABEL),
+            size: stride,
+            usage: USAGE,
+            mapped_at_creation: false,
+        });
+        Self {
+            buffer,
+            stride,
+            bind_groups: Vec::new(),
+        }
+    }
+
+    /// Writes each batch's settings to its slot, growing the buffer to fit.
+    pub(super) fn write(
+        &mut self,
+        rc: &Context,
+        layout: &BindGroupLayout,
+        transform: &Buffer,
+        tint: &Buffer,
+        batches: &[Batch],
+    ) -> Result<(), GeometryError> {
+        let size = self.stride as usize * batches.len();
+        let old_size = self.buffer.size();
+        grow(rc, &mut self.buffer, size, LABEL, USAGE)?;
+        if self.buffer.size() != old_size {
+            self.bind_groups.clear();
+        }
+        let slots = (self.buffer.size() / self.stride) as usize;
+        while self.bind_groups.len() < slots.min(batches.len()) {
+            let offset = self.bind_groups.len() as u64 * self.stride;
+            self.bind_groups.push(settings_bind_group(
+                rc,
+                "Painter batch bind group",
+                layout,
+                &self.buffer,
+                offset,
+                transform,
+                tint,
+            ));
+        }
+
+        let mut bytes = vec![0; size];
+        for (slot, batch) in bytes.chunks_mut(self.stride as usize).zip(batches) {
+            let uniform = SettingsUniform::from(&batch.settings);
+            let settings = unsafe { as_raw_bytes(&uniform) };
+            slot[..settings.len()].copy_from_slice(settings);
+        }
+        if !bytes.is_empty() {
+            rc.commands.write_buffer(&self.buffer, 0, &bytes);
+        }
+        Ok(())
+    }
+
+    /// The bind group for each slot, in order, for at least as many
+    /// batches as the last `write` had.
+    pub(super) fn bind_groups(&self) -> &[BindGroup] {
+        &self.bind_groups
+    }
+}
@@ -0,0 +1,194 @@
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, Buffer, BufferUsages, IndexFormat, RenderPass, RenderPipeline,
+};
+
+use super::{geometry, GeometryMode, Rect, Vertex};
+use crate::{util::as_u8_slice, Context, GeometryError};
+
+/// Part of a clip path. See `Painter::set_clip_path`.
+#[derive(Debug, Clone)]
+pub enum ClipShape {
+    /// Clips like the painter draws the rect: its corner radius rounds the
+    /// clip, and its edge band is inside it. The clip ends at the rect, so
+    /// it leaves out whatever part of the band `StrokeAlign::Center` or
+    /// `Outside` puts past it.
+    Rect(Rect),
+    /// A simple polygon, in either winding, filled flat. Its edges
+    /// mustn't cross; what's left of a polygon that does after cutting
+    /// off every corner that can be is dropped.
+    Polygon(Vec<[f32; 2]>),
+}
+
+/// The GPU copy of a painter's clip path.
+pub(super) struct Clip {
+    /// Unset when every shape was degenerate.
+    buffers: Option<(Buffer, Buffer)>,
+    index_count: u32,
+}
+
+impl Clip {
+    pub(super) fn new(
+        rc: &Context,
+        mode: GeometryMode,
+        reverse_winding: bool,
+        shapes: &[ClipShape],
+    ) -> Result<Self, GeometryError> {
+        let rects: Vec<Rect> = shapes
+            .iter()
+            .filter_map(|shape| match shape {
+                ClipShape::Rect(rect) => Some(*rect),
+                ClipShape::Polygon(_) => None,
+            })
+            .collect();
+        let geometry::Encoded {
+            mut vertices,
+            mut indexes,
+            ..
+        } = geometry::encode(mode, reverse_winding, 0.0, &rects);
+        for shape in shapes {
+            let ClipShape::Polygon(points) = shape else {
+                continue;
+            };
+            let base = vertices.len() as u32;
+            vertices.extend(
+                points
+                    .iter()
+                    .map(|&[x, y]| Vertex::from(([x, y, 0.0], [0.0; 2]))),
+            );
+            for mut triangle in triangulate(points) {
+                if reverse_winding {
+                    triangle.swap(1, 2);
+                }
+                indexes.extend(triangle.map(|i| base + i));
+            }
+        }
+
+        let max = rc.limits().max_buffer_size;
+        for (buffer, size) in [
+            (
+                "Clip vertex buffer",
+                std::mem::size_of_val(vertices.as_slice()),
+            ),
+            (
+                "Clip index buffer",
+                std::mem::size_of_val(indexes.as_slice()),
+            ),
+        ] {
+            if size as u64 > max {
+                return Err(GeometryError::TooLarge {
+                    buffer,
+                    requested: size as u64,
+                    max,
+                });
+            }
+        }
+        let buffers = (!indexes.is_empty()).then(|| {
+            let make = |label, usage, contents| {
+                rc.device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some(label),
+                    usage,
+                    contents,
+                })
+            };
+            (
+                make("Clip vertex buffer", BufferUsages::VERTEX, unsafe {
+                    as_u8_slice(&vertices)
+                }),
+                make("Clip index buffer", BufferUsages::INDEX, unsafe {
+                    as_u8_slice(&indexes)
+                }),
+            )
+        });
+        Ok(Self {
+            buffers,
+            index_count: indexes.len() as u32,
+        })
+    }
+
+    pub(super) fn buffer_sizes(&self) -> usize {
+        self.buffers.as_ref().map_or(0, |(vertices, indexes)| {
+            (vertices.size() + indexes.size()) as usize
+        })
+    }
+
+    /// Draws the clip with `pipeline`, which writes it into the stencil
+    /// buffer.
+    pub(super) fn record<'a>(
+        &'a self,
+        pass: &mut RenderPass<'a>,
+        pipeline: &'a RenderPipeline,
+        bind_group: &'a BindGroup,
+        view_bind_group: &'a BindGroup,
+    ) {
+        let Some((vertices, indexes)) = &self.buffers else {
+            return;
+        };
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.set_bind_group(1, view_bind_group, &[]);
+        pass.set_vertex_buffer(0, vertices.slice(..));
+        pass.set_index_buffer(indexes.slice(..), IndexFormat::Uint32);
+        pass.draw_indexed(0..self.index_count, 0, 0..1);
+    }
+}
+
+/// Splits a simple polygon into counter-clockwise triangles by ear
+/// clipping, as indexes into `points`.
+fn triangulate(points: &[[f32; 2]]) -> Vec<[u32; 3]> {
+    let cross = |a: usize, b: usize, c: usize| {
+        let ([ax, ay], [bx, by], [cx, cy]) = (points[a], points[b], points[c]);
+        (bx - ax) * (cy - ay) - (by - ay) * (cx - ax)
+    };
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let twice_area: f32 = (0..points.len())
+        .map(|i| {
+            let ([x0, y0], [x1, y1]) = (points[i], points[(i + 1) % points.len()]);
+            x0 * y1 - x1 * y0
+        })
+        .sum();
+    if twice_area < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+    while remaining.len() >= 3 {
+        let n = remaining.len();
+        let corner = |i: usize| {
+            (
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            )
+        };
+        let is_ear = |i: usize| {
+            let (a, b, c) = corner(i);
+            cross(a, b, c) > 0.0
+                && remaining.iter().all(|&p| {
+                    // Points on the triangle's boundary block it too, so no
+                    // triangle overlaps another.
+                    [a, b, c].contains(&p)
+                        || points[p] == points[a]
+                        || points[p] == points[b]
+                        || points[p] == points[c]
+                        || cross(a, b, p) < 0.0
+                        || cross(b, c, p) < 0.0
+                        || cross(c, a, p) < 0.0
+                })
+        };
+        if let Some(i) = (0..n).find(|&i| is_ear(i)) {
+            let (a, b, c) = corner(i);
+            triangles.push([a, b, c].map(|v| v as u32));
+            remaining.remove(i);
+        } else if let Some(i) = (0..n).find(|&i| {
+            let (a, b, c) = corner(i);
+            cross(a, b, c) == 0.0
+        }) {
+            // A corner with no area adds nothing.
+            remaining.remove(i);
+        } else {
+            break;
+        }
+    }
+    triangles
+}
@@ -0,0 +1,279 @@
+use std::num::NonZeroU64;
+
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBinding, BufferBindingType,
+    BufferDescriptor, BufferUsages, CommandEncoder, ComputePassDescriptor, ComputePipeline,
+    ComputePipelineDescriptor, PipelineLayoutDescriptor, RenderPass, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages,
+};
+
+use super::RectInstance;
+use crate::{
+    util::{as_raw_bytes, grow},
+    view::View,
+    Context, GeometryError,
+};
+
+/// Invocations per workgroup, as in cull.wgsl.
+const WORKGROUP_SIZE: u32 = 256;
+
+/// Bytes of a `DrawIndirect`.
+const ARGS_SIZE: u64 = 16;
+
+/// Buffers the cull passes read instances from and write survivors to.
+const STORAGE_BUFFERS: u32 = 4;
+
+/// An `InstancePainter`'s GPU culling: compute passes that copy the rects
+/// with any part in clip space, in order, to a buffer of their own, and
+/// write the arguments of the indirect draw that draws them. See
+/// `InstancePainter::enable_gpu_culling`.
+pub(super) struct GpuCulling {
+    layout: BindGroupLayout,
+    count: ComputePipeline,
+    scan: ComputePipeline,
+    compact: ComputePipeline,
+    /// The instance count, as the shader's `Params`.
+    params: Buffer,
+    /// The surviving instances, drawn as the vertex buffer.
+    culled: Buffer,
+    group_counts: Buffer,
+    pub(super) args: Buffer,
+    /// Unset while there are no instances, since bindings can't be empty.
+    bind_group: Option<BindGroup>,
+    instance_count: usize,
+}
+
+impl GpuCulling {
+    /// Whether the device can run the passes. Judged from its limits, since
+    /// a `from_raw` context doesn't know its adapter's downlevel flags:
+    /// devices without compute shaders, like WebGL2's, have no compute
+    /// workgroups or storage buffers, and the backends with compute shaders
+    /// draw indirectly too.
+    pub(super) fn supported(rc: &Context) -> bool {
+        let limits = rc.limits();
+        limits.max_compute_workgroups_per_dimension > 0
+            && limits.max_compute_invocations_per_workgroup >= WORKGROUP_SIZE
+            && limits.max_compute_workgroup_size_x >= WORKGROUP_SIZE
+            && limits.max_storage_buffers_per_shader_stage >= STORAGE_BUFFERS
+    }
+
+    pub(super) fn new(rc: &Context) -> Self {
+        let uniform = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = |binding, read_only| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = rc
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Rect culling bind group layout"),
+                entries: &[
+                    // Settings, transform, view and params
+                    uniform(0),
+                    uniform(1),
+                    uniform(2),
+                    uniform(3),
+                    // Instances, survivors, group counts and draw arguments
+                    storage(4, true),
+                    storage(5, false),
+                    storage(6, false),
+                    storage(7, false),
+                ],
+            });
+        let module = rc.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Rect culling shader module"),
+            source: ShaderSource::Wgsl(include_str!("cull.wgsl").into()),
+        });
+        let pipeline_layout = rc.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Rect culling pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point| {
+            rc.device
+                .create_compute_pipeline(&ComputePipelineDescriptor {
+                    label: Some("Rect culling pipeline"),
+                    layout: Some(&pipeline_layout),
+                    module: &module,
+                    entry_point,
+                })
+        };
+        let buffer = |label, size, usage| {
+            rc.device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+
+        Self {
+            count: pipeline("count"),
+            scan: pipeline("scan"),
+            compact: pipeline("compact"),
+            layout,
+            // A uniform buffer is at least 16 bytes.
+            params: buffer(
+                "Rect culling params",
+                16,
+                BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            ),
+            // All three grow as needed in `update`.
+            culled: buffer(
+                "Culled rect instance buffer",
+                64 * std::mem::size_of::<RectInstance>() as u64,
+                Self::CULLED_USAGES,
+            ),
+            group_counts: buffer("Rect culling group counts", 4, BufferUsages::STORAGE),
+            args: buffer(
+                "Rect culling draw arguments",
+                ARGS_SIZE,
+                BufferUsages::STORAGE | BufferUsages::INDIRECT | BufferUsages::COPY_SRC,
+            ),
+            bind_group: None,
+            instance_count: 0,
+        }
+    }
+
+    const CULLED_USAGES: BufferUsages = BufferUsages::STORAGE.union(BufferUsages::VERTEX);
+
+    /// Points the passes at the painter's first `instance_count` instances
+    /// and buffers.
+    ///
+    /// Fails if the instances are too large to bind as a storage buffer, or
+    /// for the survivors' buffer.
+    pub(super) fn update(
+        &mut self,
+        rc: &Context,
+        instances: &Buffer,
+        instance_count: usize,
+        uniforms: [&Buffer; 2],
+        view: &View,
+    ) -> Result<(), GeometryError> {
+        self.instance_count = 0;
+        self.bind_group = None;
+        let bytes = (instance_count * std::mem::size_of::<RectInstance>()) as u64;
+        let max = rc.limits().max_storage_buffer_binding_size as u64;
+        if bytes > max {
+            return Err(GeometryError::TooLargeToCull {
+                requested: bytes,
+                max,
+            });
+        }
+        let Some(size) = NonZeroU64::new(bytes) else {
+            return Ok(());
+        };
+        grow(
+            rc,
+            &mut self.culled,
+            bytes as usize,
+            "Culled rect instance buffer",
+            Self::CULLED_USAGES,
+        )?;
+        let groups = instance_count.div_ceil(WORKGROUP_SIZE as usize);
+        grow(
+            rc,
+            &mut self.group_counts,
+            4 * groups,
+            "Rect culling group counts",
+            BufferUsages::STORAGE,
+        )?;
+        let count = instance_count as u32;
+        rc.commands
+            .write_buffer(&self.params, 0, unsafe { as_raw_bytes(&[count, 0, 0, 0]) });
+
+        let sized = |buffer, size| {
+            BindingResource::Buffer(BufferBinding {
+                buffer,
+                offset: 0,
+                size: Some(size),
+            })
+        };
+        let [settings, transform] = uniforms;
+        let resources = [
+            settings.as_entire_binding(),
+            transform.as_entire_binding(),
+            view.uniforms.as_entire_binding(),
+            self.params.as_entire_binding(),
+            sized(instances, size),
+            sized(&self.culled, size),
+            sized(
+                &self.group_counts,
+                NonZeroU64::new(4 * groups as u64).unwrap(),
+            ),
+            self.args.as_entire_binding(),
+        ];
+        let entries: Vec<BindGroupEntry> = resources
+            .into_iter()
+            .enumerate()
+            .map(|(binding, resource)| BindGroupEntry {
+                binding: binding as u32,
+                resource,
+            })
+            .collect();
+        self.bind_group = Some(rc.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Rect culling bind group"),
+            layout: &self.layout,
+            entries: &entries,
+        }));
+        self.instance_count = instance_count;
+        Ok(())
+    }
+
+    /// Records the passes. A later `paint` in the same submission, or a
+    /// later one, draws what they kept.
+    pub(super) fn record(&self, rc: &Context, commands: &mut CommandEncoder) {
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
+        let groups = (self.instance_count as u32).div_ceil(WORKGROUP_SIZE);
+        // Over the per-dimension limit, spread the groups across rows.
+        let x = groups.min(rc.limits().max_compute_workgroups_per_dimension);
+        let y = groups.div_ceil(x);
+
+        let mut pass = commands.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("Rect culling pass"),
+        });
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.set_pipeline(&self.count);
+        pass.dispatch_workgroups(x, y, 1);
+        pass.set_pipeline(&self.scan);
+        pass.dispatch_workgroups(1, 1, 1);
+        pass.set_pipeline(&self.compact);
+        pass.dispatch_workgroups(x, y, 1);
+    }
+
+    /// Binds the survivors and draws them. The caller has the pipeline and
+    /// bind groups set.
+    pub(super) fn paint<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        if self.bind_group.is_none() {
+            return;
+        }
+        let bytes = self.instance_count * std::mem::size_of::<RectInstance>();
+        pass.set_vertex_buffer(0, self.culled.slice(..bytes as u64));
+        pass.draw_indirect(&self.args, 0);
+    }
+
+    pub(super) fn buffer_sizes(&self) -> usize {
+        [&self.params, &self.culled, &self.group_counts, &self.args]
+            .iter()
+            .map(|b| b.size() as usize)
+            .sum()
+    }
+}
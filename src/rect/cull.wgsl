@@ -0,0 +1,181 @@
+// Culls an InstancePainter's rects against clip space in three passes,
+// keeping the survivors in order so they overlap as they would unculled:
+// `count` tallies each workgroup's survivors, `scan` turns the tallies into
+// offsets and fills in the draw arguments, and `compact` copies the
+// survivors to their offsets. See cull.rs.
+
+// See instanced.wgsl.
+struct Settings {
+    edge: vec4<f32>,
+    fill: vec4<f32>,
+    line_width_px: f32,
+    corner_radius_px: f32,
+    debug_visualize: u32,
+    line_width_space: u32,
+    alpha_cutoff: f32,
+    stroke_align: u32,
+}
+
+struct View {
+    transform: mat4x4<f32>,
+}
+
+struct Params {
+    // Rects in `instances`.
+    count: u32,
+}
+
+// wgpu's DrawIndirect, as draw_indirect reads it.
+struct DrawArgs {
+    vertex_count: u32,
+    instance_count: u32,
+    first_vertex: u32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> settings: Settings;
+
+@group(0) @binding(1)
+var<uniform> transform: mat4x4<f32>;
+
+@group(0) @binding(2)
+var<uniform> view: View;
+
+@group(0) @binding(3)
+var<uniform> params: Params;
+
+// RectInstances, 13 floats each. Read as floats because a struct holding
+// vec4s would be padded to 64 bytes.
+@group(0) @binding(4)
+var<storage, read> instances: array<f32>;
+
+@group(0) @binding(5)
+var<storage, read_write> culled: array<f32>;
+
+// Survivors in each workgroup, then where each workgroup's go.
+@group(0) @binding(6)
+var<storage, read_write> group_counts: array<u32>;
+
+@group(0) @binding(7)
+var<storage, read_write> args: DrawArgs;
+
+const WORKGROUP_SIZE: u32 = 256u;
+const INSTANCE_FLOATS: u32 = 13u;
+
+var<workgroup> sums: array<u32, WORKGROUP_SIZE>;
+
+// Whether instance `i` exists and has any part in clip space.
+fn visible(i: u32) -> bool {
+    if i >= params.count {
+        return false;
+    }
+    let base = i * INSTANCE_FLOATS;
+    let center = vec2(instances[base], instances[base + 1u]);
+    let size = vec2(instances[base + 2u], instances[base + 3u]);
+    let angle = instances[base + 12u];
+    // Degenerate rects cover nothing; NaNs fail this too.
+    if !all(size > vec2<f32>()) {
+        return false;
+    }
+
+    // The quad instanced.wgsl draws, margin included.
+    var margin = 0.0;
+    if settings.line_width_space == 1u {
+        margin = settings.line_width_px * 0.5 * f32(min(settings.stroke_align, 2u));
+    }
+    let half = 0.5 * (size + 2.0 * margin);
+    let c = cos(angle);
+    let s = sin(angle);
+    let m = view.transform * transform;
+    var lo = vec2(1e30);
+    var hi = vec2(-1e30);
+    for (var k = 0u; k < 4u; k++) {
+        let corner = half * vec2(select(-1.0, 1.0, (k & 1u) != 0u), select(-1.0, 1.0, k >= 2u));
+        let world = vec2(corner.x * c - corner.y * s, corner.x * s + corner.y * c) + center;
+        let clip = m * vec4(world, 0.0, 1.0);
+        let p = clip.xy / clip.w;
+        lo = min(lo, p);
+        hi = max(hi, p);
+    }
+    return all(hi >= vec2(-1.0)) && all(lo <= vec2(1.0));
+}
+
+fn group_index(id: vec3<u32>, groups: vec3<u32>) -> u32 {
+    return id.y * groups.x + id.x;
+}
+
+// Leaves `sums` holding each invocation's inclusive prefix sum of `value`.
+fn workgroup_scan(local: u32, value: u32) {
+    sums[local] = value;
+    workgroupBarrier();
+    for (var step = 1u; step < WORKGROUP_SIZE; step *= 2u) {
+        var add = 0u;
+        if local >= step {
+            add = sums[local - step];
+        }
+        workgroupBarrier();
+        sums[local] += add;
+        workgroupBarrier();
+    }
+}
+
+@compute @workgroup_size(256)
+fn count(
+    @builtin(workgroup_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    let group = group_index(id, groups);
+    workgroup_scan(local, u32(visible(group * WORKGROUP_SIZE + local)));
+    // Dispatches in two dimensions can run past the last group.
+    if local == WORKGROUP_SIZE - 1u && group * WORKGROUP_SIZE < params.count {
+        group_counts[group] = sums[local];
+    }
+}
+
+// Run as a single workgroup, each invocation scanning a run of tallies.
+@compute @workgroup_size(256)
+fn scan(@builtin(local_invocation_index) local: u32) {
+    let group_count = (params.count + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE;
+    let run = (group_count + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE;
+    let start = min(local * run, group_count);
+    let end = min(start + run, group_count);
+    var total = 0u;
+    for (var g = start; g < end; g++) {
+        total += group_counts[g];
+    }
+    workgroup_scan(local, total);
+
+    var offset = sums[local] - total;
+    for (var g = start; g < end; g++) {
+        let n = group_counts[g];
+        group_counts[g] = offset;
+        offset += n;
+    }
+    if local == WORKGROUP_SIZE - 1u {
+        args.vertex_count = 6u;
+        args.instance_count = sums[local];
+        args.first_vertex = 0u;
+        args.first_instance = 0u;
+    }
+}
+
+@compute @workgroup_size(256)
+fn compact(
+    @builtin(workgroup_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    let group = group_index(id, groups);
+    let i = group * WORKGROUP_SIZE + local;
+    let keep = visible(i);
+    workgroup_scan(local, u32(keep));
+    if keep {
+        let dst = (group_counts[group] + sums[local] - 1u) * INSTANCE_FLOATS;
+        let src = i * INSTANCE_FLOATS;
+        for (var k = 0u; k < INSTANCE_FLOATS; k++) {
+            culled[dst + k] = instances[src + k];
+        }
+    }
+}
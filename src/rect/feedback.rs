@@ -0,0 +1,150 @@
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType,
+    BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites, FragmentState, MultisampleState,
+    PipelineLayoutDescriptor, PrimitiveState, RenderPass, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureSampleType, TextureView,
+    TextureViewDimension, VertexState,
+};
+
+use crate::{ignore_depth_stencil, util::as_raw_bytes, Context};
+
+/// A `Settings` from feedback.wgsl, padded to 16 bytes.
+#[repr(C, align(16))]
+struct Uniforms {
+    decay: f32,
+}
+
+/// Draws a faded copy of the previous frame under a painter's rects. See
+/// `Painter::set_feedback_source`.
+pub(super) struct Feedback {
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    uniforms: Buffer,
+    /// Set while there's a source.
+    bind_group: Option<BindGroup>,
+}
+
+impl Feedback {
+    pub(super) fn new(rc: &Context, decay: f32) -> Self {
+        let layout = rc
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Feedback bind group layout"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: false },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let uniforms = rc.device.create_buffer(&BufferDescriptor {
+            label: Some("Feedback uniforms"),
+            size: std::mem::size_of::<Uniforms>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let feedback = Self {
+            pipeline: make_pipeline(rc, &layout),
+            layout,
+            uniforms,
+            bind_group: None,
+        };
+        feedback.set_decay(rc, decay);
+        feedback
+    }
+
+    pub(super) fn rebuild(&mut self, rc: &Context) {
+        self.pipeline = make_pipeline(rc, &self.layout);
+    }
+
+    pub(super) fn set_decay(&self, rc: &Context, decay: f32) {
+        rc.commands.write_buffer(&self.uniforms, 0, unsafe {
+            as_raw_bytes(&Uniforms { decay })
+        });
+    }
+
+    pub(super) fn set_source(&mut self, rc: &Context, source: Option<&TextureView>) {
+        self.bind_group = source.map(|view| {
+            rc.device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Feedback bind group"),
+                layout: &self.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: self.uniforms.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(view),
+                    },
+                ],
+            })
+        });
+    }
+
+    pub(super) fn has_source(&self) -> bool {
+        self.bind_group.is_some()
+    }
+
+    pub(super) fn record<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        if let Some(bind_group) = &self.bind_group {
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+fn make_pipeline(rc: &Context, layout: &BindGroupLayout) -> RenderPipeline {
+    let module = &rc.device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Feedback shader module"),
+        source: ShaderSource::Wgsl(include_str!("feedback.wgsl").into()),
+    });
+    rc.device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Feedback pipeline"),
+        layout: Some(
+            &rc.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Feedback pipeline layout"),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            }),
+        ),
+        vertex: VertexState {
+            module,
+            entry_point: "vs",
+            buffers: &[],
+        },
+        fragment: Some(FragmentState {
+            module,
+            entry_point: "fs",
+            targets: &[Some(ColorTargetState {
+                format: rc.config.format,
+                // Covers the whole target, replacing whatever it was loaded
+                // or cleared with.
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: ignore_depth_stencil(rc),
+        multisample: MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,30 @@
+struct Settings {
+    // Multiplies the previous frame. See rect/feedback.rs.
+    decay: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> settings: Settings;
+
+@group(0) @binding(1)
+var previous: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+}
+
+// One triangle covering the viewport.
+@vertex
+fn vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(2.0 * uv - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The previous frame is the same size as the target, so this is a 1:1
+    // copy of texels. It's premultiplied, so fading scales every channel.
+    return settings.decay * textureLoad(previous, vec2<i32>(in.position.xy), 0);
+}
@@ -0,0 +1,395 @@
+use super::Vertex;
+
+/// A rectangle in clip space.
+#[derive(Debug, Copy, Clone)]
+pub struct Rect {
+    pub center: [f32; 2],
+    pub size: [f32; 2],
+    pub orientation_radians: f32,
+}
+
+impl Rect {
+    /// `center` and `size` accept anything convertible to `[f32; 2]`, like
+    /// `glam::Vec2` when the `glam` feature is enabled.
+    pub fn new(
+        center: impl Into<[f32; 2]>,
+        size: impl Into<[f32; 2]>,
+        orientation_radians: f32,
+    ) -> Self {
+        Self {
+            center: center.into(),
+            size: size.into(),
+            orientation_radians,
+        }
+    }
+
+    /// The smallest axis-aligned box containing the rotated rect, as
+    /// `[min_x, min_y, max_x, max_y]` in clip space.
+    ///
+    /// Covers the rect's full extent, so it's conservative for rounded
+    /// corners.
+    pub fn aabb(&self) -> [f32; 4] {
+        let [cx, cy] = self.center;
+        let [half_w, half_h] = self.size.map(|e| 0.5 * e);
+        let (s, c) = self.orientation_radians.sin_cos();
+        // The corners farthest along each axis, from rotating (±w/2, ±h/2).
+        let ex = half_w * c.abs() + half_h * s.abs();
+        let ey = half_w * s.abs() + half_h * c.abs();
+        [cx - ex, cy - ey, cx + ex, cy + ey]
+    }
+
+    /// Whether the rect can't be drawn: a width or height that isn't
+    /// positive, or a value that isn't finite. `set_rects` skips these
+    /// rather than sending the shader triangles whose SDF would divide by
+    /// zero.
+    ///
+    /// Any positive size is drawn, but below about a pixel the antialiased
+    /// edge is all there is, so tiny rects come out as faint dots.
+    pub fn is_degenerate(&self) -> bool {
+        let finite = self
+            .center
+            .iter()
+            .chain(&self.size)
+            .chain([&self.orientation_radians])
+            .all(|e| e.is_finite());
+        !(finite && self.size.iter().all(|&e| e > 0.0))
+    }
+
+    /// Where `anchor` is on the rect, in clip space. It turns with the
+    /// rect, so e.g. `Top` is the middle of the edge that's on top before
+    /// rotating.
+    ///
+    /// oreb has no text painter, but a label rendered into a
+    /// `SpriteTexture` can be attached to a rect by drawing a `Sprite` at
+    /// the anchor with the rect's `orientation_radians`. Size the sprite in
+    /// clip space to have it zoom along with the rect's painter transform,
+    /// or divide by the transform's scale to keep the text a fixed size.
+    pub fn anchor(&self, anchor: Anchor) -> [f32; 2] {
+        let [x, y] = anchor.offset();
+        let [w, h] = self.size;
+        let (px, py) = (0.5 * x * w, 0.5 * y * h);
+        let (s, c) = self.orientation_radians.sin_cos();
+        let [cx, cy] = self.center;
+        [cx + px * c - py * s, cy + px * s + py * c]
+    }
+}
+
+/// A point on a rect, for placing things such as labels relative to it.
+/// See `Rect::anchor`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Anchor {
+    #[default]
+    Center,
+    /// The middle of the edge toward +y.
+    Top,
+    Bottom,
+    /// The middle of the edge toward -x.
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Position on the unrotated rect, with each axis spanning `[-1, 1]`.
+    fn offset(self) -> [f32; 2] {
+        match self {
+            Anchor::Center => [0.0, 0.0],
+            Anchor::Top => [0.0, 1.0],
+            Anchor::Bottom => [0.0, -1.0],
+            Anchor::Left => [-1.0, 0.0],
+            Anchor::Right => [1.0, 0.0],
+            Anchor::TopLeft => [-1.0, 1.0],
+            Anchor::TopRight => [1.0, 1.0],
+            Anchor::BottomLeft => [-1.0, -1.0],
+            Anchor::BottomRight => [1.0, -1.0],
+        }
+    }
+}
+
+#[cfg(feature = "glam")]
+impl Rect {
+    pub fn center(&self) -> glam::Vec2 {
+        self.center.into()
+    }
+
+    pub fn size(&self) -> glam::Vec2 {
+        self.size.into()
+    }
+}
+
+/// Controls how each rect is covered with triangles before it gets shaded.
+///
+/// The fragment shader evaluates the rect's SDF, so any geometry that covers
+/// the rect will do. The choice trades vertex work against fragment work.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum GeometryMode {
+    /// One triangle per rect. The triangle is larger than the rect so some
+    /// fragments outside the rect get shaded and discarded.
+    #[default]
+    Triangle,
+    /// Two triangles per rect forming a quad that exactly bounds the rect.
+    /// More vertices, but no overdraw outside the rect.
+    Quad,
+}
+
+impl GeometryMode {
+    /// Vertices `set_rects` makes for each rect.
+    pub fn vertices_per_rect(&self) -> usize {
+        match self {
+            GeometryMode::Triangle => 3,
+            GeometryMode::Quad => 4,
+        }
+    }
+
+    /// Indexes `set_rects` makes for each rect, e.g. to find a rect's
+    /// range for `Painter::draw_style`.
+    pub fn indexes_per_rect(&self) -> usize {
+        match self {
+            GeometryMode::Triangle => 3,
+            GeometryMode::Quad => 6,
+        }
+    }
+}
+
+// Vertices are generated for an axis-aligned rect centered at the origin.
+// Then they are rotated and translated into place.
+//
+// uv: [0,0] is the center of the rect, and the rect's [w,h] in uv coords is
+// [1,1].
+fn place(
+    rect: &Rect,
+    color: [f32; 4],
+    vertices: impl IntoIterator<Item = Vertex>,
+) -> impl Iterator<Item = Vertex> {
+    let [cx, cy] = rect.center;
+    let (s, c) = rect.orientation_radians.sin_cos();
+    vertices.into_iter().map(move |mut v| {
+        let x = v.xyz[0] * c - v.xyz[1] * s;
+        let y = v.xyz[0] * s + v.xyz[1] * c;
+        v.xyz[0] = x + cx;
+        v.xyz[1] = y + cy;
+        v.color = color;
+        v
+    })
+}
+
+fn covering_triangle(rect: &Rect) -> [Vertex; 3] {
+    let [half_w, half_h] = rect.size.map(|e| 0.5 * e);
+    // The smallest right triangle with its right angle on the bottom-left
+    // corner of the rect whose hypotenuse touches the top-right corner has
+    // legs that are twice the rect's width and height.
+    [
+        // bottom-left
+        ([-half_w, -half_h, 0.0], [-0.5, -0.5]).into(),
+        // bottom-right
+        ([3.0 * half_w, -half_h, 0.0], [1.5, -0.5]).into(),
+        // top-left
+        ([-half_w, 3.0 * half_h, 0.0], [-0.5, 1.5]).into(),
+    ]
+}
+
+fn covering_quad(rect: &Rect) -> [Vertex; 4] {
+    let [half_w, half_h] = rect.size.map(|e| 0.5 * e);
+    [
+        // bottom-left
+        ([-half_w, -half_h, 0.0], [-0.5, -0.5]).into(),
+        // bottom-right
+        ([half_w, -half_h, 0.0], [0.5, -0.5]).into(),
+        // top-right
+        ([half_w, half_h, 0.0], [0.5, 0.5]).into(),
+        // top-left
+        ([-half_w, half_h, 0.0], [-0.5, 0.5]).into(),
+    ]
+}
+
+/// Triangles for a batch of rects, and how many were skipped for being
+/// degenerate.
+pub(crate) struct Encoded {
+    pub(crate) vertices: Vec<Vertex>,
+    pub(crate) indexes: Vec<u32>,
+    pub(crate) skipped: usize,
+}
+
+/// Expands `rects` into triangles according to `mode`. Degenerate rects
+/// are left out.
+///
+/// Triangles go counter-clockwise in clip space before any transform, or
+/// clockwise with `reverse_winding`. `margin` grows each rect's geometry on
+/// every side, in the rect's units, with the uv extended past [-0.5, 0.5]
+/// to match, so the shape the shader finds stays the rect.
+pub(crate) fn encode(
+    mode: GeometryMode,
+    reverse_winding: bool,
+    margin: f32,
+    rects: &[Rect],
+) -> Encoded {
+    encode_colored(
+        mode,
+        reverse_winding,
+        margin,
+        rects.iter().map(|rect| (rect, Vertex::WHITE)),
+    )
+}
+
+/// Like `encode`, with a color for each rect.
+pub(crate) fn encode_colored<'a>(
+    mode: GeometryMode,
+    reverse_winding: bool,
+    margin: f32,
+    shapes: impl ExactSizeIterator<Item = (&'a Rect, [f32; 4])>,
+) -> Encoded {
+    // Swapping the last two corners of each triangle reverses it.
+    let (triangle, quad) = if reverse_winding {
+        ([0, 2, 1], [0, 2, 1, 0, 3, 2])
+    } else {
+        ([0, 1, 2], [0, 1, 2, 0, 2, 3])
+    };
+    let mut vertices = Vec::with_capacity(mode.vertices_per_rect() * shapes.len());
+    let mut indexes = Vec::with_capacity(mode.indexes_per_rect() * shapes.len());
+    let mut skipped = 0;
+    for (rect, color) in shapes {
+        if rect.is_degenerate() {
+            skipped += 1;
+            continue;
+        }
+        let base = vertices.len() as u32;
+        let grown = Rect {
+            size: rect.size.map(|e| e + 2.0 * margin),
+            ..*rect
+        };
+        let uv_scale = [0, 1].map(|i| grown.size[i] / rect.size[i]);
+        let rescale = move |mut v: Vertex| {
+            v.uv = [0, 1].map(|i| v.uv[i] * uv_scale[i]);
+            v
+        };
+        match mode {
+            GeometryMode::Triangle => {
+                let corners = covering_triangle(&grown).map(rescale);
+                vertices.extend(place(rect, color, corners));
+                indexes.extend(triangle.map(|i| base + i));
+            }
+            GeometryMode::Quad => {
+                let corners = covering_quad(&grown).map(rescale);
+                vertices.extend(place(rect, color, corners));
+                indexes.extend(quad.map(|i| base + i));
+            }
+        }
+    }
+    Encoded {
+        vertices,
+        indexes,
+        skipped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, SQRT_2};
+
+    use super::*;
+
+    fn assert_near(actual: [f32; 4], expected: [f32; 4]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-6, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn aabb_of_upright_rect_is_the_rect() {
+        let rect = Rect::new([0.5, -0.25], [1.0, 0.5], 0.0);
+        assert_eq!(rect.aabb(), [0.0, -0.5, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn aabb_of_turned_rect() {
+        // A square on its corner reaches half its diagonal along each axis.
+        let square = Rect::new([1.0, 1.0], [1.0, 1.0], FRAC_PI_4);
+        let e = 0.5 * SQRT_2;
+        assert_near(square.aabb(), [1.0 - e, 1.0 - e, 1.0 + e, 1.0 + e]);
+        // A quarter turn swaps width and height.
+        let rect = Rect::new([0.0, 0.0], [2.0, 1.0], FRAC_PI_2);
+        assert_near(rect.aabb(), [-0.5, -1.0, 0.5, 1.0]);
+        assert_near(
+            Rect::new([0.0, 0.0], [2.0, 1.0], -FRAC_PI_2).aabb(),
+            rect.aabb(),
+        );
+    }
+
+    #[test]
+    fn aabb_of_degenerate_rect() {
+        // No width leaves the turned height.
+        let line = Rect::new([0.0, 0.0], [0.0, 2.0], FRAC_PI_4);
+        let e = 0.5 * SQRT_2;
+        assert_near(line.aabb(), [-e, -e, e, e]);
+        let point = Rect::new([0.25, 0.75], [0.0, 0.0], 1.0);
+        assert_eq!(point.aabb(), [0.25, 0.75, 0.25, 0.75]);
+    }
+
+    /// Twice the signed areas of the encoded triangles, positive for
+    /// counter-clockwise ones.
+    fn signed_areas(encoded: &Encoded) -> Vec<f32> {
+        encoded
+            .indexes
+            .chunks_exact(3)
+            .map(|t| {
+                let [a, b, c] = [0, 1, 2].map(|i| encoded.vertices[t[i] as usize].xyz);
+                (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encode_winds_per_reverse_winding() {
+        let rects = [
+            Rect::new([0.0, 0.0], [1.0, 0.5], 0.0),
+            Rect::new([0.5, -0.5], [0.25, 1.0], 2.5),
+        ];
+        for mode in [GeometryMode::Triangle, GeometryMode::Quad] {
+            let forward = encode(mode, false, 0.0, &rects);
+            let reversed = encode(mode, true, 0.1, &rects);
+            assert_eq!(forward.indexes.len(), 2 * mode.indexes_per_rect());
+            assert!(signed_areas(&forward).iter().all(|&area| area > 0.0));
+            assert!(signed_areas(&reversed).iter().all(|&area| area < 0.0));
+        }
+        // Quads cover the rect exactly: two triangles of half its area.
+        let quad = encode(GeometryMode::Quad, false, 0.0, &rects[..1]);
+        assert_eq!(signed_areas(&quad), [0.5, 0.5]);
+    }
+
+    #[test]
+    fn degenerate_rects() {
+        let ok = Rect::new([0.0, 0.0], [1e-6, 2.0], 0.0);
+        assert!(!ok.is_degenerate());
+        for rect in [
+            Rect::new([0.0, 0.0], [0.0, 1.0], 0.0),
+            Rect::new([0.0, 0.0], [1.0, -1.0], 0.0),
+            Rect::new([f32::NAN, 0.0], [1.0, 1.0], 0.0),
+            Rect::new([0.0, 0.0], [f32::INFINITY, 1.0], 0.0),
+            Rect::new([0.0, 0.0], [1.0, 1.0], f32::NAN),
+        ] {
+            assert!(rect.is_degenerate(), "{rect:?}");
+        }
+    }
+
+    #[test]
+    fn encode_skips_degenerate_rects() {
+        let rects = [
+            Rect::new([0.0, 0.0], [0.0, 0.0], 0.0),
+            Rect::new([0.0, 0.0], [0.5, 0.5], 0.0),
+            Rect::new([0.0, 0.0], [-1.0, 0.5], 0.0),
+            Rect::new([0.0, f32::NAN], [0.5, 0.5], 0.0),
+        ];
+        for mode in [GeometryMode::Triangle, GeometryMode::Quad] {
+            let encoded = encode(mode, false, 0.25, &rects);
+            assert_eq!(encoded.skipped, 3);
+            assert_eq!(encoded.vertices.len(), mode.vertices_per_rect());
+            assert_eq!(encoded.indexes.len(), mode.indexes_per_rect());
+            for v in &encoded.vertices {
+                assert!(v.xyz.iter().chain(&v.uv).all(|e| e.is_finite()), "{v:?}");
+            }
+        }
+    }
+}
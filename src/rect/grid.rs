@@ -0,0 +1,111 @@
+use super::Rect;
+
+/// Lays out rects in rows and columns with edges on whole pixels, for
+/// grid-based UIs.
+///
+/// Positions are in pixels with y pointing down, as from `origin`, the
+/// top left corner of cell `(0, 0)`. Column `col` starts
+/// `col * (cell[0] + gap[0])` pixels to the right of it and row `row` as
+/// far down, so cells tile the plane with `gap` between them.
+///
+/// Adding up widths in floating point drifts, so edges laid out one after
+/// another land a fraction of a pixel apart and blend into a visible seam
+/// or a doubled line. `cell_rect` instead rounds every edge from its own
+/// position, and computes a cell's right (bottom) edge the same way as
+/// the next cell's left (top) one. Without a gap they're bit-for-bit the
+/// same value, so cells meet exactly. The price is that cell and gap sizes
+/// vary by up to a pixel when they aren't whole numbers.
+///
+/// The rects come out in the grid's pixels. Draw them with
+/// `Context::set_logical_coordinates` on, which takes geometry in logical
+/// pixels; pass the context's `scale_factor()` to `cell_rect_scaled` so
+/// edges land on physical pixels at any scale. Any other painter
+/// transform that maps whole pixels onto whole pixels works too.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Grid {
+    pub origin: [f32; 2],
+    pub cell: [f32; 2],
+    pub gap: [f32; 2],
+}
+
+impl Grid {
+    /// The rect covering the cell at `col`, `row`, with its edges rounded
+    /// to whole pixels. Negative indexes count left of (above) the origin.
+    pub fn cell_rect(&self, col: i32, row: i32) -> Rect {
+        self.cell_rect_scaled(col, row, 1.0)
+    }
+
+    /// Like `cell_rect`, but rounds edges to multiples of
+    /// `1 / pixels_per_unit`, so a grid in logical pixels lands on physical
+    /// ones at that scale factor.
+    pub fn cell_rect_scaled(&self, col: i32, row: i32, pixels_per_unit: f32) -> Rect {
+        let [left, top, right, bottom] = self.cell_edges(col, row, pixels_per_unit);
+        Rect {
+            center: [0.5 * (left + right), 0.5 * (top + bottom)],
+            size: [right - left, bottom - top],
+            orientation_radians: 0.0,
+        }
+    }
+
+    /// The snapped `[left, top, right, bottom]` of a cell. Neighbors'
+    /// shared edges are bit-for-bit equal here; the `Rect` made from them
+    /// reproduces them to within a rounding error.
+    fn cell_edges(&self, col: i32, row: i32, pixels_per_unit: f32) -> [f32; 4] {
+        let snap = |v: f32| (v * pixels_per_unit).round() / pixels_per_unit;
+        let [(left, right), (top, bottom)] = [0, 1].map(|axis| {
+            let index = [col, row][axis] as f32;
+            let pitch = self.cell[axis] + self.gap[axis];
+            let start = self.origin[axis] + index * pitch;
+            let end = self.origin[axis] + (index + 1.0) * pitch - self.gap[axis];
+            (snap(start), snap(end))
+        });
+        [left, top, right, bottom]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRID: Grid = Grid {
+        origin: [3.3, -7.9],
+        cell: [17.37, 9.81],
+        gap: [0.0, 0.0],
+    };
+
+    #[test]
+    fn adjacent_cells_share_edges_exactly() {
+        for scale in [1.0, 1.25] {
+            for i in -40..40 {
+                for j in -3..3 {
+                    let cell = GRID.cell_edges(i, j, scale);
+                    let right = GRID.cell_edges(i + 1, j, scale);
+                    let below = GRID.cell_edges(i, j + 1, scale);
+                    let at = format!("({i}, {j}) at {scale}");
+                    assert_eq!(cell[2].to_bits(), right[0].to_bits(), "{at}");
+                    assert_eq!(cell[3].to_bits(), below[1].to_bits(), "{at}");
+                    // Edges land on physical pixels.
+                    for edge in cell {
+                        let px = edge * scale;
+                        assert!((px - px.round()).abs() < 1e-3, "{edge} {at}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cell_rect_has_the_snapped_edges() {
+        for scale in [1.0, 1.25] {
+            for i in -40..40 {
+                let rect = GRID.cell_rect_scaled(i, -i, scale);
+                let [cx, cy] = rect.center;
+                let [half_w, half_h] = rect.size.map(|e| 0.5 * e);
+                let from_rect = [cx - half_w, cy - half_h, cx + half_w, cy + half_h];
+                for (a, e) in from_rect.iter().zip(GRID.cell_edges(i, -i, scale)) {
+                    assert!((a - e).abs() < 1e-4, "{from_rect:?} at {scale}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,643 @@
+use std::sync::Arc;
+
+use log::debug;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    vertex_attr_array, BindGroup, BindGroupLayout, BlendState, Buffer, BufferDescriptor,
+    BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoder, CommandEncoderDescriptor,
+    Face, FragmentState, FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode,
+    PrimitiveState, PrimitiveTopology, RenderPass, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, SurfaceError, TextureFormat, TextureView,
+    TextureViewDescriptor, VertexAttribute, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use super::{cull::GpuCulling, Bindings, PainterSettings, SettingsUniform};
+use crate::{
+    ignore_depth_stencil, transform,
+    upload::Uploader,
+    util::{as_raw_bytes, as_u8_slice, grow, shrink},
+    view::View,
+    Context, GeometryError, RenderPassBuilder, Transform2D, UploadStrategy,
+};
+
+/// A rect drawn by an `InstancePainter`, with its own corner radii and
+/// color. `Pod` with the `bytemuck` feature, like `Vertex`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct RectInstance {
+    /// In clip space.
+    pub center: [f32; 2],
+    /// In clip space.
+    pub size: [f32; 2],
+    /// In px, for the top-left, top-right, bottom-right and bottom-left
+    /// corners of the unrotated rect. Radii larger than half the rect's
+    /// shorter side are clamped to it.
+    pub corner_radius: [f32; 4],
+    /// Multiplies the painter's fill color.
+    pub color: [f32; 4],
+    pub orientation_radians: f32,
+}
+
+impl RectInstance {
+    /// An upright, white rect with the same radius on every corner.
+    pub fn new(center: [f32; 2], size: [f32; 2], corner_radius_px: f32) -> Self {
+        Self {
+            center,
+            size,
+            corner_radius: [corner_radius_px; 4],
+            color: [1.0; 4],
+            orientation_radians: 0.0,
+        }
+    }
+
+    const ATTRS: [VertexAttribute; 5] = vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x4,
+        3 => Float32x4,
+        4 => Float32
+    ];
+
+    fn layout<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as _,
+            step_mode: VertexStepMode::Instance,
+            attributes: &Self::ATTRS,
+        }
+    }
+}
+
+/// Draws rounded rects as instances of one quad, all in a single draw call.
+///
+/// Shares `PainterSettings` with `Painter`, except `corner_radius_px`,
+/// which is replaced by each instance's radii.
+///
+/// For scenes of millions of rects, mostly off screen, see
+/// `enable_gpu_culling`.
+pub struct InstancePainter {
+    pipeline: RenderPipeline,
+    /// The color format the pipeline was made for.
+    format: TextureFormat,
+    /// The context's view uniforms, bound as group 1.
+    pub(crate) view: Arc<View>,
+    layout: BindGroupLayout,
+    bind_group: BindGroup,
+    uniforms: Buffer,
+    transform: Buffer,
+    tint: Buffer,
+    instances: Buffer,
+    instance_count: usize,
+    uploader: Uploader,
+    /// Set while culling on the GPU.
+    culling: Option<GpuCulling>,
+}
+
+impl InstancePainter {
+    pub(crate) fn new(rc: &Context) -> Self {
+        let Bindings {
+            layout,
+            bind_group,
+            uniforms,
+            transform,
+            tint,
+        } = Bindings::new(rc);
+
+        let pipeline = make_pipeline(rc, &layout);
+
+        // Grows as needed in `set_instances`.
+        let instances = rc.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Rect instance buffer"),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            contents: &[0; 64 * std::mem::size_of::<RectInstance>()],
+        });
+
+        Self {
+            pipeline,
+            format: rc.format(),
+            view: rc.view.clone(),
+            layout,
+            bind_group,
+            uniforms,
+            transform,
+            tint,
+            instances,
+            instance_count: 0,
+            uploader: Uploader::new(UploadStrategy::default()),
+            culling: None,
+        }
+    }
+
+    /// Remakes the painter's pipeline for the context's current `format()`.
+    /// See `Painter::rebuild_for_format`.
+    pub fn rebuild_for_format(&mut self, rc: &Context) {
+        if self.format != rc.format() {
+            self.pipeline = make_pipeline(rc, &self.layout);
+            self.format = rc.format();
+        }
+    }
+
+    pub fn set_uniforms(&self, rc: &Context, settings: &PainterSettings) {
+        let uniform = SettingsUniform::from(settings);
+        rc.commands
+            .write_buffer(&self.uniforms, 0, unsafe { as_raw_bytes(&uniform) });
+    }
+
+    /// Transforms the painter's rects before the context's orientation is
+    /// applied. The identity by default.
+    pub fn set_transform(&self, rc: &Context, transform: &Transform2D) {
+        transform::write_buffer(rc, &self.transform, transform);
+    }
+
+    /// Multiplies everything the painter draws. See
+    /// `Painter::set_global_tint`.
+    pub fn set_global_tint(&self, rc: &Context, tint: [f32; 4]) {
+        rc.commands
+            .write_buffer(&self.tint, 0, unsafe { as_raw_bytes(&tint) });
+    }
+
+    /// Selects how `set_instances` copies instances to the GPU.
+    pub fn set_upload_strategy(&mut self, strategy: UploadStrategy) {
+        if strategy != self.uploader.strategy() {
+            self.uploader = Uploader::new(strategy);
+        }
+    }
+
+    pub fn upload_strategy(&self) -> UploadStrategy {
+        self.uploader.strategy()
+    }
+
+    /// Bytes held in the painter's instance and uniform buffers. An
+    /// estimate for diagnostics.
+    pub fn gpu_memory_bytes(&self) -> usize {
+        [&self.instances, &self.uniforms, &self.transform, &self.tint]
+            .iter()
+            .map(|b| b.size() as usize)
+            .sum::<usize>()
+            + self.culling.as_ref().map_or(0, GpuCulling::buffer_sizes)
+    }
+
+    /// Culls the rects on the GPU before drawing them, or stops. Off by
+    /// default.
+    ///
+    /// Each draw then starts with compute passes testing every rect against
+    /// clip space, after the painter's transform and the context's view,
+    /// and copying those with any part on screen, in their order, to a
+    /// buffer of their own. The render pass draws that buffer with
+    /// `draw_indirect`, so the count never comes back to the CPU. That
+    /// saves vertex and rasterizer work on rects off screen, and the CPU a
+    /// pass over them to cull them itself; rects on screen cost a little
+    /// more, so it only pays off when most are off. Degenerate rects are
+    /// dropped too.
+    ///
+    /// Returns whether culling is on afterwards: it stays off on devices
+    /// without compute shaders, such as WebGL2, and the painter draws
+    /// everything as before. Fails with `GeometryError::TooLargeToCull`,
+    /// leaving it off, if the current instances don't fit in one storage
+    /// buffer binding (`max_storage_buffer_binding_size`, 128 MiB or about
+    /// 2.5 million rects by default); while it's on, `set_instances` fails
+    /// like that too.
+    ///
+    /// Draws record the culling themselves, but `paint` can't inside a
+    /// render pass: call `record_culling` on the encoder first, whenever
+    /// the instances, settings, transform or the context's view changed
+    /// since the last time.
+    pub fn enable_gpu_culling(
+        &mut self,
+        rc: &Context,
+        enable: bool,
+    ) -> Result<bool, GeometryError> {
+        if !enable || !GpuCulling::supported(rc) {
+            self.culling = None;
+            return Ok(false);
+        }
+        if self.culling.is_some() {
+            return Ok(true);
+        }
+        let mut culling = GpuCulling::new(rc);
+        // Remade as a storage buffer the passes can read, keeping the
+        // instances.
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        let instances = rc.device.create_buffer(&BufferDescriptor {
+            label: Some("Rect instance buffer"),
+            size: self.instances.size(),
+            usage: self.instances.usage() | BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        commands.copy_buffer_to_buffer(&self.instances, 0, &instances, 0, instances.size());
+        rc.commands.submit(std::iter::once(commands.finish()));
+        self.instances = instances;
+        culling.update(
+            rc,
+            &self.instances,
+            self.instance_count,
+            [&self.uniforms, &self.transform],
+            &self.view,
+        )?;
+        self.culling = Some(culling);
+        Ok(true)
+    }
+
+    /// Whether `enable_gpu_culling` turned culling on.
+    pub fn gpu_culling(&self) -> bool {
+        self.culling.is_some()
+    }
+
+    /// Records the culling passes into `commands`, for drawing with
+    /// `paint` in a render pass begun on it afterwards. Does nothing with
+    /// culling off. `draw` and the context's draws do this themselves.
+    pub fn record_culling(&self, rc: &Context, commands: &mut CommandEncoder) {
+        if let Some(culling) = &self.culling {
+            culling.record(rc, commands);
+        }
+    }
+
+    /// With culling on, the buffer `paint` takes its draw's arguments from,
+    /// e.g. to copy back and count the rects that survived.
+    ///
+    /// It holds wgpu's `util::DrawIndirect`, four `u32`s in the device's
+    /// byte order: `vertex_count` (6, the quad), `instance_count` (the
+    /// survivors), `first_vertex` and `first_instance` (both 0). A plain
+    /// instanced draw, so it needs no `INDIRECT_FIRST_INSTANCE`. Written
+    /// by `record_culling`, so it's only current once that has run on the
+    /// GPU.
+    pub fn indirect_args(&self) -> Option<&Buffer> {
+        self.culling.as_ref().map(|culling| &culling.args)
+    }
+
+    fn instance_usages(&self) -> BufferUsages {
+        let usages = BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC;
+        if self.culling.is_some() {
+            usages | BufferUsages::STORAGE
+        } else {
+            usages
+        }
+    }
+
+    /// Reallocates the instance buffer to fit the current rects. See
+    /// `Painter::shrink_to_fit`.
+    pub fn shrink_to_fit(&mut self, rc: &Context) {
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        shrink(
+            rc,
+            &mut commands,
+            &mut self.instances,
+            self.instance_count * std::mem::size_of::<RectInstance>(),
+            "Rect instance buffer",
+        );
+        rc.commands.submit(std::iter::once(commands.finish()));
+        if let Some(culling) = &mut self.culling {
+            culling
+                .update(
+                    rc,
+                    &self.instances,
+                    self.instance_count,
+                    [&self.uniforms, &self.transform],
+                    &self.view,
+                )
+                .expect("The instances fit before shrinking");
+        }
+    }
+
+    /// Uploads rects for drawing.
+    ///
+    /// Fails if the instance buffer would be larger than the device's
+    /// `max_buffer_size`, or with culling on if it's too large to cull (see
+    /// `enable_gpu_culling`). The painter draws nothing until the next
+    /// successful call in that case.
+    pub fn set_instances(
+        &mut self,
+        rc: &Context,
+        instances: &[RectInstance],
+    ) -> Result<(), GeometryError> {
+        self.instance_count = 0;
+        let usages = self.instance_usages();
+        grow(
+            rc,
+            &mut self.instances,
+            std::mem::size_of_val(instances),
+            "Rect instance buffer",
+            usages,
+        )?;
+        if let Some(culling) = &mut self.culling {
+            culling.update(
+                rc,
+                &self.instances,
+                instances.len(),
+                [&self.uniforms, &self.transform],
+                &self.view,
+            )?;
+        }
+        self.instance_count = instances.len();
+        self.uploader.write(
+            rc,
+            &[(&self.instances, 0, unsafe { as_u8_slice(instances) })],
+        );
+        Ok(())
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `rc` isn't on the device the painter was made with.
+    pub fn draw(
+        &self,
+        rc: &Context,
+        view: &TextureView,
+        clear_color: impl Into<Option<Color>>,
+    ) -> Result<(), SurfaceError> {
+        self.view.assert_same_device(rc);
+        let clear_color = clear_color.into().unwrap_or(rc.clear_color());
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        self.record_culling(rc, &mut commands);
+        {
+            let builder = RenderPassBuilder::new(view).clear_color(clear_color);
+            let mut pass = builder.begin(rc, &mut commands);
+            self.paint(&mut pass);
+        }
+        rc.commands.submit(std::iter::once(commands.finish()));
+        Ok(())
+    }
+
+    /// Acquires the next frame, draws into it, and presents it.
+    ///
+    /// If the surface is lost or outdated it's reconfigured and acquisition
+    /// is retried once before giving up. Does nothing while the context is
+    /// paused (see `Context::set_active`) or minimized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context has no surface (see `Context::from_raw`).
+    pub fn present(
+        &self,
+        rc: &mut Context,
+        clear_color: impl Into<Option<Color>>,
+    ) -> Result<(), SurfaceError> {
+        let Some(frame) = rc.acquire_frame()? else {
+            return Ok(());
+        };
+        let view = frame.texture.create_view(&TextureViewDescriptor::default());
+        self.draw(rc, &view, clear_color)?;
+        rc.present_frame(frame);
+        Ok(())
+    }
+
+    /// Records the draw into a render pass someone else began.
+    ///
+    /// All the rects go out in one draw call. With culling on, only those
+    /// `record_culling` last kept do.
+    pub fn paint<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        if self.instance_count == 0 {
+            // Empty buffer slices aren't allowed.
+            return;
+        }
+        debug!("rect instance count {}", self.instance_count);
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_bind_group(1, &self.view.bind_group, &[]);
+        if let Some(culling) = &self.culling {
+            culling.paint(pass);
+            return;
+        }
+        pass.set_vertex_buffer(
+            0,
+            self.instances
+                .slice(..(std::mem::size_of::<RectInstance>() * self.instance_count) as u64),
+        );
+        pass.draw(0..6, 0..self.instance_count as u32);
+    }
+}
+
+fn make_pipeline(rc: &Context, layout: &BindGroupLayout) -> RenderPipeline {
+    let module = &rc.device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Rect instance painter shader module"),
+        source: ShaderSource::Wgsl(include_str!("instanced.wgsl").into()),
+    });
+
+    rc.device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Rect Instance Painter Render Pipeline"),
+        layout: Some(
+            &rc.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Rect Instance Painter Render Pipeline Layout"),
+                bind_group_layouts: &[layout, &rc.view.layout],
+                push_constant_ranges: &[],
+            }),
+        ),
+        vertex: VertexState {
+            module,
+            entry_point: "vs",
+            buffers: &[RectInstance::layout()],
+        },
+        fragment: Some(FragmentState {
+            module,
+            entry_point: "fs",
+            targets: &[Some(ColorTargetState {
+                format: rc.config.format,
+                blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Back),
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: ignore_depth_stencil(rc),
+        multisample: MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{context, Target};
+
+    /// The `DrawIndirect` culling last wrote.
+    fn indirect_args(rc: &Context, painter: &InstancePainter) -> [u32; 4] {
+        let bytes = crate::util::read_buffer(rc, painter.indirect_args().unwrap(), 16);
+        std::array::from_fn(|i| u32::from_ne_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap()))
+    }
+
+    /// `count` overlapping rects of different colors, every `off`th one
+    /// moved off screen.
+    fn rects(count: usize, off: usize) -> Vec<RectInstance> {
+        (0..count)
+            .map(|i| {
+                let t = i as f32;
+                let center = if i % off == 0 {
+                    [3.0, 0.5 * t.cos()]
+                } else {
+                    [0.5 * t.sin(), 0.5 * t.cos()]
+                };
+                RectInstance {
+                    color: [t.sin().abs(), (0.7 * t).cos().abs(), (t % 8.0) / 8.0, 1.0],
+                    ..RectInstance::new(center, [0.4, 0.3], 0.0)
+                }
+            })
+            .collect()
+    }
+
+    fn drawn(rc: &Context, painter: &InstancePainter, target: &Target) -> Vec<u8> {
+        painter.draw(rc, &target.view, Color::BLACK).unwrap();
+        target.read(rc).rgba
+    }
+
+    #[test]
+    fn culling_keeps_the_rects_on_screen() {
+        let Some(rc) = context(32, 32) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let mut painter = rc.make_rect_instance_painter();
+        painter.set_uniforms(&rc, &Default::default());
+        assert!(painter.enable_gpu_culling(&rc, true).unwrap());
+        // A quarter off screen, and one straddling the right edge.
+        let mut instances = rects(1000, 4);
+        instances.push(RectInstance::new([1.1, 0.0], [0.4, 0.4], 0.0));
+        painter.set_instances(&rc, &instances).unwrap();
+        painter.draw(&rc, &target.view, Color::BLACK).unwrap();
+        assert_eq!(indirect_args(&rc, &painter), [6, 751, 0, 0]);
+    }
+
+    #[test]
+    fn culling_draws_what_drawing_everything_does() {
+        let Some(rc) = context(64, 64) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let mut painter = rc.make_rect_instance_painter();
+        painter.set_uniforms(&rc, &Default::default());
+        painter.set_instances(&rc, &rects(1000, 3)).unwrap();
+        let unculled = drawn(&rc, &painter, &target);
+        assert!(painter.enable_gpu_culling(&rc, true).unwrap());
+        // Equal only if the survivors overlap in their original order.
+        assert!(drawn(&rc, &painter, &target) == unculled);
+    }
+
+    #[test]
+    fn culling_spreads_workgroups_over_rows() {
+        let Some(mut rc) = context(64, 64) else {
+            return;
+        };
+        // 274 groups of 256, as 16 by 18, with a few past the last.
+        rc.limits.max_compute_workgroups_per_dimension = 16;
+        let target = Target::new(&rc);
+        let mut painter = rc.make_rect_instance_painter();
+        painter.set_uniforms(&rc, &Default::default());
+        painter.set_instances(&rc, &rects(70_000, 10)).unwrap();
+        let unculled = drawn(&rc, &painter, &target);
+        assert!(painter.enable_gpu_culling(&rc, true).unwrap());
+        assert!(drawn(&rc, &painter, &target) == unculled);
+        assert_eq!(indirect_args(&rc, &painter), [6, 63_000, 0, 0]);
+    }
+
+    #[test]
+    fn set_instances_too_large_to_cull_fails() {
+        let Some(mut rc) = context(8, 8) else {
+            return;
+        };
+        let size = std::mem::size_of::<RectInstance>() as u64;
+        rc.limits.max_storage_buffer_binding_size = 10 * size as u32;
+        let mut painter = rc.make_rect_instance_painter();
+        assert!(painter.enable_gpu_culling(&rc, true).unwrap());
+        painter.set_instances(&rc, &rects(10, 2)).unwrap();
+        match painter.set_instances(&rc, &rects(11, 2)) {
+            Err(GeometryError::TooLargeToCull { requested, max }) => {
+                assert_eq!((requested, max), (11 * size, 10 * size));
+            }
+            other => panic!("{other:?}"),
+        }
+        // And nothing's drawn until instances fit again.
+        assert_eq!(painter.instance_count, 0);
+    }
+
+    #[test]
+    fn rounds_only_the_corners_given_a_radius() {
+        let Some(rc) = context(64, 64) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let mut painter = rc.make_rect_instance_painter();
+        painter.set_uniforms(
+            &rc,
+            &PainterSettings {
+                edge: [1.0; 4],
+                ..Default::default()
+            },
+        );
+        let white = [255; 4];
+        let black = [0, 0, 0, 255];
+        // The rect spans px 16 to 48. A radius of 100 is clamped to the
+        // 16 px half side, rounding the corner without growing the shape.
+        for (radius, center) in [(12.0, 28.0), (100.0, 32.0)] {
+            let instance = RectInstance {
+                corner_radius: [radius, 0.0, 0.0, 0.0],
+                ..RectInstance::new([0.0, 0.0], [1.0, 1.0], 0.0)
+            };
+            painter.set_instances(&rc, &[instance]).unwrap();
+            painter.draw(&rc, &target.view, Color::BLACK).unwrap();
+            let pixels = target.read(&rc);
+            let at = |x, y| pixels.at(x, y);
+            assert_eq!(at(16, 16), black, "radius {radius}");
+            for (x, y) in [(47, 16), (47, 47), (16, 47)] {
+                assert_eq!(at(x, y), white, "({x}, {y}) with radius {radius}");
+            }
+            // Just inside and outside the arc, on the diagonal.
+            let r = radius.min(16.0);
+            let inside = (center - 0.6 * r / 2f32.sqrt()) as u32;
+            let outside = (center - 1.2 * r / 2f32.sqrt()) as u32;
+            assert_eq!(at(inside, inside), white, "{inside} with radius {radius}");
+            assert_eq!(
+                at(outside, outside),
+                black,
+                "{outside} with radius {radius}"
+            );
+            // Nothing past the rect.
+            assert_eq!(at(15, 32), black, "radius {radius}");
+            assert_eq!(at(32, 15), black, "radius {radius}");
+        }
+    }
+
+    #[test]
+    fn alpha_cutoff_discards_fainter_instances() {
+        let Some(rc) = context(32, 32) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let mut painter = rc.make_rect_instance_painter();
+        painter.set_uniforms(
+            &rc,
+            &PainterSettings {
+                edge: [1.0; 4],
+                alpha_cutoff: Some(0.5),
+                ..Default::default()
+            },
+        );
+        // A quarter opaque on the left, three quarters on the right.
+        let half = |x, alpha| RectInstance {
+            color: [alpha; 4],
+            ..RectInstance::new([x, 0.0], [1.0, 2.0], 0.0)
+        };
+        painter
+            .set_instances(&rc, &[half(-0.5, 0.25), half(0.5, 0.75)])
+            .unwrap();
+        painter.draw(&rc, &target.view, Color::TRANSPARENT).unwrap();
+        let pixels = target.read(&rc);
+        assert_eq!(pixels.at(8, 16)[3], 0);
+        assert_eq!(pixels.at(24, 16)[3], 191);
+    }
+}
@@ -0,0 +1,165 @@
+struct Settings {
+    edge: vec4<f32>,
+    fill: vec4<f32>,
+    line_width_px: f32,
+    // Unused here. Each instance has its own radii.
+    corner_radius_px: f32,
+    debug_visualize: u32,
+    // See LineWidthSpace in mod.rs. 0 is Screen, 1 is World.
+    line_width_space: u32,
+    // See painter.wgsl.
+    alpha_cutoff: f32,
+    // See StrokeAlign in mod.rs. 0 is Inside, 1 is Center, 2 is Outside.
+    stroke_align: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> settings: Settings;
+
+// The painter's transform. See transform.rs.
+@group(0) @binding(1)
+var<uniform> transform: mat4x4<f32>;
+
+// Multiplies the output. See set_global_tint in mod.rs.
+@group(0) @binding(2)
+var<uniform> tint: vec4<f32>;
+
+struct View {
+    // Applied to clip space positions. See view.rs.
+    transform: mat4x4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> view: View;
+
+struct InstanceInput {
+    @location(0) center: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    // px: top-left, top-right, bottom-right, bottom-left
+    @location(2) corner_radius: vec4<f32>,
+    @location(3) color: vec4<f32>,
+    @location(4) orientation_radians: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) corner_radius: vec4<f32>,
+    @location(2) @interpolate(flat) color: vec4<f32>,
+    // Before the painter's transform, for world space line widths.
+    @location(3) world: vec2<f32>,
+}
+
+@vertex
+fn vs(
+    @builtin(vertex_index) vertex_index: u32,
+    rect: InstanceInput,
+) -> VertexOutput {
+    // Two counter-clockwise triangles covering the rect. Rounding only
+    // removes area, so nothing outside is needed but an edge band drawn
+    // past the rect. That's only known here for world space widths; screen
+    // space ones are cut off at the rect.
+    var corners = array<vec2<f32>, 6>(
+        vec2(-0.5, -0.5),
+        vec2(0.5, -0.5),
+        vec2(0.5, 0.5),
+        vec2(-0.5, -0.5),
+        vec2(0.5, 0.5),
+        vec2(-0.5, 0.5),
+    );
+    var margin = 0.0;
+    if settings.line_width_space == 1u {
+        margin = stroke_offset(settings.line_width_px);
+    }
+    let p = corners[vertex_index] * (rect.size + 2.0 * margin);
+    // The uv spans [-0.5, 0.5] across the rect itself, past it in the margin.
+    let corner = p / rect.size;
+    let c = cos(rect.orientation_radians);
+    let s = sin(rect.orientation_radians);
+
+    var out: VertexOutput;
+    out.world = vec2(
+        p.x * c - p.y * s + rect.center.x,
+        p.x * s + p.y * c + rect.center.y,
+    );
+    out.position = view.transform * transform * vec4<f32>(out.world, 0.0, 1.0);
+    out.tex_coords = corner;
+    out.corner_radius = rect.corner_radius;
+    out.color = rect.color;
+    return out;
+}
+
+// p    query point
+// b    half box shape (w/2,h/2) where (w,h) is the shape of the box.
+// r    corner radii: top-left, top-right, bottom-right, bottom-left
+//
+// Like sd_round_box in painter.wgsl, but with a radius per corner. Radii are
+// limited to the half size so they can't grow the shape.
+fn sd_round_box4(p: vec2<f32>, b: vec2<f32>, r: vec4<f32>) -> f32 {
+    var rr: f32;
+    if p.x > 0.0 {
+        rr = select(r.z, r.y, p.y > 0.0);
+    } else {
+        rr = select(r.w, r.x, p.y > 0.0);
+    }
+    rr = min(rr, min(b.x, b.y));
+    let q = abs(p) - b + rr;
+    return length(max(q, vec2<f32>())) + min(max(q.x, q.y), 0.0) - rr;
+}
+
+// See painter.wgsl.
+fn stroke_offset(line_width: f32) -> f32 {
+    switch settings.stroke_align {
+        case 1u: {
+            return 0.5 * line_width;
+        }
+        case 2u: {
+            return line_width;
+        }
+        default: {
+            return 0.0;
+        }
+    }
+}
+
+// See painter.wgsl. `dx` and `dy` are the world position's derivatives,
+// taken by the caller: the vertex stage reads `settings` too, and the GL
+// backend then brings functions using it into that stage, where
+// derivatives don't exist.
+fn line_width_px(dx: vec2<f32>, dy: vec2<f32>) -> f32 {
+    if settings.line_width_space == 0u {
+        return settings.line_width_px;
+    }
+    let world_per_px = sqrt(abs(dx.x * dy.y - dx.y * dy.x));
+    return settings.line_width_px / max(world_per_px, 1e-20);
+}
+
+@fragment
+fn fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Evaluate distance in viewport pixels. See painter.wgsl.
+    let duvdx = dpdx(in.tex_coords);
+    let duvdy = dpdy(in.tex_coords);
+    let s = vec2(length(vec2(duvdx.x, duvdy.x)), length(vec2(duvdx.y, duvdy.y)));
+
+    var d = sd_round_box4(in.tex_coords / s, 0.5 / s, in.corner_radius);
+    // Edges blend over ~1px on screen. See painter.wgsl.
+    let aa = max(fwidth(d), 1e-4);
+
+    let line_width = line_width_px(dpdx(in.world), dpdy(in.world));
+    d -= stroke_offset(line_width);
+    var color: vec4<f32>;
+    if d < -line_width {
+        let eps = d + line_width;
+        color = mix(settings.edge, settings.fill * in.color, saturate(-eps / aa));
+    } else if d < 0.0 {
+        color = settings.edge;
+        color.a = saturate(0.5 - d / aa);
+    } else {
+        discard;
+    }
+    color *= tint;
+    if color.a < settings.alpha_cutoff {
+        discard;
+    }
+    return color;
+}
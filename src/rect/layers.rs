@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use wgpu::{Color, LoadOp, TextureView};
+
+use super::{DrawStats, Painter, PainterSettings, RectScene};
+use crate::{Context, DrawOptions, GeometryError};
+
+/// Draws a `RectScene` a layer at a time, in increasing `SceneRect::layer`
+/// order, each in the blend mode and clip the scene gives it with
+/// `RectScene::set_layer_settings`, e.g. for a UI's background, content,
+/// overlays and tooltips.
+///
+/// Ordering: every rect of a layer is drawn before any of the next one,
+/// and within a layer rects go by `z_index`, then by the order they were
+/// added, as for `RectScene::upload`. The first layer clears the target;
+/// the rest draw over it. The stencil buffer is cleared before each layer,
+/// so every layer's clip starts afresh and clips don't leak into later
+/// layers, which also means layers don't keep masks drawn with
+/// `Painter::draw_mask`.
+///
+/// Performance: each layer has a painter of its own, with its own
+/// pipelines and buffers, made the first time the layer has rects and
+/// kept after it empties out, until the renderer is dropped. Each layer
+/// takes one render pass and submission, and a clipped layer a second draw
+/// for the clip. A scene edit re-encodes the whole scene once and
+/// re-uploads every layer; a layer settings change remakes only the
+/// changed layers' pipelines or clips. Merge layers that don't need
+/// settings of their own: a single `upload` and `draw` is cheaper.
+#[derive(Default)]
+pub struct LayerRenderer {
+    settings: PainterSettings,
+    painters: BTreeMap<u32, LayerPainter>,
+}
+
+struct LayerPainter {
+    painter: Painter,
+    /// The scene's `layer_tag` when the blend mode and clip were last
+    /// taken from it.
+    applied: Option<(u64, u64)>,
+}
+
+impl LayerRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Styles every layer's rects, as `Painter::set_uniforms`.
+    pub fn set_uniforms(&mut self, rc: &Context, settings: &PainterSettings) {
+        self.settings = *settings;
+        for layer in self.painters.values() {
+            layer.painter.set_uniforms(rc, settings);
+        }
+    }
+
+    /// The painter drawing `layer`, made if needed, e.g. to give it a
+    /// transform or style of its own. Its geometry, blend mode and clip
+    /// are replaced on the next `draw`.
+    pub fn painter_mut(&mut self, rc: &Context, layer: u32) -> &mut Painter {
+        let settings = &self.settings;
+        &mut self
+            .painters
+            .entry(layer)
+            .or_insert_with(|| {
+                let painter = rc.make_rect_painter();
+                painter.set_uniforms(rc, settings);
+                LayerPainter {
+                    painter,
+                    applied: None,
+                }
+            })
+            .painter
+    }
+
+    /// Clears `view` to `clear_color` (or the context's `clear_color()` for
+    /// `None`) and draws `scene`'s layers over it. See `LayerRenderer`.
+    ///
+    /// Fails like `Painter::set_geometry` if a layer's rects or clip don't
+    /// fit in a buffer, leaving the layers drawn so far.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a layer has a clip and the context wasn't built
+    /// `with_stencil(true)`.
+    pub fn draw(
+        &mut self,
+        rc: &Context,
+        scene: &RectScene,
+        view: &TextureView,
+        clear_color: impl Into<Option<Color>>,
+    ) -> Result<DrawStats, GeometryError> {
+        let clear_color = clear_color.into().unwrap_or(rc.clear_color());
+        let layers = scene.layers();
+        if layers.is_empty() {
+            rc.begin_frame(view, clear_color).finish();
+        }
+        let mut stats = DrawStats::default();
+        for (i, &layer) in layers.iter().enumerate() {
+            self.painter_mut(rc, layer);
+            let entry = self.painters.get_mut(&layer).unwrap();
+            let painter = &mut entry.painter;
+            if entry.applied != Some(scene.layer_tag()) {
+                let settings = scene.layer_settings(layer);
+                if painter.blend_mode() != settings.blend {
+                    painter
+                        .set_blend_mode(rc, settings.blend)
+                        .expect("Layer blend modes are checked when they're set");
+                }
+                match &settings.clip {
+                    Some(shapes) => painter.set_clip_path(rc, shapes)?,
+                    None => painter.clear_clip_path(),
+                }
+                entry.applied = Some(scene.layer_tag());
+            }
+            scene.upload_layer(rc, layer, painter)?;
+
+            let load = if i == 0 {
+                LoadOp::Clear(clear_color)
+            } else {
+                LoadOp::Load
+            };
+            painter.draw_with(rc, view, load, &DrawOptions::default());
+            stats.draw_calls += 1;
+            stats.vertices += painter.vertex_count;
+            stats.indexes += painter.index_count;
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rect::{BlendMode, Rect},
+        test_util::{context, Target},
+    };
+
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+
+    /// Blue in layer 0 and red in layer 1 overlap around x = 32, and green
+    /// in layer 2 sits over the red around x = 48. Z indexes put the blue
+    /// on top, if layers didn't come first.
+    fn scene() -> RectScene {
+        let mut scene = RectScene::new();
+        let rect = |x, size| Rect::new([x, 0.0], [size, size], 0.0);
+        scene.push_to_layer(0, rect(-0.25, 1.0), [0.0, 0.0, 1.0, 1.0], 5);
+        scene.push_to_layer(1, rect(0.25, 1.0), [1.0, 0.0, 0.0, 1.0], -5);
+        scene.push_to_layer(2, rect(0.5, 0.5), [0.0, 1.0, 0.0, 1.0], -10);
+        scene
+    }
+
+    fn row(rc: &Context, scene: &RectScene) -> [[u8; 4]; 3] {
+        let target = Target::new(rc);
+        LayerRenderer::new()
+            .draw(rc, scene, &target.view, Color::BLACK)
+            .unwrap();
+        let pixels = target.read(rc);
+        [16, 32, 48].map(|x| pixels.at(x, 32))
+    }
+
+    #[test]
+    fn later_layers_draw_over_higher_z_indexes() {
+        let Some(rc) = context(64, 64) else {
+            return;
+        };
+        assert_eq!(row(&rc, &scene()), [BLUE, RED, GREEN]);
+    }
+
+    #[test]
+    fn layer_blend_applies_to_its_layer_only() {
+        let Some(rc) = context(64, 64) else {
+            return;
+        };
+        let mut scene = scene();
+        scene.set_layer_blend(1, BlendMode::Additive).unwrap();
+        // The red adds to the blue under it, and the green still covers it.
+        assert_eq!(row(&rc, &scene), [BLUE, [255, 0, 255, 255], GREEN]);
+    }
+}
@@ -1,36 +1,170 @@
+use std::{ops::Range, sync::Arc};
+
 use log::debug;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    vertex_attr_array, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
-    BindGroupLayoutEntry, BindingType, BlendState, Buffer, BufferBindingType, BufferDescriptor,
-    BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoderDescriptor, Face,
-    FragmentState, FrontFace, IndexFormat, LoadOp, MultisampleState, Operations,
-    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology,
-    RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
-    ShaderModuleDescriptor, ShaderSource, ShaderStages, SurfaceError, TextureView, VertexAttribute,
-    VertexBufferLayout, VertexState, VertexStepMode,
+    vertex_attr_array, Backend, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendState, Buffer,
+    BufferBindingType, BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites,
+    CommandEncoder, CommandEncoderDescriptor, CompareFunction, DepthStencilState, Face,
+    FragmentState, FrontFace, IndexFormat, LoadOp, MultisampleState, PipelineLayout,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPass,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, StencilFaceState, StencilOperation, StencilState, SurfaceError, TextureFormat,
+    TextureView, TextureViewDescriptor, VertexAttribute, VertexBufferLayout, VertexState,
+    VertexStepMode, COPY_BUFFER_ALIGNMENT,
 };
 
-use crate::Context;
+use crate::{
+    transform,
+    upload::Uploader,
+    util::{as_raw_bytes, as_u8_slice, grow, shrink},
+    view::View,
+    BlendError, BlendMode, Context, DrawOptions, GeometryError, RenderPassBuilder, Transform2D,
+    UploadStrategy, STENCIL_FORMAT,
+};
 
-unsafe fn as_u8_slice<T>(x: &[T]) -> &[u8] {
-    std::slice::from_raw_parts(x.as_ptr() as *const u8, std::mem::size_of_val(x))
-}
+mod batches;
+mod clip;
+mod cull;
+mod feedback;
+mod geometry;
+mod grid;
+mod instanced;
+mod layers;
+mod polyline;
+mod scene;
+mod styles;
+mod theme;
 
-unsafe fn as_raw_bytes<T>(x: &T) -> &[u8] {
-    std::slice::from_raw_parts(x as *const T as *const u8, std::mem::size_of::<T>())
-}
+pub use batches::{Batch, DrawStats};
+pub use clip::ClipShape;
+pub use geometry::{Anchor, GeometryMode, Rect};
+pub use grid::Grid;
+pub use instanced::{InstancePainter, RectInstance};
+pub use layers::LayerRenderer;
+pub use polyline::{stroke_polyline, JoinStyle};
+pub use scene::{LayerSettings, RectScene, SceneRect};
+pub use theme::{Role, Theme};
 
+/// With the `bytemuck` feature, `Vertex` is `Pod`, so vertices can be cast
+/// to and from other plain data, e.g. `bytemuck::cast_slice::<f32, Vertex>`
+/// on 10 floats per vertex, without copying. The layout is `repr(C)` and
+/// follows the field order.
 #[repr(C)]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct Vertex {
     pub xyz: [f32; 3],
     pub uv: [f32; 2],
+    /// Multiplies the painter's fill color.
+    pub color: [f32; 4],
+    /// Multiplies everything drawn, edge included, and is interpolated
+    /// across the triangle. Varying it between vertices fades part of a
+    /// shape out, e.g. to feather one side of a rect.
+    ///
+    /// This applies on top of `color` and the alpha of the painter's fill
+    /// and edge colors: a fill alpha of 0.5 at a vertex with alpha 0.5
+    /// comes out a quarter opaque.
+    pub alpha: f32,
+}
+
+/// The color defaults to white and alpha to 1, which leave the output
+/// unchanged.
+impl From<([f32; 3], [f32; 2])> for Vertex {
+    fn from((xyz, uv): ([f32; 3], [f32; 2])) -> Self {
+        Self {
+            xyz,
+            uv,
+            color: Self::WHITE,
+            alpha: 1.0,
+        }
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<(glam::Vec3, glam::Vec2)> for Vertex {
+    fn from((xyz, uv): (glam::Vec3, glam::Vec2)) -> Self {
+        (xyz.to_array(), uv.to_array()).into()
+    }
+}
+
+#[cfg(feature = "glam")]
+impl Vertex {
+    pub fn position(&self) -> glam::Vec3 {
+        self.xyz.into()
+    }
+
+    pub fn tex_coords(&self) -> glam::Vec2 {
+        self.uv.into()
+    }
+}
+
+/// One triangle of a soup for `Painter::set_triangles`.
+///
+/// The vertices go counter-clockwise; clockwise triangles are culled.
+/// UVs work as they do for rects, where the shape spans `[-0.5, 0.5]`, so
+/// suitable ones draw an SDF rect clipped to the triangle. Giving all three
+/// the same UV, e.g. zero, fills the triangle flat with the painter's fill
+/// color instead, without an edge.
+#[derive(Debug, Copy, Clone)]
+pub struct Triangle {
+    pub verts: [Vertex; 3],
+    /// Multiplies each vertex's own color.
+    pub color: [f32; 4],
+}
+
+impl Triangle {
+    /// A flat filled triangle. See `Triangle`.
+    pub fn flat(positions: [[f32; 3]; 3], color: [f32; 4]) -> Self {
+        Self {
+            verts: positions.map(|xyz| (xyz, [0.0; 2]).into()),
+            color,
+        }
+    }
+}
+
+/// A textured quad of `size` about `center`, as vertices and the indexes
+/// of its two triangles, for drawing images with pipelines of your own.
+///
+/// The corners go counter-clockwise from the bottom left with y up, as in
+/// clip space: bottom left, bottom right, top right, top left. Their UVs
+/// are `Vertex::quad_uvs()`, spanning `[0, 1]` with `(0, 0)` at the top
+/// left like the sprite painter's. The triangles are `[0, 1, 2]` and
+/// `[0, 2, 3]`, both counter-clockwise, so they're front facing under
+/// the painters' `FrontFace::Ccw`.
+///
+/// These aren't the UVs the rect painter's shader expects: it measures the
+/// edge from UVs spanning `[-0.5, 0.5]`, and would draw this quad as a
+/// rect's corner. Use `Rect`s and `set_rects` for that.
+pub fn quad(center: impl Into<[f32; 2]>, size: impl Into<[f32; 2]>) -> ([Vertex; 4], [u32; 6]) {
+    let [cx, cy] = center.into();
+    let [half_w, half_h] = size.into().map(|e| 0.5 * e);
+    let corners = [
+        [cx - half_w, cy - half_h],
+        [cx + half_w, cy - half_h],
+        [cx + half_w, cy + half_h],
+        [cx - half_w, cy + half_h],
+    ];
+    let uvs = Vertex::quad_uvs();
+    let vertices = std::array::from_fn(|i| ([corners[i][0], corners[i][1], 0.0], uvs[i]).into());
+    (vertices, [0, 1, 2, 0, 2, 3])
 }
 
 impl Vertex {
-    const ATTRS: [VertexAttribute; 2] = vertex_attr_array![
+    pub const WHITE: [f32; 4] = [1.0; 4];
+
+    /// The UVs of `quad`'s corners, in its order: `[0, 1]`, `[1, 1]`,
+    /// `[1, 0]`, `[0, 0]`.
+    pub fn quad_uvs() -> [[f32; 2]; 4] {
+        [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]
+    }
+
+    const ATTRS: [VertexAttribute; 4] = vertex_attr_array![
         0 => Float32x3,
-        1 => Float32x2
+        1 => Float32x2,
+        2 => Float32x4,
+        3 => Float32
     ];
 
     fn layout<'a>() -> VertexBufferLayout<'a> {
@@ -42,16 +176,167 @@ impl Vertex {
     }
 }
 
-#[repr(C, align(16))]
+/// How a painter styles its rects. Uploaded as a `SettingsUniform`.
+///
+/// `edge` and `fill` are premultiplied colors in the context's
+/// `output_color_space()`, linear or encoded depending on the format; see
+/// `ColorSpace`.
+///
+/// It isn't plain data, since `alpha_cutoff` is an `Option`, so it's no
+/// longer `NoUninit` with the `bytemuck` feature. Code that cast it with
+/// `bytemuck::bytes_of(&settings)` casts
+/// `SettingsUniform::from(&settings)` instead.
 #[derive(Debug, Copy, Clone)]
 pub struct PainterSettings {
     pub edge: [f32; 4],
     pub fill: [f32; 4],
+    /// The edge's width, in the units picked by `line_width_space`.
     pub line_width_px: f32,
     pub corner_radius_px: f32,
+    pub debug_visualize: DebugViz,
+    pub line_width_space: LineWidthSpace,
+    /// Where the edge band sits relative to the rect's outline.
+    pub stroke_align: StrokeAlign,
+    /// Discards fragments whose final alpha, after per-vertex alpha and the
+    /// global tint, is below this, for cutouts such as foliage. `None`, the
+    /// default, blends everything.
+    ///
+    /// Fragments that are kept still blend with their alpha, and with
+    /// `DepthSettings::depth_write` only they write depth, so the cutout's
+    /// holes don't hide what's drawn behind them later and cutout layers
+    /// don't need sorting among themselves. The cut is binary per pixel,
+    /// so cut edges alias. Painters draw single-sampled; with MSAA,
+    /// alpha-to-coverage would smooth them, but that takes a multisampled
+    /// pipeline of your own (`MultisampleState::alpha_to_coverage_enabled`)
+    /// drawing this shader.
+    pub alpha_cutoff: Option<f32>,
+}
+
+/// `PainterSettings` as uploaded, the `Settings` struct of `SHADER_SOURCE`,
+/// with `alpha_cutoff`'s `None` as 0, for hosts writing the uniform
+/// buffers of their own pipelines.
+///
+/// With the `bytemuck` feature it's `NoUninit`, so `bytemuck::bytes_of`
+/// gives its bytes as uploaded. It can't be `Pod` because its enums don't
+/// accept every bit pattern.
+#[repr(C, align(16))]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::NoUninit))]
+pub struct SettingsUniform {
+    edge: [f32; 4],
+    fill: [f32; 4],
+    line_width_px: f32,
+    corner_radius_px: f32,
+    debug_visualize: DebugViz,
+    line_width_space: LineWidthSpace,
+    alpha_cutoff: f32,
+    stroke_align: StrokeAlign,
+    /// Up to the struct's 16 byte alignment, so it has no implicit padding.
+    _padding: [u32; 2],
+}
+
+impl From<&PainterSettings> for SettingsUniform {
+    fn from(settings: &PainterSettings) -> Self {
+        Self {
+            edge: settings.edge,
+            fill: settings.fill,
+            line_width_px: settings.line_width_px,
+            corner_radius_px: settings.corner_radius_px,
+            debug_visualize: settings.debug_visualize,
+            line_width_space: settings.line_width_space,
+            alpha_cutoff: settings.alpha_cutoff.unwrap_or(0.0),
+            stroke_align: settings.stroke_align,
+            _padding: [0; 2],
+        }
+    }
+}
+
+/// What `PainterSettings::line_width_px` is measured in.
+#[repr(u32)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::NoUninit))]
+pub enum LineWidthSpace {
+    /// Viewport pixels. Edges stay the same width however the painter's
+    /// transform zooms, so a width of 1 draws a hairline, as in diagram
+    /// and CAD views.
+    #[default]
+    Screen,
+    /// The coordinates rects are given in, before the painter's transform,
+    /// so edges scale with the rects when zooming.
+    ///
+    /// On screen the width works out to `line_width_px * s * w / 2` px,
+    /// where `s` is the transform's scale (the length of its first column
+    /// for uniform scales) and `w` is the viewport's width in px, since
+    /// clip space spans 2 units. Non-uniform scales use their geometric
+    /// mean.
+    World,
+}
+
+/// Where `PainterSettings`' edge band sits relative to a rect's outline,
+/// which decides how big the rect looks.
+///
+/// The fill always ends `line_width_px` inside the outer side of the band.
+/// Only geometry is shaded, and `set_rects` makes it end at the rect, so
+/// for `Center` and `Outside` give it room with
+/// `Painter::set_geometry_margin`, or the band is cut off at the rect.
+/// `InstancePainter` makes room itself for `LineWidthSpace::World` widths
+/// only.
+#[repr(u32)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::NoUninit))]
+pub enum StrokeAlign {
+    /// The band lies within the rect, so the rect looks its size and the
+    /// fill shrinks by the line width on each side. How oreb has always
+    /// drawn edges.
+    #[default]
+    Inside,
+    /// The band straddles the outline, so the rect looks half a line width
+    /// bigger on each side, its corners rounded by half a line width more,
+    /// and the fill half a line width smaller.
+    Center,
+    /// The band lies around the rect, so the rect looks a line width
+    /// bigger on each side, its corners rounded by the line width more,
+    /// and the fill is the whole rect.
+    Outside,
+}
+
+/// Shows one of the shader's intermediate values instead of the styled rect.
+///
+/// A development aid for working on the shader. Only takes effect in debug
+/// builds; release builds compile the visualization out.
+#[repr(u32)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::NoUninit))]
+pub enum DebugViz {
+    #[default]
+    None,
+    /// Signed distance to the edge as a color ramp. Orange outside, blue
+    /// inside, with bands about every 12 px and a white line on the edge.
+    Distance,
+    /// Tex coords as red and green, where the rect spans `[0, 1]`.
+    UV,
+    /// The antialiased coverage of the shape as grayscale.
+    Coverage,
+}
+
+/// How a painter's pipelines use the depth half of the context's
+/// depth/stencil buffer. See `Painter::set_depth`.
+///
+/// These are pipeline state rather than uniforms, so they live apart from
+/// `PainterSettings`. Only meaningful when the context was built
+/// `with_stencil(true)`, which is what allocates the depth buffer. Depth
+/// comes from the `z` of `Vertex::xyz` after the transforms, and smaller
+/// is nearer.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct DepthSettings {
+    /// Whether fragments write their depth.
+    pub depth_write: bool,
+    /// Whether fragments are discarded unless they're nearer than what the
+    /// depth buffer holds (`Less`). Otherwise everything passes (`Always`).
+    pub depth_test: bool,
 }
 
-impl PainterSettings {
+impl SettingsUniform {
     fn descriptor<'a>() -> BufferDescriptor<'a> {
         BufferDescriptor {
             label: None,
@@ -69,22 +354,271 @@ impl Default for PainterSettings {
             fill: [1.0, 1.0, 1.0, 1.0],
             line_width_px: 2.0,
             corner_radius_px: 0.0,
+            debug_visualize: DebugViz::None,
+            line_width_space: LineWidthSpace::Screen,
+            stroke_align: StrokeAlign::Inside,
+            alpha_cutoff: None,
         }
     }
 }
 
-pub struct Painter {
-    pipeline: RenderPipeline,
+/// The rect painter's WGSL, with entry points `vs` and `fs`.
+///
+/// Bindings:
+///
+/// - group 0, binding 0: `PainterSettings`, as the shader's `Settings`
+///   struct, vertex and fragment stages.
+/// - group 0, binding 1: the painter's transform as a `mat4x4<f32>`
+///   (see `set_transform`), vertex stage.
+/// - group 0, binding 2: the global tint as a `vec4<f32>` (see
+///   `set_global_tint`), fragment stage.
+/// - group 1, binding 0: the context's view uniforms, a struct holding one
+///   `mat4x4<f32>`, vertex stage. Shared by every painter of a context.
+///
+/// Vertex buffer 0 holds `Vertex`es at locations 0 (position), 1 (uv),
+/// 2 (color) and 3 (alpha). Release builds load this with
+/// `DEBUG_VIZ_ENABLED` set to false, `ShaderVariant::FineDerivatives` swaps
+/// its derivatives, and `ShaderVariant::ConstantAa` clears `DERIVATIVE_AA`.
+pub const SHADER_SOURCE: &str = include_str!("painter.wgsl");
+
+/// Which version of `SHADER_SOURCE` a painter runs.
+///
+/// The antialiasing comes from screen space derivatives of the tex coords
+/// and of the distance to the edge (`fwidth`), and drivers differ in how
+/// precisely they compute plain `dpdx`/`dpdy`/`fwidth`.
+/// Painters pick a variant from `Context::backend()`:
+///
+/// - Vulkan, Metal, DX12, DX11 and WebGPU get `Standard`.
+/// - GL gets `FineDerivatives`, since GL drivers often compute coarse
+///   derivatives (one per 2x2 block of pixels) by default, which makes
+///   rotated edges look softer and more jagged than on Metal.
+/// - `Backend::Empty`, a `from_raw` context that wasn't told its backend,
+///   gets `Standard`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ShaderVariant {
+    /// `dpdx` and `dpdy`, at whatever precision the driver picks.
+    #[default]
+    Standard,
+    /// `dpdxFine`, `dpdyFine` and `fwidthFine`, computed for every pixel.
+    /// GL only honors this from GL 4.5; older GL and GLES draw as with
+    /// `Standard`.
+    FineDerivatives,
+    /// Blends edges over a fixed 1px band of the estimated distance,
+    /// skipping the `fwidth` of it. Every wgpu backend has derivatives, so
+    /// no backend gets this by default; it's a fallback for drivers whose
+    /// `fwidth` is broken, and matches how painters drew before edges were
+    /// measured. Edges get softer or harder than 1px where the distance
+    /// estimate is off, e.g. on skewed rects.
+    ConstantAa,
+}
+
+impl ShaderVariant {
+    /// The variant painters use on `backend`.
+    pub fn for_backend(backend: Backend) -> Self {
+        match backend {
+            Backend::Gl => ShaderVariant::FineDerivatives,
+            _ => ShaderVariant::Standard,
+        }
+    }
+}
+
+fn shader_source(variant: ShaderVariant) -> String {
+    let mut source = SHADER_SOURCE.to_owned();
+    if !cfg!(debug_assertions) {
+        source = source.replace(
+            "const DEBUG_VIZ_ENABLED: bool = true;",
+            "const DEBUG_VIZ_ENABLED: bool = false;",
+        );
+    }
+    if variant == ShaderVariant::FineDerivatives {
+        source = source
+            .replace("dpdx(", "dpdxFine(")
+            .replace("dpdy(", "dpdyFine(")
+            .replace("fwidth(", "fwidthFine(");
+    }
+    if variant == ShaderVariant::ConstantAa {
+        source = source.replace(
+            "const DERIVATIVE_AA: bool = true;",
+            "const DERIVATIVE_AA: bool = false;",
+        );
+    }
+    source
+}
+
+/// What the painter's vertex buffer is always made with. `COPY_SRC` is for
+/// `shrink_to_fit`.
+const VERTEX_USAGES: BufferUsages = BufferUsages::VERTEX
+    .union(BufferUsages::COPY_DST)
+    .union(BufferUsages::COPY_SRC);
+
+/// The most styles a painter holds. See `Painter::define_style`.
+pub const MAX_STYLES: usize = 16;
+
+/// How a pipeline uses the context's stencil buffer, if it has one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum StencilUse {
+    Ignore,
+    /// Writes `MASK_REFERENCE` wherever the shape is, without touching color.
+    Write,
+    /// Only draws where the stencil holds `MASK_REFERENCE`.
+    Test,
+}
+
+/// The stencil value marking the inside of a mask.
+const MASK_REFERENCE: u32 = 1;
+
+/// A pass that keeps both color and depth/stencil.
+fn keep_pass(view: &TextureView) -> RenderPassBuilder<'_> {
+    RenderPassBuilder::new(view)
+        .load(LoadOp::Load)
+        .depth_stencil_options(DrawOptions {
+            clear_depth_stencil: false,
+            ..Default::default()
+        })
+}
+
+struct MaskPipelines {
+    mask: RenderPipeline,
+    masked: RenderPipeline,
+}
+
+fn make_pipeline(
+    rc: &Context,
+    layout: &PipelineLayout,
+    module: &ShaderModule,
+    stencil: StencilUse,
+    depth: DepthSettings,
+    blend: BlendState,
+) -> RenderPipeline {
+    rc.device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("My Painter Render Pipeline"),
+        layout: Some(layout),
+        vertex: VertexState {
+            module,
+            entry_point: "vs",
+            buffers: &[Vertex::layout()],
+        },
+        fragment: Some(FragmentState {
+            module,
+            entry_point: "fs",
+            targets: &[Some(ColorTargetState {
+                format: rc.config.format,
+                blend: Some(blend),
+                write_mask: if stencil == StencilUse::Write {
+                    ColorWrites::empty()
+                } else {
+                    ColorWrites::ALL
+                },
+            })],
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Back),
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: rc.has_stencil().then(|| {
+            let (compare, pass_op) = match stencil {
+                StencilUse::Ignore => (CompareFunction::Always, StencilOperation::Keep),
+                StencilUse::Write => (CompareFunction::Always, StencilOperation::Replace),
+                StencilUse::Test => (CompareFunction::Equal, StencilOperation::Keep),
+            };
+            let face = StencilFaceState {
+                compare,
+                fail_op: StencilOperation::Keep,
+                depth_fail_op: StencilOperation::Keep,
+                pass_op,
+            };
+            DepthStencilState {
+                format: STENCIL_FORMAT,
+                depth_write_enabled: depth.depth_write,
+                depth_compare: if depth.depth_test {
+                    CompareFunction::Less
+                } else {
+                    CompareFunction::Always
+                },
+                stencil: StencilState {
+                    front: face,
+                    back: face,
+                    read_mask: !0,
+                    write_mask: !0,
+                },
+                bias: Default::default(),
+            }
+        }),
+        multisample: MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+/// The painter's pipeline, and its masking pipelines if the context has a
+/// stencil buffer.
+fn make_pipelines(
+    rc: &Context,
+    layout: &BindGroupLayout,
+    depth: DepthSettings,
+    variant: ShaderVariant,
+    blend: BlendState,
+) -> (RenderPipeline, Option<MaskPipelines>) {
+    let module = &rc.device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("My Painter shader module"),
+        source: ShaderSource::Wgsl(shader_source(variant).into()),
+    });
+
+    let pipeline_layout = rc.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("My Painter Render Pipeline Layout"),
+        bind_group_layouts: &[layout, &rc.view.layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = make_pipeline(
+        rc,
+        &pipeline_layout,
+        module,
+        StencilUse::Ignore,
+        depth,
+        blend,
+    );
+    let mask_pipelines = rc.has_stencil().then(|| MaskPipelines {
+        mask: make_pipeline(
+            rc,
+            &pipeline_layout,
+            module,
+            StencilUse::Write,
+            depth,
+            blend,
+        ),
+        masked: make_pipeline(rc, &pipeline_layout, module, StencilUse::Test, depth, blend),
+    });
+    (pipeline, mask_pipelines)
+}
+
+/// Progress of a chunked upload. See `Painter::begin_streaming`.
+struct Stream {
+    declared_vertices: usize,
+    declared_indexes: usize,
+    vertices: usize,
+    indexes: usize,
+}
+
+/// Group 0 of the rect shaders: `PainterSettings`, the transform and the
+/// global tint.
+struct Bindings {
+    layout: BindGroupLayout,
     bind_group: BindGroup,
     uniforms: Buffer,
-    vertices: Buffer,
-    vertex_count: usize,
-    indexes: Buffer,
-    index_count: usize,
+    transform: Buffer,
+    tint: Buffer,
 }
 
-impl Painter {
-    pub(crate) fn new(rc: &Context) -> Self {
+impl Bindings {
+    fn new(rc: &Context) -> Self {
         // Memory layout for the painter
         let layout = rc
             .device
@@ -102,158 +636,1595 @@ impl Painter {
                         },
                         count: None,
                     },
+                    // Transform
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::VERTEX,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Tint
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
-        let uniforms = rc.device.create_buffer(&PainterSettings::descriptor());
+        let uniforms = rc.device.create_buffer(&SettingsUniform::descriptor());
+        let transform = transform::make_buffer(rc, &Transform2D::IDENTITY);
+        let tint = rc.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Painter tint"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            contents: unsafe { as_raw_bytes(&[1.0f32; 4]) },
+        });
 
         let bind_group = rc.device.create_bind_group(&BindGroupDescriptor {
             label: Some("My painter bind group"),
             layout: &layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: uniforms.as_entire_binding(),
-            }],
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniforms.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: transform.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: tint.as_entire_binding(),
+                },
+            ],
         });
 
-        let module = &rc.device.create_shader_module(ShaderModuleDescriptor {
-            label: Some("My Painter shader module"),
-            source: ShaderSource::Wgsl(include_str!("painter.wgsl").into()),
-        });
+        Self {
+            layout,
+            bind_group,
+            uniforms,
+            transform,
+            tint,
+        }
+    }
+}
 
-        let pipeline = rc.device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("My Painter Render Pipeline"),
-            layout: Some(
-                &rc.device.create_pipeline_layout(&PipelineLayoutDescriptor {
-                    label: Some("My Painter Render Pipeline Layout"),
-                    bind_group_layouts: &[&layout],
-                    push_constant_ranges: &[],
-                }),
-            ),
-            vertex: VertexState {
-                module,
-                entry_point: "vs",
-                buffers: &[Vertex::layout()],
-            },
-            fragment: Some(FragmentState {
-                module,
-                entry_point: "fs",
-                targets: &[Some(ColorTargetState {
-                    format: rc.config.format,
-                    blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                    write_mask: ColorWrites::ALL,
-                })],
-            }),
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
-                unclipped_depth: false,
-                polygon_mode: PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
+pub struct Painter {
+    pipeline: RenderPipeline,
+    /// The color format the pipelines were made for.
+    format: TextureFormat,
+    /// The context's view uniforms, bound as group 1.
+    pub(crate) view: Arc<View>,
+    /// Only when the context has a stencil buffer.
+    mask_pipelines: Option<MaskPipelines>,
+    /// What the pipelines were made with.
+    depth: DepthSettings,
+    shader_variant: ShaderVariant,
+    blend: BlendMode,
+    /// Made by the first `set_feedback_source`.
+    feedback: Option<feedback::Feedback>,
+    feedback_decay: f32,
+    /// Made by the first `define_style`.
+    styles: Option<styles::Styles>,
+    /// Set by `set_clip_path`.
+    clip: Option<clip::Clip>,
+    /// Made by the first `draw_batches`.
+    batches: Option<batches::BatchSettings>,
+    layout: BindGroupLayout,
+    bind_group: BindGroup,
+    uniforms: Buffer,
+    transform: Buffer,
+    tint: Buffer,
+    vertices: Buffer,
+    /// `VERTEX_USAGES` and whatever `with_vertex_usages` added.
+    vertex_usages: BufferUsages,
+    vertex_count: usize,
+    indexes: Buffer,
+    index_count: usize,
+    /// Whether `indexes` holds `u16`s or `u32`s.
+    index_format: IndexFormat,
+    geometry_mode: GeometryMode,
+    reverse_winding: bool,
+    geometry_margin: f32,
+    /// Set between `begin_streaming` and `end_streaming`.
+    stream: Option<Stream>,
+    /// Set when the geometry came from `RectScene::upload`.
+    scene_tag: Option<scene::SceneTag>,
+    uploader: Uploader,
+    max_buffer_size: u64,
+}
+
+impl Painter {
+    pub(crate) fn new(rc: &Context) -> Self {
+        let Bindings {
+            layout,
+            bind_group,
+            uniforms,
+            transform,
+            tint,
+        } = Bindings::new(rc);
+
+        let shader_variant = ShaderVariant::for_backend(rc.backend());
+        let (pipeline, mask_pipelines) = make_pipelines(
+            rc,
+            &layout,
+            DepthSettings::default(),
+            shader_variant,
+            BlendMode::default().state(),
+        );
 
         // Geometry buffers
+        // These grow as needed in `set_geometry`.
         let vertices = rc.device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Painter vertex buffer"),
-            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-            contents: &[0; 6000], // FIXME: reallocation?
+            usage: VERTEX_USAGES,
+            contents: &[0; 6000],
         });
 
         let indexes = rc.device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Painter index buffer"),
-            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
-            contents: &[0; 6000], // FIXME: reallocation?
+            usage: BufferUsages::INDEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            contents: &[0; 6000],
         });
 
         Self {
             pipeline,
+            format: rc.format(),
+            view: rc.view.clone(),
+            mask_pipelines,
+            depth: DepthSettings::default(),
+            shader_variant,
+            blend: BlendMode::default(),
+            feedback: None,
+            feedback_decay: 0.9,
+            styles: None,
+            clip: None,
+            batches: None,
+            layout,
             bind_group,
             uniforms,
+            transform,
+            tint,
             vertices,
+            vertex_usages: VERTEX_USAGES,
             vertex_count: 0,
             indexes,
             index_count: 0,
+            index_format: IndexFormat::Uint32,
+            geometry_mode: GeometryMode::default(),
+            reverse_winding: false,
+            geometry_margin: 0.0,
+            stream: None,
+            scene_tag: None,
+            uploader: Uploader::new(UploadStrategy::default()),
+            max_buffer_size: rc.limits().max_buffer_size,
+        }
+    }
+
+    /// Remakes the painter's pipelines for the context's current
+    /// `format()`, after it changed from the one the painter was made with.
+    /// Drawing to a target of another format is a wgpu validation error.
+    ///
+    /// Geometry, uniforms and the transform are kept; only the pipelines
+    /// are replaced. Does nothing if the format is unchanged. Painters
+    /// aren't tracked by their context, so this is up to the caller
+    /// whenever the format changes.
+    pub fn rebuild_for_format(&mut self, rc: &Context) {
+        if self.format == rc.format() {
+            return;
+        }
+        (self.pipeline, self.mask_pipelines) = make_pipelines(
+            rc,
+            &self.layout,
+            self.depth,
+            self.shader_variant,
+            self.blend.state(),
+        );
+        if let Some(feedback) = &mut self.feedback {
+            feedback.rebuild(rc);
+        }
+        self.format = rc.format();
+    }
+
+    /// Changes how the painter's pipelines use depth, remaking them if
+    /// needed. Both are off by default.
+    ///
+    /// For a scene mixing opaque and translucent rects, use two painters
+    /// and clear depth once per frame:
+    ///
+    /// 1. Draw the opaque rects with `depth_write` and `depth_test`, in any
+    ///    order.
+    /// 2. Draw the translucent ones with `depth_test` only, back to front,
+    ///    using `draw_over` to keep the depth buffer. They're hidden behind
+    ///    nearer opaque rects but don't hide each other, so blending sees
+    ///    everything behind them.
+    pub fn set_depth(&mut self, rc: &Context, depth: DepthSettings) {
+        if depth == self.depth {
+            return;
+        }
+        (self.pipeline, self.mask_pipelines) = make_pipelines(
+            rc,
+            &self.layout,
+            depth,
+            self.shader_variant,
+            self.blend.state(),
+        );
+        self.depth = depth;
+    }
+
+    pub fn depth(&self) -> DepthSettings {
+        self.depth
+    }
+
+    /// Overrides the shader variant picked from the context's backend,
+    /// remaking the pipelines if needed.
+    pub fn set_shader_variant(&mut self, rc: &Context, variant: ShaderVariant) {
+        if variant == self.shader_variant {
+            return;
+        }
+        (self.pipeline, self.mask_pipelines) =
+            make_pipelines(rc, &self.layout, self.depth, variant, self.blend.state());
+        self.shader_variant = variant;
+    }
+
+    pub fn shader_variant(&self) -> ShaderVariant {
+        self.shader_variant
+    }
+
+    /// Changes how the painter's output combines with the target,
+    /// remaking the pipelines if needed. `BlendMode::Premultiplied` by
+    /// default.
+    ///
+    /// Masking pipelines use the mode too, though `draw_mask` writes no
+    /// color. A `Custom` state is checked first, and the painter is left
+    /// as it was if it's invalid.
+    pub fn set_blend_mode(&mut self, rc: &Context, mode: BlendMode) -> Result<(), BlendError> {
+        let state = mode.validate()?;
+        if mode == self.blend {
+            return Ok(());
+        }
+        (self.pipeline, self.mask_pipelines) =
+            make_pipelines(rc, &self.layout, self.depth, self.shader_variant, state);
+        self.blend = mode;
+        Ok(())
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend
+    }
+
+    /// Draws `source`, faded by `feedback_decay`, under the rects at the
+    /// start of every color draw, for trails and motion blur. `None` stops.
+    ///
+    /// `source` should hold the previous frame. It has to be the same size
+    /// as the draw's target, and a texture can't be sampled and drawn to in
+    /// the same pass, so this takes two textures used in turn: draw into
+    /// one while the other is the source, then swap and call this again
+    /// before the next frame. Each needs `TEXTURE_BINDING` as well as
+    /// `RENDER_ATTACHMENT` usage. The surface can't be a source; copy or
+    /// post-process the latest one onto it instead.
+    ///
+    /// The faded copy replaces whatever the pass was loaded or cleared
+    /// with. Masking draws (`draw_mask`, `draw_masked`) don't include it.
+    pub fn set_feedback_source(&mut self, rc: &Context, source: Option<&TextureView>) {
+        if source.is_none() && self.feedback.is_none() {
+            return;
+        }
+        let decay = self.feedback_decay;
+        self.feedback
+            .get_or_insert_with(|| feedback::Feedback::new(rc, decay))
+            .set_source(rc, source);
+    }
+
+    pub fn has_feedback_source(&self) -> bool {
+        self.feedback
+            .as_ref()
+            .is_some_and(feedback::Feedback::has_source)
+    }
+
+    /// What the previous frame is multiplied by under this one. 0.9 by
+    /// default; smaller values make shorter trails.
+    ///
+    /// Colors are premultiplied, so alpha fades along with color.
+    pub fn set_feedback_decay(&mut self, rc: &Context, decay: f32) {
+        self.feedback_decay = decay;
+        if let Some(feedback) = &self.feedback {
+            feedback.set_decay(rc, decay);
+        }
+    }
+
+    pub fn feedback_decay(&self) -> f32 {
+        self.feedback_decay
+    }
+
+    /// Adds `usages` to the vertex buffer's, e.g. `STORAGE` so a compute
+    /// pass elsewhere can read the geometry for GPU-side culling. The
+    /// buffer always has `VERTEX | COPY_DST | COPY_SRC`, so those needn't
+    /// be given.
+    ///
+    /// The buffer is remade with the new usages, keeping its contents, and
+    /// every later reallocation keeps them too.
+    pub fn with_vertex_usages(mut self, rc: &Context, usages: BufferUsages) -> Self {
+        let usages = usages | VERTEX_USAGES;
+        if usages == self.vertex_usages {
+            return self;
+        }
+        let vertices = rc.device.create_buffer(&BufferDescriptor {
+            label: Some("Painter vertex buffer"),
+            size: self.vertices.size(),
+            usage: usages,
+            mapped_at_creation: false,
+        });
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        commands.copy_buffer_to_buffer(&self.vertices, 0, &vertices, 0, vertices.size());
+        rc.commands.submit(std::iter::once(commands.finish()));
+        self.vertices = vertices;
+        self.vertex_usages = usages;
+        self
+    }
+
+    /// The buffer holding the painter's `Vertex`es, for reading them from
+    /// other passes. Only the first `vertex_count()` are current.
+    ///
+    /// Uploads that outgrow it replace it with a larger one, so fetch it
+    /// again after changing the geometry.
+    pub fn vertex_buffer(&self) -> &Buffer {
+        &self.vertices
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_count
+    }
+
+    /// Copies the current `Vertex`es back from the GPU, to check what the
+    /// painter actually draws, e.g. the covering triangles `set_rects` or
+    /// `RectScene::upload` made.
+    ///
+    /// For debugging only: this stalls until the GPU has finished all
+    /// submitted work, then copies the whole vertex buffer through a
+    /// staging buffer, so calling it every frame costs a frame's worth of
+    /// pipelining.
+    #[cfg(feature = "debug")]
+    pub fn debug_read_vertices(&self, rc: &Context) -> Vec<Vertex> {
+        let size = self.vertex_count * std::mem::size_of::<Vertex>();
+        let bytes = crate::util::read_buffer(rc, &self.vertices, size);
+        (0..self.vertex_count)
+            .map(|i| {
+                let start = i * std::mem::size_of::<Vertex>();
+                // SAFETY: `Vertex` is `repr(C)` floats, valid for any bits,
+                // and the read may be unaligned.
+                unsafe { std::ptr::read_unaligned(bytes[start..].as_ptr() as *const Vertex) }
+            })
+            .collect()
+    }
+
+    /// Copies the current indexes back from the GPU, widened to `u32` if
+    /// they're stored as `u16`s. Costs the same stall as
+    /// `debug_read_vertices`.
+    #[cfg(feature = "debug")]
+    pub fn debug_read_indexes(&self, rc: &Context) -> Vec<u32> {
+        let width = match self.index_format {
+            IndexFormat::Uint16 => 2,
+            IndexFormat::Uint32 => 4,
+        };
+        let bytes = crate::util::read_buffer(rc, &self.indexes, self.index_count * width);
+        bytes[..self.index_count * width]
+            .chunks_exact(width)
+            .map(|b| match *b {
+                [a, b] => u16::from_ne_bytes([a, b]) as u32,
+                [a, b, c, d] => u32::from_ne_bytes([a, b, c, d]),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    /// The layout of bind group 0. See `SHADER_SOURCE` for its bindings.
+    pub fn bind_group_layout(&self) -> &BindGroupLayout {
+        &self.layout
+    }
+
+    /// Selects the geometry `set_rects` generates for each rect.
+    ///
+    /// Takes effect on the next call to `set_rects`.
+    pub fn set_geometry_mode(&mut self, mode: GeometryMode) {
+        self.geometry_mode = mode;
+    }
+
+    pub fn geometry_mode(&self) -> GeometryMode {
+        self.geometry_mode
+    }
+
+    /// Grows the geometry `set_rects` and `RectScene::upload` make for each
+    /// rect by `margin` on every side, in the rects' units, to leave room
+    /// for what `StrokeAlign::Center` and `Outside` draw past the rect. 0
+    /// by default.
+    ///
+    /// Use half the line width for `Center` and the whole of it for
+    /// `Outside`. For `LineWidthSpace::Screen` widths, that's the width in
+    /// pixels over the pixels per rect unit at the painter's zoom. A margin
+    /// bigger than needed only costs fragments that get discarded.
+    ///
+    /// Takes effect on the next call to `set_rects`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `margin` is negative or not finite.
+    pub fn set_geometry_margin(&mut self, margin: f32) {
+        assert!(
+            margin.is_finite() && margin >= 0.0,
+            "A geometry margin of {margin} isn't a finite, non-negative size"
+        );
+        self.geometry_margin = margin;
+    }
+
+    pub fn geometry_margin(&self) -> f32 {
+        self.geometry_margin
+    }
+
+    /// Makes `set_rects` emit clockwise triangles instead of the default
+    /// counter-clockwise ones.
+    ///
+    /// The painter culls clockwise triangles as back faces after its
+    /// transform and the context's orientation. A transform that mirrors,
+    /// e.g. `Transform2D` with a negative scale on one axis, flips the
+    /// rects' winding, so they'd all be culled; set this to draw them. So
+    /// does `Context::set_logical_coordinates`, whose y points down.
+    /// Geometry from `set_geometry` and `set_triangles` is drawn as given.
+    ///
+    /// Takes effect on the next call to `set_rects`.
+    pub fn set_reverse_winding(&mut self, reverse: bool) {
+        self.reverse_winding = reverse;
+    }
+
+    pub fn reverse_winding(&self) -> bool {
+        self.reverse_winding
+    }
+
+    /// Selects how `set_geometry` copies geometry to the GPU.
+    pub fn set_upload_strategy(&mut self, strategy: UploadStrategy) {
+        if strategy != self.uploader.strategy() {
+            self.uploader = Uploader::new(strategy);
+        }
+    }
+
+    pub fn upload_strategy(&self) -> UploadStrategy {
+        self.uploader.strategy()
+    }
+
+    /// The most rects `set_rects` accepts in the current geometry mode.
+    ///
+    /// Limited by the device's `max_buffer_size`.
+    pub fn max_rects(&self) -> usize {
+        let max = self.max_buffer_size as usize;
+        let mode = self.geometry_mode;
+        let vertex_bytes = mode.vertices_per_rect() * std::mem::size_of::<Vertex>();
+        let index_bytes = mode.indexes_per_rect() * std::mem::size_of::<u32>();
+        (max / vertex_bytes).min(max / index_bytes)
+    }
+
+    /// Bytes held in the painter's vertex, index, and uniform buffers.
+    ///
+    /// An estimate for diagnostics, e.g. to decide which cached painters to
+    /// drop. Buffers only grow, so this reflects the largest geometry set so
+    /// far rather than the current one.
+    pub fn gpu_memory_bytes(&self) -> usize {
+        [
+            &self.vertices,
+            &self.indexes,
+            &self.uniforms,
+            &self.transform,
+            &self.tint,
+        ]
+        .into_iter()
+        .chain(self.styles.as_ref().map(styles::Styles::buffer))
+        .map(|b| b.size() as usize)
+        .sum::<usize>()
+            + self.clip.as_ref().map_or(0, clip::Clip::buffer_sizes)
+    }
+
+    /// Encodes `rects` according to the geometry mode and uploads the result.
+    ///
+    /// Degenerate rects (see `Rect::is_degenerate`) are skipped, and the
+    /// number skipped is returned. Fails if there are more than
+    /// `max_rects()` rects.
+    pub fn set_rects(&mut self, rc: &Context, rects: &[Rect]) -> Result<usize, GeometryError> {
+        let encoded = geometry::encode(
+            self.geometry_mode,
+            self.reverse_winding,
+            self.geometry_margin,
+            rects,
+        );
+        if encoded.skipped > 0 {
+            debug!("Skipped {} degenerate rects", encoded.skipped);
+        }
+        self.set_geometry(rc, &encoded.vertices, &encoded.indexes)?;
+        Ok(encoded.skipped)
+    }
+
+    /// Uploads a triangle soup, bypassing rects, e.g. to debug geometry.
+    ///
+    /// Each triangle's color is folded into its vertices, so there's no
+    /// sharing between triangles. Fails like `set_geometry`.
+    pub fn set_triangles(
+        &mut self,
+        rc: &Context,
+        triangles: &[Triangle],
+    ) -> Result<(), GeometryError> {
+        let vertices: Vec<Vertex> = triangles
+            .iter()
+            .flat_map(|triangle| {
+                triangle.verts.map(|mut vertex| {
+                    for (c, t) in vertex.color.iter_mut().zip(triangle.color) {
+                        *c *= t;
+                    }
+                    vertex
+                })
+            })
+            .collect();
+        let indexes: Vec<u32> = (0..vertices.len() as u32).collect();
+        self.set_geometry(rc, &vertices, &indexes)
+    }
+
+    /// Uploads a line `width` wide through `points`, with corners joined
+    /// per `join`, filled with the painter's fill color times `color`. See
+    /// `stroke_polyline`. Fails like `set_geometry`.
+    pub fn set_polyline(
+        &mut self,
+        rc: &Context,
+        points: &[[f32; 2]],
+        width: f32,
+        join: JoinStyle,
+        color: [f32; 4],
+    ) -> Result<(), GeometryError> {
+        let mut triangles = stroke_polyline(points, width, join, color);
+        if self.reverse_winding {
+            for triangle in &mut triangles {
+                triangle.verts.swap(1, 2);
+            }
+        }
+        self.set_triangles(rc, &triangles)
+    }
+
+    /// Uploads triangles for drawing.
+    ///
+    /// Fails if either buffer would be larger than the device's
+    /// `max_buffer_size`. The painter draws nothing until the next
+    /// successful call in that case.
+    pub fn set_geometry(
+        &mut self,
+        rc: &Context,
+        vertices: &[Vertex],
+        indexes: &[u32],
+    ) -> Result<(), GeometryError> {
+        self.upload_geometry(rc, vertices, indexes, IndexFormat::Uint32)
+    }
+
+    /// Like `set_geometry`, but with 16-bit indexes, which take half the
+    /// memory and bandwidth. Good for meshes of up to 65536 vertices, e.g.
+    /// up to 16384 rects in [`GeometryMode::Quad`].
+    ///
+    /// Also fails with `GeometryError::TooManyVertices` if there are more
+    /// vertices than that.
+    pub fn set_geometry_u16(
+        &mut self,
+        rc: &Context,
+        vertices: &[Vertex],
+        indexes: &[u16],
+    ) -> Result<(), GeometryError> {
+        let max = u16::MAX as usize + 1;
+        if vertices.len() > max {
+            return Err(GeometryError::TooManyVertices {
+                count: vertices.len(),
+                max,
+            });
         }
+        self.upload_geometry(rc, vertices, indexes, IndexFormat::Uint16)
     }
 
-    pub fn set_geometry(&mut self, rc: &Context, vertices: &[Vertex], indexes: &[u32]) {
+    fn upload_geometry<I: Copy>(
+        &mut self,
+        rc: &Context,
+        vertices: &[Vertex],
+        indexes: &[I],
+        index_format: IndexFormat,
+    ) -> Result<(), GeometryError> {
+        // Buffer writes must be a multiple of 4 bytes, which an odd number
+        // of u16s isn't. The padding is never drawn.
+        let mut index_bytes = unsafe { as_u8_slice(indexes) };
+        let padded;
+        if index_bytes.len() % COPY_BUFFER_ALIGNMENT as usize != 0 {
+            padded = [index_bytes, &[0; 2]].concat();
+            index_bytes = &padded;
+        }
+
+        self.vertex_count = 0;
+        self.index_count = 0;
+        self.stream = None;
+        self.scene_tag = None;
+        grow(
+            rc,
+            &mut self.vertices,
+            std::mem::size_of_val(vertices),
+            "Painter vertex buffer",
+            self.vertex_usages,
+        )?;
+        grow(
+            rc,
+            &mut self.indexes,
+            index_bytes.len(),
+            "Painter index buffer",
+            BufferUsages::INDEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        )?;
         self.vertex_count = vertices.len();
         self.index_count = indexes.len();
-        rc.commands
-            .write_buffer(&self.vertices, 0, unsafe { as_u8_slice(vertices) });
-        debug!("Writing index buffer. {:?}", unsafe {
-            as_u8_slice(indexes)
+        self.index_format = index_format;
+        debug!("Writing index buffer. {:?}", index_bytes);
+        self.uploader.write(
+            rc,
+            &[
+                (&self.vertices, 0, unsafe { as_u8_slice(vertices) }),
+                (&self.indexes, 0, index_bytes),
+            ],
+        );
+        Ok(())
+    }
+
+    /// Reallocates the vertex and index buffers to fit the current geometry,
+    /// giving back memory held since some earlier, larger upload.
+    ///
+    /// Buffers otherwise only grow, so painters are cheap to refill every
+    /// frame. This always copies the geometry into new buffers, and the
+    /// next larger upload allocates again, so call it once after a one-off
+    /// large scene rather than routinely. A stream in progress keeps the
+    /// room it declared.
+    pub fn shrink_to_fit(&mut self, rc: &Context) {
+        let (vertices, indexes) = match &self.stream {
+            Some(stream) => (stream.declared_vertices, stream.declared_indexes),
+            None => (self.vertex_count, self.index_count),
+        };
+        let index_bytes = indexes * self.index_size();
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        shrink(
+            rc,
+            &mut commands,
+            &mut self.vertices,
+            vertices * std::mem::size_of::<Vertex>(),
+            "Painter vertex buffer",
+        );
+        shrink(
+            rc,
+            &mut commands,
+            &mut self.indexes,
+            index_bytes,
+            "Painter index buffer",
+        );
+        rc.commands.submit(std::iter::once(commands.finish()));
+    }
+
+    fn index_size(&self) -> usize {
+        match self.index_format {
+            IndexFormat::Uint16 => std::mem::size_of::<u16>(),
+            IndexFormat::Uint32 => std::mem::size_of::<u32>(),
+        }
+    }
+
+    /// Starts uploading geometry in chunks, so it never has to be in memory
+    /// all at once. Sizes the buffers for `total_vertices` and
+    /// `total_indexes`; follow with `push_chunk` calls that add up to
+    /// exactly those totals, then `end_streaming`.
+    ///
+    /// The painter draws nothing until the stream ends. Starting a new stream
+    /// or calling `set_geometry` abandons one in progress.
+    pub fn begin_streaming(
+        &mut self,
+        rc: &Context,
+        total_vertices: usize,
+        total_indexes: usize,
+    ) -> Result<(), GeometryError> {
+        self.vertex_count = 0;
+        self.index_count = 0;
+        self.stream = None;
+        self.scene_tag = None;
+        grow(
+            rc,
+            &mut self.vertices,
+            total_vertices * std::mem::size_of::<Vertex>(),
+            "Painter vertex buffer",
+            self.vertex_usages,
+        )?;
+        grow(
+            rc,
+            &mut self.indexes,
+            total_indexes * std::mem::size_of::<u32>(),
+            "Painter index buffer",
+            BufferUsages::INDEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        )?;
+        self.index_format = IndexFormat::Uint32;
+        self.stream = Some(Stream {
+            declared_vertices: total_vertices,
+            declared_indexes: total_indexes,
+            vertices: 0,
+            indexes: 0,
         });
-        rc.commands
-            .write_buffer(&self.indexes, 0, unsafe { as_u8_slice(indexes) });
-        // self.rc.commands.submit(None);
+        Ok(())
+    }
+
+    /// Uploads the next part of the stream after everything pushed so far.
+    ///
+    /// `indexes` refer to this chunk's `vertices`, starting from 0.
+    /// A chunk that would go past the declared totals is rejected with
+    /// `GeometryError::StreamOverflow` and the stream carries on without it.
+    pub fn push_chunk(
+        &mut self,
+        rc: &Context,
+        vertices: &[Vertex],
+        indexes: &[u32],
+    ) -> Result<(), GeometryError> {
+        let stream = self.stream.as_mut().ok_or(GeometryError::NotStreaming)?;
+        let (vertex_offset, index_offset) = (stream.vertices, stream.indexes);
+        let (vertex_end, index_end) =
+            (vertex_offset + vertices.len(), index_offset + indexes.len());
+        if vertex_end > stream.declared_vertices || index_end > stream.declared_indexes {
+            return Err(GeometryError::StreamOverflow {
+                declared_vertices: stream.declared_vertices,
+                declared_indexes: stream.declared_indexes,
+                vertices: vertex_end,
+                indexes: index_end,
+            });
+        }
+        stream.vertices = vertex_end;
+        stream.indexes = index_end;
+
+        let base = vertex_offset as u32;
+        let indexes: Vec<u32> = indexes.iter().map(|i| base + i).collect();
+        self.uploader.write(
+            rc,
+            &[
+                (
+                    &self.vertices,
+                    (vertex_offset * std::mem::size_of::<Vertex>()) as u64,
+                    unsafe { as_u8_slice(vertices) },
+                ),
+                (
+                    &self.indexes,
+                    (index_offset * std::mem::size_of::<u32>()) as u64,
+                    unsafe { as_u8_slice(&indexes) },
+                ),
+            ],
+        );
+        Ok(())
+    }
+
+    /// Finishes the stream, after which the streamed geometry is drawn.
+    ///
+    /// Fails with `GeometryError::StreamIncomplete` if the chunks didn't add
+    /// up to the declared totals. The stream is over either way; in that
+    /// case the painter draws nothing until new geometry is set.
+    pub fn end_streaming(&mut self) -> Result<(), GeometryError> {
+        let stream = self.stream.take().ok_or(GeometryError::NotStreaming)?;
+        if stream.vertices != stream.declared_vertices || stream.indexes != stream.declared_indexes
+        {
+            return Err(GeometryError::StreamIncomplete {
+                declared_vertices: stream.declared_vertices,
+                declared_indexes: stream.declared_indexes,
+                vertices: stream.vertices,
+                indexes: stream.indexes,
+            });
+        }
+        self.vertex_count = stream.vertices;
+        self.index_count = stream.indexes;
+        Ok(())
     }
 
+    /// Only the uniform buffer is written; geometry stays as uploaded.
+    ///
+    /// To draw the same geometry several times with different settings,
+    /// e.g. a glow under a crisp outline, alternate `set_uniforms` with
+    /// `draw_over` (or `draw` for the first pass). Each draw submits right
+    /// away and sees the settings set before it. That's not true of
+    /// `record_into`: every pass recorded before the host submits sees the
+    /// last settings, so use one painter per style there.
     pub fn set_uniforms(&self, rc: &Context, settings: &PainterSettings) {
+        let uniform = SettingsUniform::from(settings);
         rc.commands
-            .write_buffer(&self.uniforms, 0, unsafe { as_raw_bytes(settings) });
+            .write_buffer(&self.uniforms, 0, unsafe { as_raw_bytes(&uniform) });
         // self.rc.commands.submit(None);
     }
 
-    pub fn draw(
+    /// Stores `settings` under `name` for `draw_style`, replacing any
+    /// earlier settings of that name.
+    ///
+    /// Styles let one painter draw parts of its geometry in different
+    /// settings without uploading between draws, and unlike `set_uniforms`
+    /// every style keeps its own settings until the GPU has drawn with it.
+    /// They share the painter's transform and tint.
+    ///
+    /// The styles live in one uniform buffer, a slot each, allocated on
+    /// first use. Slots are spaced by the device's
+    /// `min_uniform_buffer_offset_alignment` (256 bytes on most), so
+    /// `PainterSettings` being smaller doesn't matter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` would be the painter's `MAX_STYLES + 1`th style.
+    /// Styles can't be removed, but redefining a name reuses its slot.
+    pub fn define_style(&mut self, rc: &Context, name: &str, settings: &PainterSettings) {
+        self.styles
+            .get_or_insert_with(|| styles::Styles::new(rc))
+            .define(
+                rc,
+                &self.layout,
+                &self.transform,
+                &self.tint,
+                name,
+                settings,
+            );
+    }
+
+    /// Draws the triangles for `indexes` over what's in `view`, in the
+    /// settings `define_style` stored under `name`. The stencil buffer is
+    /// kept, and so is any mask in it.
+    ///
+    /// `indexes` counts into the uploaded index buffer and is clamped to
+    /// it: with `set_rects`, rect `i` starts at index
+    /// `i * geometry_mode().indexes_per_rect()`, not counting skipped
+    /// degenerate rects. Nothing is cleared, so
+    /// clear the target beforehand, and the feedback source isn't drawn.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no style is named `name`.
+    pub fn draw_style(
         &self,
         rc: &Context,
         view: &TextureView,
-        clear_color: Color,
+        name: &str,
+        indexes: Range<u32>,
     ) -> Result<(), SurfaceError> {
+        let bind_group = self
+            .styles
+            .as_ref()
+            .and_then(|styles| styles.bind_group(name))
+            .unwrap_or_else(|| panic!("No style is named {name:?}"));
+        self.view.assert_same_device(rc);
         let mut commands = rc
             .device
             .create_command_encoder(&CommandEncoderDescriptor::default());
         {
-            let mut pass = commands.begin_render_pass(&RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view,
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Clear(clear_color),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-            pass.set_pipeline(&self.pipeline);
-            pass.set_bind_group(0, &self.bind_group, &[]);
-            debug!(
-                "vertex count {} size {} {:?}",
-                self.vertex_count,
-                self.vertices.size(),
-                self.vertices
-            );
-            pass.set_vertex_buffer(
-                0,
-                self.vertices
-                    .slice(..(std::mem::size_of::<Vertex>() * self.vertex_count) as u64),
+            let pass = keep_pass(view);
+            let mut pass = pass.begin(rc, &mut commands);
+            let pipeline = self.begin_clip(&mut pass, bind_group);
+            self.record(&mut pass, pipeline, bind_group, indexes);
+        }
+        rc.commands.submit(std::iter::once(commands.finish()));
+        Ok(())
+    }
+
+    /// Clears `view` to `clear_color` (or the context's `clear_color()` for
+    /// `None`) and draws each batch in its own settings, in order, in one
+    /// render pass. The stencil buffer is cleared, as with `draw`, and the
+    /// feedback source is drawn first.
+    ///
+    /// The batches' geometry is concatenated and becomes the painter's, so
+    /// a later `draw` draws all of it in the painter's own settings. That
+    /// takes a copy of every batch's vertices and indexes on the CPU each
+    /// call, and the painter's buffers grow to fit the whole list, as with
+    /// `set_geometry`. Settings take one slot per batch in a uniform buffer
+    /// (usually 256 bytes each, the device's uniform offset alignment),
+    /// which likewise grows to the longest list drawn and is kept. Use
+    /// `shrink_to_fit` after a one-off large frame to give back the
+    /// geometry buffers; the settings slots stay.
+    ///
+    /// Fails like `set_geometry` if the geometry or the settings don't fit
+    /// in a buffer, drawing nothing.
+    pub fn draw_batches(
+        &mut self,
+        rc: &Context,
+        view: &TextureView,
+        clear_color: impl Into<Option<Color>>,
+        batches: &[Batch],
+    ) -> Result<DrawStats, GeometryError> {
+        self.view.assert_same_device(rc);
+        let mut vertices = Vec::with_capacity(batches.iter().map(|b| b.vertices.len()).sum());
+        let mut indexes = Vec::with_capacity(batches.iter().map(|b| b.indexes.len()).sum());
+        let mut ranges = Vec::with_capacity(batches.len());
+        for batch in batches {
+            let base = vertices.len() as u32;
+            let start = indexes.len() as u32;
+            vertices.extend_from_slice(batch.vertices);
+            indexes.extend(batch.indexes.iter().map(|i| base + i));
+            ranges.push(start..indexes.len() as u32);
+        }
+        self.set_geometry(rc, &vertices, &indexes)?;
+        let settings = self
+            .batches
+            .get_or_insert_with(|| batches::BatchSettings::new(rc));
+        settings.write(rc, &self.layout, &self.transform, &self.tint, batches)?;
+
+        let clear_color = clear_color.into().unwrap_or(rc.clear_color());
+        let mut stats = DrawStats {
+            vertices: vertices.len(),
+            indexes: indexes.len(),
+            ..Default::default()
+        };
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        {
+            let pass = RenderPassBuilder::new(view).load(LoadOp::Clear(clear_color));
+            let mut pass = pass.begin(rc, &mut commands);
+            pass.set_stencil_reference(MASK_REFERENCE);
+            if let Some(feedback) = &self.feedback {
+                feedback.record(&mut pass);
+            }
+            let pipeline = self.begin_clip(&mut pass, &self.bind_group);
+            let bind_groups = self.batches.as_ref().unwrap().bind_groups();
+            for (range, bind_group) in ranges.into_iter().zip(bind_groups) {
+                if !range.is_empty() {
+                    self.record(&mut pass, pipeline, bind_group, range);
+                    stats.draw_calls += 1;
+                }
+            }
+        }
+        rc.commands.submit(std::iter::once(commands.finish()));
+        Ok(stats)
+    }
+
+    /// Transforms the painter's geometry before the context's orientation
+    /// is applied. The identity by default.
+    pub fn set_transform(&self, rc: &Context, transform: &Transform2D) {
+        transform::write_buffer(rc, &self.transform, transform);
+    }
+
+    /// Multiplies everything the painter draws, e.g. `[0.5, 0.5, 0.5, 1.0]`
+    /// to dim a scene behind a modal. White, the identity, by default.
+    ///
+    /// Applied last, after per-rect colors, alpha and the edge, so it
+    /// composes with all of them. Output is premultiplied: scaling rgb
+    /// darkens, and scaling all four channels fades.
+    pub fn set_global_tint(&self, rc: &Context, tint: [f32; 4]) {
+        rc.commands
+            .write_buffer(&self.tint, 0, unsafe { as_raw_bytes(&tint) });
+    }
+
+    /// Clears `view` to `clear_color` and draws. `None` clears to the
+    /// context's `clear_color()` instead.
+    ///
+    /// Also clears the stencil buffer, when the context has one, which
+    /// removes any mask.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rc` isn't on the device the painter was made with, e.g.
+    /// another context, or this one after `switch_adapter`. So do the other
+    /// draws and `record_into`.
+    pub fn draw(
+        &self,
+        rc: &Context,
+        view: &TextureView,
+        clear_color: impl Into<Option<Color>>,
+    ) -> Result<(), SurfaceError> {
+        let clear_color = clear_color.into().unwrap_or(rc.clear_color());
+        self.draw_with(
+            rc,
+            view,
+            LoadOp::Clear(clear_color),
+            &DrawOptions::default(),
+        );
+        Ok(())
+    }
+
+    /// Draws with explicit control over how the attachments are loaded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `options.depth_clear` is outside `[0, 1]`.
+    pub fn draw_with(
+        &self,
+        rc: &Context,
+        view: &TextureView,
+        load: LoadOp<Color>,
+        options: &DrawOptions,
+    ) {
+        let pass = RenderPassBuilder::new(view)
+            .load(load)
+            .depth_stencil_options(*options);
+        self.submit(rc, &pass, StencilUse::Ignore);
+    }
+
+    /// Draws on top of what's already in `view`, keeping the stencil buffer
+    /// and any mask in it.
+    pub fn draw_over(&self, rc: &Context, view: &TextureView) -> Result<(), SurfaceError> {
+        self.draw_with(
+            rc,
+            view,
+            LoadOp::Load,
+            &DrawOptions {
+                clear_depth_stencil: false,
+                ..Default::default()
+            },
+        );
+        Ok(())
+    }
+
+    /// Writes the painter's shapes into the stencil buffer as a mask.
+    /// Color isn't touched.
+    ///
+    /// Coverage is binary: a pixel is in the mask if any part of the shape
+    /// (including the edge band) lands on it, so masked content won't be
+    /// antialiased along the mask's boundary. Masks accumulate until the
+    /// next `draw`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context wasn't built `with_stencil(true)`.
+    pub fn draw_mask(&self, rc: &Context, view: &TextureView) -> Result<(), SurfaceError> {
+        self.submit(rc, &keep_pass(view), StencilUse::Write);
+        Ok(())
+    }
+
+    /// Draws over `view`, but only inside the mask (see `draw_mask`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context wasn't built `with_stencil(true)`.
+    pub fn draw_masked(&self, rc: &Context, view: &TextureView) -> Result<(), SurfaceError> {
+        self.submit(rc, &keep_pass(view), StencilUse::Test);
+        Ok(())
+    }
+
+    /// Clips everything the painter draws from now on to `shapes`, until
+    /// `clear_clip_path`, e.g. to keep content inside a rounded panel.
+    ///
+    /// Each draw writes the shapes into the stencil buffer as `draw_mask`
+    /// does, in the painter's current settings and transform, then draws
+    /// its geometry where they are. So the clip follows `set_transform`
+    /// and rounds with the corner radius, but like a mask isn't
+    /// antialiased along its boundary. `draw`, `draw_over`, `draw_style`,
+    /// `draw_batches`, `record_into` and `paint` are all clipped; the
+    /// feedback source isn't, nor are `draw_mask` and `draw_masked`.
+    ///
+    /// The clip shares the stencil value masks use, so clips don't nest:
+    /// draws that keep the stencil buffer, like `draw_over` after another
+    /// painter's `draw_mask` or clipped draw, show through the union of the
+    /// clip and what's already there rather than the intersection. Clip
+    /// each layer's content with one painter, or clear the stencil buffer
+    /// between layers. The shapes' triangles follow the geometry mode and
+    /// `set_reverse_winding` when the clip is set; set it again after
+    /// changing either.
+    ///
+    /// Fails like `set_geometry` if the shapes don't fit in a buffer,
+    /// keeping the previous clip.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context wasn't built `with_stencil(true)`.
+    pub fn set_clip_path(
+        &mut self,
+        rc: &Context,
+        shapes: &[ClipShape],
+    ) -> Result<(), GeometryError> {
+        self.mask_pipelines();
+        self.clip = Some(clip::Clip::new(
+            rc,
+            self.geometry_mode,
+            self.reverse_winding,
+            shapes,
+        )?);
+        Ok(())
+    }
+
+    /// Removes the clip set by `set_clip_path`, so draws cover the whole
+    /// target again.
+    pub fn clear_clip_path(&mut self) {
+        self.clip = None;
+    }
+
+    pub fn has_clip_path(&self) -> bool {
+        self.clip.is_some()
+    }
+
+    fn mask_pipelines(&self) -> &MaskPipelines {
+        self.mask_pipelines
+            .as_ref()
+            .expect("Masking requires a context built with_stencil(true)")
+    }
+
+    /// Records a render pass drawing into `target` on a host's encoder.
+    ///
+    /// Nothing is submitted; that's up to the host, so oreb's draw is ordered
+    /// with the rest of the host's commands. Geometry and uniforms must
+    /// already be set: their uploads go through the queue and land before
+    /// the host's next submission.
+    ///
+    /// When the context has a stencil buffer, it's cleared along with color
+    /// if `load` is `LoadOp::Clear`, and kept otherwise.
+    pub fn record_into(
+        &self,
+        rc: &Context,
+        encoder: &mut CommandEncoder,
+        target: &TextureView,
+        load: LoadOp<Color>,
+    ) {
+        let pass = match load {
+            LoadOp::Clear(_) => RenderPassBuilder::new(target).load(load),
+            LoadOp::Load => keep_pass(target),
+        };
+        self.record_with(rc, encoder, &pass);
+    }
+
+    /// Like `record_into`, but with full control over the pass: store ops,
+    /// labels, a depth/stencil attachment other than the context's and so
+    /// on. See `RenderPassBuilder`.
+    ///
+    /// The color attachment must match the format the painter was made for
+    /// and be single-sampled.
+    pub fn record_with(
+        &self,
+        rc: &Context,
+        encoder: &mut CommandEncoder,
+        pass: &RenderPassBuilder,
+    ) {
+        self.record_pass(rc, encoder, pass, StencilUse::Ignore);
+    }
+
+    fn submit(&self, rc: &Context, pass: &RenderPassBuilder, stencil: StencilUse) {
+        self.view.assert_same_device(rc);
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        self.record_pass(rc, &mut commands, pass, stencil);
+        rc.commands.submit(std::iter::once(commands.finish()));
+    }
+
+    fn record_pass(
+        &self,
+        rc: &Context,
+        commands: &mut CommandEncoder,
+        pass: &RenderPassBuilder,
+        stencil: StencilUse,
+    ) {
+        self.view.assert_same_device(rc);
+        let mut pass = pass.begin(rc, commands);
+        pass.set_stencil_reference(MASK_REFERENCE);
+        match stencil {
+            StencilUse::Ignore => self.paint(&mut pass),
+            StencilUse::Write => self.record_all(&mut pass, &self.mask_pipelines().mask),
+            StencilUse::Test => self.record_all(&mut pass, &self.mask_pipelines().masked),
+        }
+    }
+
+    /// Acquires the next frame, draws into it, and presents it.
+    ///
+    /// If the surface is lost or outdated it's reconfigured and acquisition
+    /// is retried once before giving up. Does nothing while the context is
+    /// paused (see `Context::set_active`) or minimized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context has no surface (see `Context::from_raw`).
+    pub fn present(
+        &self,
+        rc: &mut Context,
+        clear_color: impl Into<Option<Color>>,
+    ) -> Result<(), SurfaceError> {
+        let Some(frame) = rc.acquire_frame()? else {
+            return Ok(());
+        };
+        let view = frame.texture.create_view(&TextureViewDescriptor::default());
+        self.draw(rc, &view, clear_color)?;
+        rc.present_frame(frame);
+        Ok(())
+    }
+
+    /// Records the draw into a render pass someone else began.
+    ///
+    /// The pass's color attachment must match the format the painter was
+    /// made for and be single-sampled. The pass must have the context's
+    /// depth/stencil attachment if it has one, and none otherwise.
+    pub fn paint<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        if let Some(feedback) = &self.feedback {
+            feedback.record(pass);
+        }
+        let pipeline = self.begin_clip(pass, &self.bind_group);
+        self.record_all(pass, pipeline);
+    }
+
+    /// Writes the clip path, if there is one, into the stencil buffer, and
+    /// returns the pipeline to draw with: one that keeps to it, or the
+    /// painter's own.
+    fn begin_clip<'a>(
+        &'a self,
+        pass: &mut RenderPass<'a>,
+        bind_group: &'a BindGroup,
+    ) -> &'a RenderPipeline {
+        let Some(clip) = &self.clip else {
+            return &self.pipeline;
+        };
+        let pipelines = self.mask_pipelines();
+        pass.set_stencil_reference(MASK_REFERENCE);
+        clip.record(pass, &pipelines.mask, bind_group, &self.view.bind_group);
+        &pipelines.masked
+    }
+
+    fn record_all<'a>(&'a self, pass: &mut RenderPass<'a>, pipeline: &'a RenderPipeline) {
+        self.record(pass, pipeline, &self.bind_group, 0..self.index_count as u32);
+    }
+
+    fn record<'a>(
+        &'a self,
+        pass: &mut RenderPass<'a>,
+        pipeline: &'a RenderPipeline,
+        bind_group: &'a BindGroup,
+        indexes: Range<u32>,
+    ) {
+        let indexes =
+            indexes.start.min(self.index_count as u32)..indexes.end.min(self.index_count as u32);
+        if indexes.is_empty() {
+            // Empty buffer slices aren't allowed.
+            return;
+        }
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.set_bind_group(1, &self.view.bind_group, &[]);
+        debug!(
+            "vertex count {} size {} {:?}",
+            self.vertex_count,
+            self.vertices.size(),
+            self.vertices
+        );
+        pass.set_vertex_buffer(
+            0,
+            self.vertices
+                .slice(..(std::mem::size_of::<Vertex>() * self.vertex_count) as u64),
+        );
+        debug!(
+            "index count {} size {} {:?}",
+            self.index_count,
+            self.indexes.size(),
+            self.indexes
+        );
+        pass.set_index_buffer(
+            self.indexes
+                .slice(..(self.index_size() * self.index_count) as u64),
+            self.index_format,
+        );
+        pass.draw_indexed(indexes, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{context, other_format, stored, Target};
+
+    #[test]
+    fn set_rects_skips_degenerate_rects() {
+        let Some(rc) = context(32, 32) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let mut painter = rc.make_rect_painter();
+        let rects = [
+            Rect::new([0.0, 0.0], [0.0, 0.0], 0.0),
+            Rect::new([0.0, 0.0], [1.0, 1.0], 0.0),
+            Rect::new([0.0, 0.0], [f32::NAN, 1.0], 0.0),
+        ];
+        assert_eq!(painter.set_rects(&rc, &rects).unwrap(), 2);
+        painter.set_uniforms(&rc, &Default::default());
+        painter.draw(&rc, &target.view, Color::BLACK).unwrap();
+        let pixels = target.read(&rc);
+        assert_eq!(pixels.at(16, 16), [255; 4]);
+        assert_eq!(pixels.at(2, 2), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn max_rects_is_the_limit() {
+        let Some(mut rc) = context(16, 16) else {
+            return;
+        };
+        let limit = 1 << 16;
+        rc.limits.max_buffer_size = limit;
+        for mode in [GeometryMode::Triangle, GeometryMode::Quad] {
+            let mut painter = rc.make_rect_painter();
+            painter.set_geometry_mode(mode);
+            let max = painter.max_rects();
+            assert!(max > 0);
+            let rects = vec![Rect::new([0.0, 0.0], [0.5, 0.5], 0.0); max + 1];
+            painter.set_rects(&rc, &rects[..max]).unwrap();
+            match painter.set_rects(&rc, &rects) {
+                Err(GeometryError::TooLarge { requested, max, .. }) => {
+                    assert!(requested > limit);
+                    assert_eq!(max, limit);
+                }
+                other => panic!("{} rects in {mode:?} gave {other:?}", rects.len()),
+            }
+        }
+    }
+
+    #[test]
+    fn rebuild_for_format_draws_in_the_new_format() {
+        let Some(mut rc) = context(32, 32) else {
+            return;
+        };
+        let mut painter = rc.make_rect_painter();
+        painter
+            .set_rects(&rc, &[Rect::new([0.0, 0.0], [1.0, 1.0], 0.0)])
+            .unwrap();
+        painter.set_uniforms(&rc, &Default::default());
+        let format = other_format(&rc);
+        rc.set_format(format).unwrap();
+        assert_eq!(rc.format(), format);
+        painter.rebuild_for_format(&rc);
+
+        // Targets are made in the context's format, which the old
+        // pipelines would be a validation error in.
+        let target = Target::new(&rc);
+        painter.draw(&rc, &target.view, Color::BLACK).unwrap();
+        let pixels = target.read(&rc);
+        assert_eq!(pixels.at(16, 16), [255; 4]);
+        assert_eq!(pixels.at(1, 1), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn redraws_with_each_uniform_set() {
+        let Some(rc) = context(32, 32) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let mut painter = rc.make_rect_painter();
+        painter.set_blend_mode(&rc, BlendMode::Additive).unwrap();
+        painter
+            .set_rects(&rc, &[Rect::new([0.0, 0.0], [1.0, 1.0], 0.0)])
+            .unwrap();
+        // Adding up to white only if each draw sees its own settings.
+        let colors = [
+            [1.0, 0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0, 1.0],
+        ];
+        for (i, color) in colors.into_iter().enumerate() {
+            painter.set_uniforms(
+                &rc,
+                &PainterSettings {
+                    edge: color,
+                    fill: color,
+                    ..Default::default()
+                },
             );
-            debug!(
-                "index count {} size {} {:?}",
-                self.index_count,
-                self.indexes.size(),
-                self.indexes
+            if i == 0 {
+                painter.draw(&rc, &target.view, Color::BLACK).unwrap();
+            } else {
+                painter.draw_over(&rc, &target.view).unwrap();
+            }
+        }
+        let pixels = target.read(&rc);
+        assert_eq!(pixels.at(16, 16), [255; 4]);
+        assert_eq!(pixels.at(1, 1), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn gray_tint_halves_white() {
+        let Some(rc) = context(32, 32) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let half = stored(rc.format(), 0.5);
+        let assert_halved = |pixel: [u8; 4]| {
+            for c in &pixel[..3] {
+                assert!((*c as f32 - half).abs() <= 1.0, "{pixel:?}");
+            }
+            assert_eq!(pixel[3], 255);
+        };
+        let tint = [0.5, 0.5, 0.5, 1.0];
+
+        let mut painter = rc.make_rect_painter();
+        painter
+            .set_rects(&rc, &[Rect::new([0.0, 0.0], [1.0, 1.0], 0.0)])
+            .unwrap();
+        painter.set_uniforms(&rc, &Default::default());
+        painter.set_global_tint(&rc, tint);
+        painter.draw(&rc, &target.view, Color::BLACK).unwrap();
+        assert_halved(target.read(&rc).at(16, 16));
+
+        let mut instanced = rc.make_rect_instance_painter();
+        instanced
+            .set_instances(&rc, &[RectInstance::new([0.0, 0.0], [1.0, 1.0], 0.0)])
+            .unwrap();
+        instanced.set_uniforms(&rc, &Default::default());
+        instanced.set_global_tint(&rc, tint);
+        instanced.draw(&rc, &target.view, Color::BLACK).unwrap();
+        assert_halved(target.read(&rc).at(16, 16));
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn vertices_cast_to_and_from_floats() {
+        let vertices: Vec<Vertex> = (0..5)
+            .map(|i| {
+                let f = i as f32;
+                Vertex {
+                    xyz: [f, -f, 0.5],
+                    uv: [0.25 * f, 1.0],
+                    color: [f, 0.0, 1.0, 0.5],
+                    alpha: 1.0 - 0.1 * f,
+                }
+            })
+            .collect();
+        let floats: &[f32] = bytemuck::cast_slice(&vertices);
+        assert_eq!(floats.len(), 10 * vertices.len());
+        assert_eq!(
+            &floats[10..20],
+            &[1.0, -1.0, 0.5, 0.25, 1.0, 1.0, 0.0, 1.0, 0.5, 0.9]
+        );
+        let back: &[Vertex] = bytemuck::cast_slice(floats);
+        for (a, b) in back.iter().zip(&vertices) {
+            assert_eq!(
+                (a.xyz, a.uv, a.color, a.alpha),
+                (b.xyz, b.uv, b.color, b.alpha)
             );
-            pass.set_index_buffer(
-                self.indexes
-                    .slice(..(std::mem::size_of::<u32>() * self.index_count) as u64),
-                IndexFormat::Uint32,
+        }
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn settings_cast_to_their_uniform_bytes() {
+        let settings = PainterSettings {
+            edge: [0.1, 0.2, 0.3, 0.4],
+            fill: [0.5, 0.6, 0.7, 0.8],
+            line_width_px: 3.0,
+            corner_radius_px: 4.0,
+            debug_visualize: DebugViz::None,
+            line_width_space: LineWidthSpace::World,
+            stroke_align: StrokeAlign::Outside,
+            alpha_cutoff: Some(0.5),
+        };
+        let uniform = SettingsUniform::from(&settings);
+        let words: &[u32] = bytemuck::cast_slice(bytemuck::bytes_of(&uniform));
+        let floats = |words: &[u32]| words.iter().map(|&w| f32::from_bits(w)).collect::<Vec<_>>();
+        assert_eq!(words.len(), 16);
+        assert_eq!(
+            floats(&words[..10]),
+            [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 3.0, 4.0]
+        );
+        assert_eq!(
+            &words[10..12],
+            &[DebugViz::None as u32, LineWidthSpace::World as u32]
+        );
+        assert_eq!(f32::from_bits(words[12]), 0.5);
+        assert_eq!(&words[13..], &[StrokeAlign::Outside as u32, 0, 0]);
+    }
+
+    #[test]
+    fn edges_blend_over_a_pixel_at_any_zoom() {
+        let Some(rc) = context(64, 64) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let mut painter = rc.make_rect_painter();
+        painter
+            .set_rects(&rc, &[Rect::new([0.0, 0.0], [0.35, 0.35], 0.0)])
+            .unwrap();
+        painter.set_uniforms(
+            &rc,
+            &PainterSettings {
+                edge: [1.0; 4],
+                ..Default::default()
+            },
+        );
+        // Edges land 5.6 and 16.8 px off the center, under half a pixel
+        // past the centers of the pixels they cross, which the quad covers.
+        // Coverage shows in alpha, over a transparent target.
+        for zoom in [1.0, 3.0] {
+            painter.set_transform(&rc, &Transform2D::scale(zoom, zoom));
+            painter.draw(&rc, &target.view, Color::TRANSPARENT).unwrap();
+            let pixels = target.read(&rc);
+            let partial = (0..64)
+                .map(|x| pixels.at(x, 32)[3])
+                .filter(|&c| c != 0 && c != 255)
+                .count();
+            // One or two pixels on each side.
+            assert!((2..=4).contains(&partial), "{partial} at zoom {zoom}");
+            assert_eq!(pixels.at(32, 32), [255; 4]);
+        }
+    }
+
+    #[test]
+    fn stroke_align_places_the_band() {
+        let Some(rc) = context(64, 64) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let red = [255, 0, 0, 255];
+        let settings = |stroke_align| PainterSettings {
+            edge: [1.0, 0.0, 0.0, 1.0],
+            line_width_px: 4.0,
+            stroke_align,
+            ..Default::default()
+        };
+        // The rect spans px 16 to 48; the margin is 4 px.
+        let draw = |stroke_align, margin| {
+            let mut painter = rc.make_rect_painter();
+            painter.set_geometry_margin(margin);
+            painter
+                .set_rects(&rc, &[Rect::new([0.0, 0.0], [1.0, 1.0], 0.0)])
+                .unwrap();
+            painter.set_uniforms(&rc, &settings(stroke_align));
+            painter.draw(&rc, &target.view, Color::TRANSPARENT).unwrap();
+            target.read(&rc)
+        };
+        for (stroke_align, outer) in [
+            (StrokeAlign::Inside, 16),
+            (StrokeAlign::Center, 14),
+            (StrokeAlign::Outside, 12),
+        ] {
+            let pixels = draw(stroke_align, 0.125);
+            for (x, expected) in [(outer - 3, [0; 4]), (outer + 1, red), (outer + 6, [255; 4])] {
+                assert_eq!(pixels.at(x, 32), expected, "{x} with {stroke_align:?}");
+                assert_eq!(pixels.at(63 - x, 32), expected, "{x} with {stroke_align:?}");
+            }
+        }
+        // Without room, the band outside the rect isn't drawn.
+        let pixels = draw(StrokeAlign::Outside, 0.0);
+        assert_eq!(pixels.at(13, 32), [0; 4]);
+        assert_eq!(pixels.at(17, 32), [255; 4]);
+    }
+
+    #[test]
+    fn alpha_cutoff_discards_the_fainter_half_of_a_gradient() {
+        let Some(rc) = context(32, 32) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let mut painter = rc.make_rect_painter();
+        // Fading in from transparent on the left, over the whole target.
+        let mut encoded = geometry::encode(
+            GeometryMode::Quad,
+            false,
+            0.0,
+            &[Rect::new([0.0, 0.0], [2.0, 2.0], 0.0)],
+        );
+        for vertex in &mut encoded.vertices {
+            vertex.alpha = 0.5 * (vertex.xyz[0] + 1.0);
+        }
+        painter
+            .set_geometry(&rc, &encoded.vertices, &encoded.indexes)
+            .unwrap();
+
+        for cutoff in [None, Some(0.5)] {
+            painter.set_uniforms(
+                &rc,
+                &PainterSettings {
+                    edge: [1.0; 4],
+                    alpha_cutoff: cutoff,
+                    ..Default::default()
+                },
             );
-            pass.draw_indexed(0..self.index_count as u32, 0, 0..1);
+            painter.draw(&rc, &target.view, Color::TRANSPARENT).unwrap();
+            let pixels = target.read(&rc);
+            for x in 0..32 {
+                let alpha = (x as f32 + 0.5) / 32.0;
+                let kept = cutoff.is_none_or(|c| alpha >= c);
+                let drawn = pixels.at(x, 16)[3];
+                if kept {
+                    assert!(drawn.abs_diff((255.0 * alpha).round() as u8) <= 1, "{x}");
+                } else {
+                    assert_eq!(drawn, 0, "{x} with {cutoff:?}");
+                }
+            }
         }
-        rc.commands.submit(std::iter::once(commands.finish()));
-        Ok(())
     }
 }
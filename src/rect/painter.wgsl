@@ -2,15 +2,50 @@ struct Settings {
     edge: vec4<f32>,
     fill: vec4<f32>,
     line_width_px: f32,
-    corner_radius_px: f32
+    corner_radius_px: f32,
+    // See DebugViz in mod.rs
+    debug_visualize: u32,
+    // See LineWidthSpace in mod.rs. 0 is Screen, 1 is World.
+    line_width_space: u32,
+    // Fragments with less alpha are discarded. 0 when
+    // PainterSettings::alpha_cutoff is None, which keeps everything.
+    alpha_cutoff: f32,
+    // See StrokeAlign in mod.rs. 0 is Inside, 1 is Center, 2 is Outside.
+    stroke_align: u32,
 }
 
+// Cleared for release builds when the module is loaded, so the debug
+// branch compiles away.
+const DEBUG_VIZ_ENABLED: bool = true;
+
+// Cleared for ShaderVariant::ConstantAa, which blends edges over a fixed
+// 1px band instead of measuring it with fwidth.
+const DERIVATIVE_AA: bool = true;
+
 @group(0) @binding(0)
 var<uniform> setttings: Settings;
 
+// The painter's transform. See transform.rs.
+@group(0) @binding(1)
+var<uniform> transform: mat4x4<f32>;
+
+// Multiplies the output. See set_global_tint in mod.rs.
+@group(0) @binding(2)
+var<uniform> tint: vec4<f32>;
+
+struct View {
+    // Applied to clip space positions. See view.rs.
+    transform: mat4x4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> view: View;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) alpha: f32,
 }
 
 struct VertexOutput {
@@ -18,6 +53,10 @@ struct VertexOutput {
     // pipleline stages (looks like)
     @builtin(position) position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) alpha: f32,
+    // Before the painter's transform, for world space line widths.
+    @location(3) world: vec2<f32>,
 }
 
 @vertex
@@ -26,7 +65,10 @@ fn vs(
 ) -> VertexOutput {
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
-    out.position = vec4<f32>(model.position, 1.0);
+    out.color = model.color;
+    out.alpha = model.alpha;
+    out.world = model.position.xy;
+    out.position = view.transform * transform * vec4<f32>(model.position, 1.0);
     return out;
 }
 
@@ -60,19 +102,96 @@ fn fs(in: VertexOutput) -> @location(0) vec4<f32> {
     let dy = length(vec2(duvdx.y, duvdy.y));
     let s = vec2(dx, dy);
 
-    let d = sd_round_box(in.tex_coords.xy / s, 0.5 / s, setttings.corner_radius_px);
+    // Constant tex coords, as from Triangle::flat, have no scale to measure
+    // the edge by. Treat those as deep inside so they fill flat.
+    var d = -1e20;
+    if any(s != vec2<f32>()) {
+        d = sd_round_box(in.tex_coords.xy / s, 0.5 / s, setttings.corner_radius_px);
+    }
+    // How much d changes across a pixel, so edges blend over ~1px on screen
+    // however the rect is scaled. That's 1 where d is an exact pixel
+    // distance, but the per-axis scale above only approximates that on
+    // skewed or non-uniformly scaled rects, and around corners.
+    var aa = 1.0;
+    if DERIVATIVE_AA {
+        aa = max(fwidth(d), 1e-4);
+    }
+
+    if DEBUG_VIZ_ENABLED && setttings.debug_visualize != 0u {
+        return debug_visualize(setttings.debug_visualize, d, in.tex_coords);
+    }
 
-    if d < -setttings.line_width_px {
-        let eps = d + setttings.line_width_px;
-        return mix(setttings.edge, setttings.fill, saturate(-eps));
+    let line_width = line_width_px(in.world);
+    // Past here the band is taken to end at d = 0, so move d out with it.
+    d -= stroke_offset(line_width);
+    var color: vec4<f32>;
+    if d < -line_width {
+        let eps = d + line_width;
+        color = mix(setttings.edge, setttings.fill * in.color, saturate(-eps / aa));
     } else if d < 0.0 {
-        var color = setttings.edge;
-        color.a = saturate(0.5 - d);
-        return color;
+        color = setttings.edge;
+        color.a = saturate(0.5 - d / aa);
     } else {
         discard;
-        // return vec4(in.tex_coords, 0.0, 1.0);
-        // let d = d * 0.05;
-        // return vec4(1.0 - d, 0.7 - 0.3 * d, 0.4 - 0.1 * d, 1.0 - 0.1 * d);
+    }
+    // Output is premultiplied, so fading scales every channel.
+    color *= in.alpha * tint;
+    if color.a < setttings.alpha_cutoff {
+        discard;
+    }
+    return color;
+}
+
+// The edge width in viewport pixels. `world` is the fragment's position
+// before the painter's transform.
+fn line_width_px(world: vec2<f32>) -> f32 {
+    if setttings.line_width_space == 0u {
+        return setttings.line_width_px;
+    }
+    // World units per pixel along x and y. The square root of the area
+    // covered by one pixel is exact for uniform scales.
+    let dx = dpdx(world);
+    let dy = dpdy(world);
+    let world_per_px = sqrt(abs(dx.x * dy.y - dx.y * dy.x));
+    return setttings.line_width_px / max(world_per_px, 1e-20);
+}
+
+// How far past the rect's outline the outer side of the edge band is, in
+// viewport pixels.
+fn stroke_offset(line_width: f32) -> f32 {
+    switch setttings.stroke_align {
+        case 1u: {
+            return 0.5 * line_width;
+        }
+        case 2u: {
+            return line_width;
+        }
+        default: {
+            return 0.0;
+        }
+    }
+}
+
+// d    signed distance to the edge in px
+// uv   rect tex coords. The rect spans [-0.5,0.5].
+fn debug_visualize(mode: u32, d: f32, uv: vec2<f32>) -> vec4<f32> {
+    switch mode {
+        // Distance: orange outside, blue inside, banded every ~12px,
+        // with a white line on the edge.
+        case 1u: {
+            var color = select(vec3(0.65, 0.85, 1.0), vec3(0.9, 0.6, 0.3), d > 0.0);
+            color *= 1.0 - exp(-abs(d) / 16.0);
+            color *= 0.8 + 0.2 * cos(0.5 * d);
+            color = mix(color, vec3(1.0), 1.0 - smoothstep(0.0, 1.5, abs(d)));
+            return vec4(color, 1.0);
+        }
+        // UV: shifted so the rect spans [0,1]
+        case 2u: {
+            return vec4(uv + 0.5, 0.0, 1.0);
+        }
+        // Coverage: the antialiased shape mask
+        default: {
+            return vec4(vec3(saturate(0.5 - d)), 1.0);
+        }
     }
 }
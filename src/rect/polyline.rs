@@ -0,0 +1,257 @@
+use std::f32::consts::PI;
+
+use super::Triangle;
+
+/// How `stroke_polyline` fills the outside of a corner between segments.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum JoinStyle {
+    /// Extends the segments' edges until they meet in a point. Corners
+    /// sharper than `limit` allows are beveled instead, since the point
+    /// runs off to infinity as segments fold back on themselves.
+    ///
+    /// `limit` bounds the distance from the corner to the point, in half
+    /// line widths, which is the ratio SVG's `stroke-miterlimit` bounds: 1
+    /// bevels every corner and the SVG default of 4 bevels corners sharper
+    /// than about 29°.
+    Miter { limit: f32 },
+    /// Cuts corners off straight.
+    Bevel,
+    /// Rounds corners with an arc of the line's width.
+    Round,
+}
+
+impl Default for JoinStyle {
+    fn default() -> Self {
+        JoinStyle::Miter { limit: 4.0 }
+    }
+}
+
+/// The most an arc of a round join turns per triangle.
+const ROUND_STEP: f32 = PI / 16.0;
+
+/// Triangles covering a line `width` wide through `points`, in order, with
+/// corners joined per `join` and square-cut (butt) ends. Feed them to
+/// `Painter::set_triangles`, or use `Painter::set_polyline`.
+///
+/// Points and width are in the units of the painter's geometry, usually
+/// clip space. Repeated points are skipped. Fewer than two distinct points
+/// make no triangles.
+///
+/// The triangles are flat filled (see `Triangle::flat`), so their edges
+/// aren't antialiased, and they overlap on the inside of corners, so a
+/// translucent `color` shows darker there.
+pub fn stroke_polyline(
+    points: &[[f32; 2]],
+    width: f32,
+    join: JoinStyle,
+    color: [f32; 4],
+) -> Vec<Triangle> {
+    let mut points = points.to_vec();
+    points.dedup();
+    let half = 0.5 * width;
+    let mut out = Triangles {
+        color,
+        triangles: Vec::new(),
+    };
+
+    let normals: Vec<[f32; 2]> = points
+        .windows(2)
+        .map(|p| {
+            let d = normalize(sub(p[1], p[0]));
+            [-d[1], d[0]]
+        })
+        .collect();
+
+    for (p, n) in points.windows(2).zip(&normals) {
+        let offset = scale(*n, half);
+        let (a0, a1) = (add(p[0], offset), sub(p[0], offset));
+        let (b0, b1) = (add(p[1], offset), sub(p[1], offset));
+        out.push(a0, a1, b1);
+        out.push(a0, b1, b0);
+    }
+
+    for (i, n) in normals.windows(2).enumerate() {
+        let corner = points[i + 1];
+        let (na, nb) = (n[0], n[1]);
+        // Positive for left turns, whose outside is to the right.
+        let turn = na[0] * nb[1] - na[1] * nb[0];
+        if turn.abs() < 1e-6 {
+            // Straight on, the segments already meet. Folding straight
+            // back, their square ends lie on each other and there's no
+            // bisector for a miter, so only a round join adds anything.
+            if dot(na, nb) > 0.0 || join != JoinStyle::Round {
+                continue;
+            }
+        }
+        let side = if turn > 0.0 { -half } else { half };
+        let (oa, ob) = (scale(na, side), scale(nb, side));
+        match join {
+            JoinStyle::Miter { limit } => {
+                let bisector = normalize(add(na, nb));
+                // The corner's half angle, as seen from the miter point.
+                let cos = dot(bisector, na);
+                if cos * limit >= 1.0 {
+                    let tip = add(corner, scale(bisector, side / cos));
+                    out.push(corner, add(corner, oa), tip);
+                    out.push(corner, tip, add(corner, ob));
+                } else {
+                    out.push(corner, add(corner, oa), add(corner, ob));
+                }
+            }
+            JoinStyle::Bevel => out.push(corner, add(corner, oa), add(corner, ob)),
+            JoinStyle::Round => {
+                let angle = dot(na, nb).clamp(-1.0, 1.0).acos();
+                let steps = (angle / ROUND_STEP).ceil().max(1.0) as usize;
+                let start = oa[1].atan2(oa[0]);
+                let direction = if turn > 0.0 { 1.0 } else { -1.0 };
+                let mut previous = add(corner, oa);
+                for step in 1..=steps {
+                    let a = start + direction * angle * step as f32 / steps as f32;
+                    let next = add(corner, [half * a.cos(), half * a.sin()]);
+                    out.push(corner, previous, next);
+                    previous = next;
+                }
+            }
+        }
+    }
+    out.triangles
+}
+
+struct Triangles {
+    color: [f32; 4],
+    triangles: Vec<Triangle>,
+}
+
+impl Triangles {
+    /// Adds a triangle wound counter-clockwise, so it isn't culled.
+    fn push(&mut self, a: [f32; 2], b: [f32; 2], c: [f32; 2]) {
+        let area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+        let (b, c) = if area < 0.0 { (c, b) } else { (b, c) };
+        let lift = |[x, y]: [f32; 2]| [x, y, 0.0];
+        self.triangles
+            .push(Triangle::flat([lift(a), lift(b), lift(c)], self.color));
+    }
+}
+
+fn add(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: [f32; 2], s: f32) -> [f32; 2] {
+    [a[0] * s, a[1] * s]
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn normalize(a: [f32; 2]) -> [f32; 2] {
+    scale(a, 1.0 / dot(a, a).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [f32; 4] = [1.0; 4];
+
+    fn corners(triangles: &[Triangle]) -> Vec<[f32; 2]> {
+        triangles
+            .iter()
+            .flat_map(|t| t.verts.map(|v| [v.xyz[0], v.xyz[1]]))
+            .collect()
+    }
+
+    fn has_corner(triangles: &[Triangle], at: [f32; 2]) -> bool {
+        corners(triangles)
+            .iter()
+            .any(|c| (c[0] - at[0]).abs() < 1e-5 && (c[1] - at[1]).abs() < 1e-5)
+    }
+
+    #[test]
+    fn fewer_than_two_distinct_points_make_nothing() {
+        for points in [&[][..], &[[0.5, 0.5]], &[[0.5, 0.5]; 3]] {
+            assert!(stroke_polyline(points, 0.1, JoinStyle::default(), WHITE).is_empty());
+        }
+    }
+
+    #[test]
+    fn straight_line_has_no_join() {
+        let points = [[0.0, 0.0], [0.5, 0.0], [0.5, 0.0], [1.0, 0.0]];
+        for join in [JoinStyle::default(), JoinStyle::Bevel, JoinStyle::Round] {
+            // Two triangles per segment.
+            assert_eq!(stroke_polyline(&points, 0.2, join, WHITE).len(), 4);
+        }
+    }
+
+    #[test]
+    fn right_angle_miters() {
+        // A left turn, so the outside of the corner is to the bottom right.
+        let points = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]];
+        let triangles = stroke_polyline(&points, 0.2, JoinStyle::default(), WHITE);
+        assert_eq!(triangles.len(), 6);
+        assert!(has_corner(&triangles, [1.1, -0.1]));
+    }
+
+    #[test]
+    fn miter_past_the_limit_bevels() {
+        // A right angle's point is √2 half widths from the corner.
+        let points = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0]];
+        let triangles = stroke_polyline(&points, 0.2, JoinStyle::Miter { limit: 1.4 }, WHITE);
+        assert_eq!(triangles.len(), 5);
+        assert!(!has_corner(&triangles, [1.1, -0.1]));
+        assert!(has_corner(&triangles, [1.0, -0.1]));
+        assert!(has_corner(&triangles, [1.1, 0.0]));
+        let triangles = stroke_polyline(&points, 0.2, JoinStyle::Miter { limit: 1.5 }, WHITE);
+        assert_eq!(triangles.len(), 6);
+    }
+
+    #[test]
+    fn folding_back_adds_only_round_joins() {
+        let points = [[0.0, 0.0], [1.0, 0.0], [0.0, 0.0]];
+        for join in [JoinStyle::default(), JoinStyle::Bevel] {
+            let triangles = stroke_polyline(&points, 0.2, join, WHITE);
+            assert_eq!(triangles.len(), 4);
+        }
+        let round = stroke_polyline(&points, 0.2, JoinStyle::Round, WHITE);
+        // A half turn around the corner.
+        assert_eq!(round.len(), 4 + 16);
+        assert!(has_corner(&round, [1.1, 0.0]));
+        for corner in corners(&round) {
+            assert!(corner.iter().all(|e| e.is_finite()));
+        }
+    }
+
+    fn signed_area(t: &Triangle) -> f32 {
+        let [a, b, c] = t.verts.map(|v| v.xyz);
+        0.5 * ((b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]))
+    }
+
+    #[test]
+    fn push_winds_counter_clockwise() {
+        let mut out = Triangles {
+            color: WHITE,
+            triangles: Vec::new(),
+        };
+        let (a, b, c) = ([0.0, 0.0], [1.0, 0.0], [0.0, 1.0]);
+        out.push(a, b, c);
+        out.push(a, c, b);
+        for t in &out.triangles {
+            assert_eq!(signed_area(t), 0.5);
+        }
+    }
+
+    #[test]
+    fn strokes_wind_counter_clockwise() {
+        let points = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.5], [0.5, -1.0]];
+        for join in [JoinStyle::default(), JoinStyle::Bevel, JoinStyle::Round] {
+            for t in stroke_polyline(&points, 0.2, join, WHITE) {
+                assert!(signed_area(&t) >= 0.0, "{join:?}");
+            }
+        }
+    }
+}
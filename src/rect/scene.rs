@@ -0,0 +1,610 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    ops::Range,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use super::{geometry, ClipShape, GeometryMode, Painter, Rect, Vertex};
+use crate::{BlendError, BlendMode, Context, GeometryError, SceneFileError};
+
+/// Starts every scene file. See `RectScene::write_binary`.
+const MAGIC: [u8; 4] = *b"ORSC";
+
+/// The scene format version written, and the newest one read.
+const FORMAT_VERSION: u8 = 1;
+
+/// A rect, the color it's filled with and where it's drawn among the rest.
+#[derive(Debug, Copy, Clone)]
+pub struct SceneRect {
+    pub rect: Rect,
+    /// Multiplies the painter's fill color.
+    pub color: [f32; 4],
+    /// Rects with a higher `z_index` are drawn later, on top. Rects with
+    /// the same one are drawn in the order they were added. See
+    /// `RectScene::upload`.
+    pub z_index: i32,
+    /// Higher layers are drawn later, on top, whatever the `z_index`, which
+    /// only orders rects within a layer. See `LayerRenderer`.
+    pub layer: u32,
+}
+
+/// How a layer of a `RectScene` is drawn. See `LayerRenderer`.
+#[derive(Debug, Clone, Default)]
+pub struct LayerSettings {
+    pub blend: BlendMode,
+    /// See `Painter::set_clip_path`.
+    pub clip: Option<Vec<ClipShape>>,
+}
+
+/// Identifies the geometry a painter got from a scene.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct SceneTag {
+    scene: u64,
+    generation: u64,
+    mode: GeometryMode,
+    reverse_winding: bool,
+    /// `Painter::geometry_margin`'s bits, so tags compare exactly.
+    margin: u32,
+    /// `None` for the whole scene.
+    layer: Option<u32>,
+}
+
+struct Encoded {
+    mode: GeometryMode,
+    reverse_winding: bool,
+    margin: u32,
+    vertices: Vec<Vertex>,
+    indexes: Vec<u32>,
+    /// Where each layer's triangles are, in layer order.
+    layers: Vec<EncodedLayer>,
+}
+
+struct EncodedLayer {
+    layer: u32,
+    vertices: Range<usize>,
+    indexes: Range<usize>,
+}
+
+static NEXT_SCENE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Colored rects, expanded into triangles only when uploaded.
+///
+/// The triangles are cached until the scene is modified, and uploading
+/// to a painter that already holds the scene's current geometry does
+/// nothing, so calling `upload` every frame only costs something on frames
+/// where the scene changed.
+pub struct RectScene {
+    id: u64,
+    /// Bumped on every mutation.
+    generation: u64,
+    shapes: Vec<SceneRect>,
+    /// Layers drawn other than with the defaults.
+    layers: BTreeMap<u32, LayerSettings>,
+    /// Bumped whenever `layers` changes, which the geometry doesn't
+    /// depend on.
+    layer_generation: u64,
+    cache: RefCell<Option<Encoded>>,
+}
+
+impl Default for RectScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The clone counts as a different scene, so it's uploaded again even if
+/// nothing changed.
+impl Clone for RectScene {
+    fn clone(&self) -> Self {
+        let mut scene = Self::new();
+        scene.shapes = self.shapes.clone();
+        scene.layers = self.layers.clone();
+        scene
+    }
+}
+
+impl RectScene {
+    pub fn new() -> Self {
+        Self {
+            id: NEXT_SCENE_ID.fetch_add(1, Ordering::Relaxed),
+            generation: 0,
+            shapes: Vec::new(),
+            layers: BTreeMap::new(),
+            layer_generation: 0,
+            cache: RefCell::new(None),
+        }
+    }
+
+    /// Adds a rect with a `z_index` of 0, in layer 0.
+    pub fn push(&mut self, rect: Rect, color: [f32; 4]) {
+        self.push_with_z(rect, color, 0);
+    }
+
+    /// Adds a rect in layer 0.
+    pub fn push_with_z(&mut self, rect: Rect, color: [f32; 4], z_index: i32) {
+        self.push_to_layer(0, rect, color, z_index);
+    }
+
+    pub fn push_to_layer(&mut self, layer: u32, rect: Rect, color: [f32; 4], z_index: i32) {
+        self.shapes_mut_vec().push(SceneRect {
+            rect,
+            color,
+            z_index,
+            layer,
+        });
+    }
+
+    /// How `layer` is drawn. Layers never set are drawn with the defaults:
+    /// premultiplied blending and no clip.
+    pub fn layer_settings(&self, layer: u32) -> LayerSettings {
+        self.layers.get(&layer).cloned().unwrap_or_default()
+    }
+
+    /// Fails, keeping the layer as it was, if the blend mode is one
+    /// painters can't use (see `BlendMode::validate`).
+    pub fn set_layer_settings(
+        &mut self,
+        layer: u32,
+        settings: LayerSettings,
+    ) -> Result<(), BlendError> {
+        settings.blend.validate()?;
+        self.layers.insert(layer, settings);
+        self.layer_generation += 1;
+        Ok(())
+    }
+
+    /// Fails like `set_layer_settings`.
+    pub fn set_layer_blend(&mut self, layer: u32, mode: BlendMode) -> Result<(), BlendError> {
+        self.set_layer_settings(
+            layer,
+            LayerSettings {
+                blend: mode,
+                ..self.layer_settings(layer)
+            },
+        )
+    }
+
+    /// Clips `layer` to `shapes`, or stops clipping it for `None`.
+    pub fn set_layer_clip(&mut self, layer: u32, shapes: Option<Vec<ClipShape>>) {
+        let settings = LayerSettings {
+            clip: shapes,
+            ..self.layer_settings(layer)
+        };
+        // The blend mode was checked when it was set.
+        self.layers.insert(layer, settings);
+        self.layer_generation += 1;
+    }
+
+    /// The layers with rects in them, in drawing order.
+    pub fn layers(&self) -> Vec<u32> {
+        let mut layers: Vec<u32> = self.shapes.iter().map(|shape| shape.layer).collect();
+        layers.sort_unstable();
+        layers.dedup();
+        layers
+    }
+
+    pub fn clear(&mut self) {
+        self.shapes_mut_vec().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    pub fn shapes(&self) -> &[SceneRect] {
+        &self.shapes
+    }
+
+    /// Marks the scene as modified, whether or not anything is written.
+    pub fn shapes_mut(&mut self) -> &mut [SceneRect] {
+        self.shapes_mut_vec()
+    }
+
+    fn shapes_mut_vec(&mut self) -> &mut Vec<SceneRect> {
+        self.generation += 1;
+        *self.cache.get_mut() = None;
+        &mut self.shapes
+    }
+
+    /// Writes the scene to a file at `path` in the binary format described
+    /// on `write_binary`, replacing any file there.
+    pub fn save_binary(&self, path: impl AsRef<Path>) -> Result<(), SceneFileError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write_binary(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Reads a scene written by `save_binary` or `write_binary`.
+    pub fn load_binary(path: impl AsRef<Path>) -> Result<Self, SceneFileError> {
+        Self::read_binary(BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the scene in oreb's binary scene format, 44 bytes per rect
+    /// after a 12-byte header. Everything is little-endian:
+    ///
+    /// - the magic bytes `ORSC`
+    /// - the format version, a `u8`, currently 1
+    /// - 3 reserved bytes, zero
+    /// - the rect count, a `u32`
+    /// - per rect, nine `f32`s: `center`, `size`, `orientation_radians`,
+    ///   then `color`, followed by the `z_index` as an `i32` and the
+    ///   `layer` as a `u32`
+    ///
+    /// Layer settings aren't saved.
+    ///
+    /// Versioning: a change to the layout bumps the version, which starts
+    /// at 1. Readers take every version up to their own, converting older
+    /// layouts, and refuse newer ones, and 0, with
+    /// `SceneFileError::UnsupportedVersion`, so files only have to be
+    /// rewritten to pick up new fields.
+    pub fn write_binary(&self, mut writer: impl Write) -> Result<(), SceneFileError> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[FORMAT_VERSION, 0, 0, 0])?;
+        writer.write_all(&(self.shapes.len() as u32).to_le_bytes())?;
+        for SceneRect {
+            rect,
+            color,
+            z_index,
+            layer,
+        } in &self.shapes
+        {
+            let values = rect
+                .center
+                .iter()
+                .chain(&rect.size)
+                .chain([&rect.orientation_radians])
+                .chain(color);
+            for value in values {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            writer.write_all(&z_index.to_le_bytes())?;
+            writer.write_all(&layer.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a scene written by `write_binary`.
+    ///
+    /// Fails with `SceneFileError::Io` on a truncated file, as well as on
+    /// read errors.
+    pub fn read_binary(mut reader: impl Read) -> Result<Self, SceneFileError> {
+        let mut header = [0; 12];
+        reader.read_exact(&mut header)?;
+        if header[..4] != MAGIC {
+            return Err(SceneFileError::BadMagic);
+        }
+        let version = header[4];
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(SceneFileError::UnsupportedVersion {
+                found: version,
+                supported: FORMAT_VERSION,
+            });
+        }
+        let count = u32::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+
+        let mut scene = Self::new();
+        // The count isn't trusted for the allocation, so a corrupt header
+        // fails on the read instead.
+        scene.shapes.reserve(count.min(1 << 16));
+        let mut record = [0; 44];
+        for _ in 0..count {
+            reader.read_exact(&mut record)?;
+            let mut words = record
+                .chunks_exact(4)
+                .map(|bytes| <[u8; 4]>::try_from(bytes).unwrap());
+            let mut next = || f32::from_le_bytes(words.next().unwrap());
+            let rect = Rect {
+                center: [next(), next()],
+                size: [next(), next()],
+                orientation_radians: next(),
+            };
+            let color = [next(), next(), next(), next()];
+            let z_index = i32::from_le_bytes(words.next().unwrap());
+            let layer = u32::from_le_bytes(words.next().unwrap());
+            scene.shapes.push(SceneRect {
+                rect,
+                color,
+                z_index,
+                layer,
+            });
+        }
+        Ok(scene)
+    }
+
+    /// Gives `painter` the scene's geometry, in the painter's geometry mode
+    /// and margin.
+    ///
+    /// Rects are ordered by `layer`, then by `z_index`, keeping the order
+    /// they were added among equal ones, so with the default blending later
+    /// rects composite over earlier ones. This is a sort on the CPU, redone
+    /// only when the scene changes; the painter needs no depth buffer, and
+    /// translucent rects blend with whatever is below them, which depth
+    /// testing alone wouldn't give. The `shapes()` keep their order. Layer
+    /// settings don't apply; see `LayerRenderer` for those.
+    ///
+    /// Does nothing if the painter already has it. Degenerate rects are
+    /// skipped, as in `Painter::set_rects`. Otherwise this fails like
+    /// `Painter::set_geometry`.
+    pub fn upload(&self, rc: &Context, painter: &mut Painter) -> Result<(), GeometryError> {
+        self.upload_tagged(rc, painter, None)
+    }
+
+    /// Like `upload`, but gives `painter` only the rects in `layer`.
+    pub fn upload_layer(
+        &self,
+        rc: &Context,
+        layer: u32,
+        painter: &mut Painter,
+    ) -> Result<(), GeometryError> {
+        self.upload_tagged(rc, painter, Some(layer))
+    }
+
+    fn upload_tagged(
+        &self,
+        rc: &Context,
+        painter: &mut Painter,
+        layer: Option<u32>,
+    ) -> Result<(), GeometryError> {
+        let tag = SceneTag {
+            scene: self.id,
+            generation: self.generation,
+            mode: painter.geometry_mode(),
+            reverse_winding: painter.reverse_winding(),
+            margin: painter.geometry_margin().to_bits(),
+            layer,
+        };
+        if painter.scene_tag == Some(tag) {
+            return Ok(());
+        }
+
+        let mut cache = self.cache.borrow_mut();
+        if !matches!(&*cache, Some(encoded)
+            if encoded.mode == tag.mode
+                && encoded.reverse_winding == tag.reverse_winding
+                && encoded.margin == tag.margin)
+        {
+            *cache = Some(self.encode(tag.mode, tag.reverse_winding, f32::from_bits(tag.margin)));
+        }
+        let encoded = cache.as_ref().unwrap();
+        match layer {
+            None => painter.set_geometry(rc, &encoded.vertices, &encoded.indexes)?,
+            Some(layer) => {
+                let (vertices, indexes) = encoded
+                    .layers
+                    .iter()
+                    .find(|encoded| encoded.layer == layer)
+                    .map_or((0..0, 0..0), |encoded| {
+                        (encoded.vertices.clone(), encoded.indexes.clone())
+                    });
+                let base = vertices.start as u32;
+                let indexes: Vec<u32> = encoded.indexes[indexes]
+                    .iter()
+                    .map(|index| index - base)
+                    .collect();
+                painter.set_geometry(rc, &encoded.vertices[vertices], &indexes)?;
+            }
+        }
+        painter.scene_tag = Some(tag);
+        Ok(())
+    }
+
+    /// Expands the shapes in drawing order, a layer at a time.
+    fn encode(&self, mode: GeometryMode, reverse_winding: bool, margin: f32) -> Encoded {
+        let mut encoded = Encoded {
+            mode,
+            reverse_winding,
+            margin: margin.to_bits(),
+            vertices: Vec::new(),
+            indexes: Vec::new(),
+            layers: Vec::new(),
+        };
+        let shapes = self.sorted_shapes();
+        for group in shapes.chunk_by(|a, b| a.layer == b.layer) {
+            let geometry::Encoded {
+                vertices, indexes, ..
+            } = geometry::encode_colored(
+                mode,
+                reverse_winding,
+                margin,
+                group.iter().map(|shape| (&shape.rect, shape.color)),
+            );
+            let (vertex_start, index_start) = (encoded.vertices.len(), encoded.indexes.len());
+            let base = vertex_start as u32;
+            encoded.vertices.extend(vertices);
+            encoded
+                .indexes
+                .extend(indexes.iter().map(|index| base + index));
+            encoded.layers.push(EncodedLayer {
+                layer: group[0].layer,
+                vertices: vertex_start..encoded.vertices.len(),
+                indexes: index_start..encoded.indexes.len(),
+            });
+        }
+        encoded
+    }
+
+    /// The shapes in drawing order. The sort is stable, so ties keep
+    /// insertion order.
+    fn sorted_shapes(&self) -> Vec<&SceneRect> {
+        let mut shapes: Vec<&SceneRect> = self.shapes.iter().collect();
+        shapes.sort_by_key(|shape| (shape.layer, shape.z_index));
+        shapes
+    }
+
+    /// Identifies the current layer settings, to tell when a painter's
+    /// are stale.
+    pub(super) fn layer_tag(&self) -> (u64, u64) {
+        (self.id, self.layer_generation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene_of(count: usize) -> RectScene {
+        let mut scene = RectScene::new();
+        for i in 0..count {
+            let f = i as f32;
+            scene.push_to_layer(
+                i as u32 % 3,
+                Rect::new([f * 1e-4, -f], [0.5 + f, 1e-3 * f], f.sin()),
+                [f / count as f32, 0.25, 1.0 / (f + 1.0), 0.5],
+                i as i32 - 5000,
+            );
+        }
+        scene
+    }
+
+    fn bytes_of(scene: &RectScene) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        scene.write_binary(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let scene = scene_of(10_000);
+        let bytes = bytes_of(&scene);
+        assert_eq!(bytes.len(), 12 + 44 * 10_000);
+        let read = RectScene::read_binary(bytes.as_slice()).unwrap();
+        assert_eq!(read.len(), scene.len());
+        for (a, b) in read.shapes().iter().zip(scene.shapes()) {
+            assert_eq!(a.rect.center, b.rect.center);
+            assert_eq!(a.rect.size, b.rect.size);
+            assert_eq!(
+                a.rect.orientation_radians.to_bits(),
+                b.rect.orientation_radians.to_bits()
+            );
+            assert_eq!(a.color, b.color);
+            assert_eq!(a.z_index, b.z_index);
+            assert_eq!(a.layer, b.layer);
+        }
+    }
+
+    #[test]
+    fn version_zero_fails() {
+        let mut bytes = bytes_of(&scene_of(1));
+        bytes[4] = 0;
+        assert!(matches!(
+            RectScene::read_binary(bytes.as_slice()),
+            Err(SceneFileError::UnsupportedVersion { found: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn truncated_binary_fails() {
+        let bytes = bytes_of(&scene_of(3));
+        for len in [0, 11, 12 + 44, bytes.len() - 1] {
+            match RectScene::read_binary(&bytes[..len]) {
+                Err(SceneFileError::Io(e)) => {
+                    assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof)
+                }
+                other => panic!("Read {len} bytes as {:?}", other.map(|s| s.len())),
+            }
+        }
+    }
+
+    #[test]
+    fn bad_magic_fails() {
+        let mut bytes = bytes_of(&scene_of(1));
+        bytes[..4].copy_from_slice(b"ORSX");
+        assert!(matches!(
+            RectScene::read_binary(bytes.as_slice()),
+            Err(SceneFileError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn newer_version_fails() {
+        let mut bytes = bytes_of(&scene_of(1));
+        bytes[4] = FORMAT_VERSION + 1;
+        assert!(matches!(
+            RectScene::read_binary(bytes.as_slice()),
+            Err(SceneFileError::UnsupportedVersion {
+                found,
+                supported: FORMAT_VERSION,
+            }) if found == FORMAT_VERSION + 1
+        ));
+    }
+
+    /// The order `upload` gives a painter the rects in, by their colors'
+    /// red channel.
+    fn drawing_order(scene: &RectScene) -> Vec<f32> {
+        let encoded = scene.encode(GeometryMode::Triangle, false, 0.0);
+        encoded
+            .vertices
+            .chunks_exact(3)
+            .map(|corners| corners[0].color[0])
+            .collect()
+    }
+
+    #[test]
+    fn upload_orders_by_z_index_stably() {
+        let mut scene = RectScene::new();
+        let rect = Rect::new([0.0, 0.0], [1.0, 1.0], 0.0);
+        for (id, z_index) in [(0, 1), (1, -1), (2, 0), (3, 1), (4, -1), (5, 0)] {
+            scene.push_with_z(rect, [id as f32, 0.0, 0.0, 1.0], z_index);
+        }
+        assert_eq!(drawing_order(&scene), [1.0, 4.0, 2.0, 5.0, 0.0, 3.0]);
+        // The scene's own order is left alone.
+        let ids: Vec<f32> = scene.shapes().iter().map(|s| s.color[0]).collect();
+        assert_eq!(ids, [0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+
+    /// Identifies the scene's cached geometry, or `None` without any.
+    fn cached(scene: &RectScene) -> Option<*const Vertex> {
+        scene.cache.borrow().as_ref().map(|e| e.vertices.as_ptr())
+    }
+
+    #[test]
+    fn upload_reuses_geometry_until_something_changes() {
+        let Some(rc) = crate::test_util::context(16, 16) else {
+            return;
+        };
+        let mut painter = rc.make_rect_painter();
+        let mut scene = scene_of(4);
+        assert_eq!(cached(&scene), None);
+        scene.upload(&rc, &mut painter).unwrap();
+        let first = cached(&scene).unwrap();
+        let tag = painter.scene_tag.unwrap();
+
+        // Unchanged, neither the scene nor the painter does anything.
+        scene.upload(&rc, &mut painter).unwrap();
+        assert_eq!(cached(&scene), Some(first));
+        assert_eq!(painter.scene_tag, Some(tag));
+        // Another painter with the same settings gets the cached geometry.
+        let mut other = rc.make_rect_painter();
+        scene.upload(&rc, &mut other).unwrap();
+        assert_eq!(cached(&scene), Some(first));
+        assert_eq!(other.vertex_count, painter.vertex_count);
+
+        // A new margin re-encodes.
+        painter.set_geometry_margin(0.5);
+        scene.upload(&rc, &mut painter).unwrap();
+        assert_ne!(painter.scene_tag, Some(tag));
+        let margin = scene.cache.borrow().as_ref().unwrap().margin;
+        assert_eq!(f32::from_bits(margin), 0.5);
+        let grown = painter.scene_tag.unwrap();
+
+        // So does any change to the rects, even one that's written back
+        // unchanged.
+        scene.shapes_mut()[0].rect.size[0] += 1.0;
+        assert_eq!(cached(&scene), None);
+        scene.upload(&rc, &mut painter).unwrap();
+        assert_ne!(painter.scene_tag, Some(grown));
+        let tag = painter.scene_tag.unwrap();
+        let _ = scene.shapes_mut();
+        scene.upload(&rc, &mut painter).unwrap();
+        assert_ne!(painter.scene_tag, Some(tag));
+    }
+}
@@ -0,0 +1,135 @@
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindingResource, Buffer,
+    BufferBinding, BufferDescriptor, BufferSize, BufferUsages,
+};
+
+use super::{PainterSettings, SettingsUniform, MAX_STYLES};
+use crate::{util::as_raw_bytes, Context};
+
+/// Named `PainterSettings` in slots of one uniform buffer, each with a bind
+/// group that swaps it in for the painter's own settings. See
+/// `Painter::define_style`.
+pub(super) struct Styles {
+    buffer: Buffer,
+    /// Bytes between slots: the settings' size rounded up to the device's
+    /// `min_uniform_buffer_offset_alignment`.
+    stride: u64,
+    names: Vec<String>,
+    /// One per name, binding the slot along with the painter's transform
+    /// and tint.
+    bind_groups: Vec<BindGroup>,
+}
+
+impl Styles {
+    pub(super) fn new(rc: &Context) -> Self {
+        let size = std::mem::size_of::<SettingsUniform>() as u64;
+        let stride = size.next_multiple_of(rc.limits().min_uniform_buffer_offset_alignment as u64);
+        let buffer = rc.device.create_buffer(&BufferDescriptor {
+            label: Some("Painter styles"),
+            size: stride * MAX_STYLES as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            buffer,
+            stride,
+            names: Vec::new(),
+            bind_groups: Vec::new(),
+        }
+    }
+
+    /// Writes `settings` into `name`'s slot, taking the next free one for a
+    /// new name.
+    pub(super) fn define(
+        &mut self,
+        rc: &Context,
+        layout: &BindGroupLayout,
+        transform: &Buffer,
+        tint: &Buffer,
+        name: &str,
+        settings: &PainterSettings,
+    ) {
+        let slot = match self.names.iter().position(|e| e == name) {
+            Some(slot) => slot,
+            None => {
+                assert!(
+                    self.names.len() < MAX_STYLES,
+                    "A painter holds at most {MAX_STYLES} styles"
+                );
+                let slot = self.names.len();
+                self.bind_groups
+                    .push(self.make_bind_group(rc, layout, transform, tint, slot));
+                self.names.push(name.to_owned());
+                slot
+            }
+        };
+        let uniform = SettingsUniform::from(settings);
+        rc.commands
+            .write_buffer(&self.buffer, slot as u64 * self.stride, unsafe {
+                as_raw_bytes(&uniform)
+            });
+    }
+
+    pub(super) fn bind_group(&self, name: &str) -> Option<&BindGroup> {
+        let slot = self.names.iter().position(|e| e == name)?;
+        Some(&self.bind_groups[slot])
+    }
+
+    pub(super) fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    fn make_bind_group(
+        &self,
+        rc: &Context,
+        layout: &BindGroupLayout,
+        transform: &Buffer,
+        tint: &Buffer,
+        slot: usize,
+    ) -> BindGroup {
+        settings_bind_group(
+            rc,
+            "Painter style bind group",
+            layout,
+            &self.buffer,
+            slot as u64 * self.stride,
+            transform,
+            tint,
+        )
+    }
+}
+
+/// Binds the `PainterSettings` at `offset` in `settings` in place of the
+/// painter's own, along with its transform and tint.
+pub(super) fn settings_bind_group(
+    rc: &Context,
+    label: &str,
+    layout: &BindGroupLayout,
+    settings: &Buffer,
+    offset: u64,
+    transform: &Buffer,
+    tint: &Buffer,
+) -> BindGroup {
+    rc.device.create_bind_group(&BindGroupDescriptor {
+        label: Some(label),
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(BufferBinding {
+                    buffer: settings,
+                    offset,
+                    size: BufferSize::new(std::mem::size_of::<SettingsUniform>() as u64),
+                }),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: transform.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: tint.as_entire_binding(),
+            },
+        ],
+    })
+}
@@ -0,0 +1,67 @@
+use super::PainterSettings;
+
+/// A UI's colors and stroke in one place, for `PainterSettings::from_theme`.
+///
+/// Colors are premultiplied, like `PainterSettings`' own.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Theme {
+    /// Fills controls, such as buttons.
+    pub primary: [f32; 4],
+    /// Fills what controls sit on, such as panels.
+    pub secondary: [f32; 4],
+    /// Outlines everything.
+    pub stroke: [f32; 4],
+    /// The outline's width, in viewport pixels.
+    pub stroke_width: f32,
+}
+
+/// Neutral greys outlined in a pixel of black.
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            primary: [0.6, 0.6, 0.6, 1.0],
+            secondary: [0.2, 0.2, 0.2, 1.0],
+            stroke: [0.0, 0.0, 0.0, 1.0],
+            stroke_width: 1.0,
+        }
+    }
+}
+
+/// What a rect is for, which picks the `Theme` colors it's drawn in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// The `secondary` fill with the stroke.
+    Panel,
+    /// The `primary` fill with the stroke.
+    Button,
+    /// Like `Button`, with the stroke twice as wide, e.g. for the chosen
+    /// item of a list or the focused control.
+    Selected,
+    /// Like `Button`, faded to half opacity.
+    Disabled,
+}
+
+impl PainterSettings {
+    /// Settings drawing `role` in `theme`'s look. Everything the theme
+    /// doesn't cover, like the corner radius, is as in `default()`, so
+    /// adjust those on the result as needed before `set_uniforms`.
+    pub fn from_theme(theme: &Theme, role: Role) -> Self {
+        let (fill, edge, line_width_px) = match role {
+            Role::Panel => (theme.secondary, theme.stroke, theme.stroke_width),
+            Role::Button => (theme.primary, theme.stroke, theme.stroke_width),
+            Role::Selected => (theme.primary, theme.stroke, 2.0 * theme.stroke_width),
+            // Premultiplied, so fading scales every channel.
+            Role::Disabled => (
+                theme.primary.map(|c| 0.5 * c),
+                theme.stroke.map(|c| 0.5 * c),
+                theme.stroke_width,
+            ),
+        };
+        Self {
+            fill,
+            edge,
+            line_width_px,
+            ..Default::default()
+        }
+    }
+}
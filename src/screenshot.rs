@@ -0,0 +1,376 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+};
+
+use wgpu::{
+    Buffer, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoderDescriptor,
+    CompositeAlphaMode, Device, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout,
+    Maintain, MapMode, Origin3d, Texture, TextureFormat, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+use crate::Context;
+
+/// Options for `Context::read_pixels`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ScreenshotOptions {
+    /// Divide color by alpha so the pixels have straight alpha, which is
+    /// what image files and editors expect.
+    ///
+    /// Painters blend premultiplied color, so this is needed for anything
+    /// translucent to look right once saved. It's skipped when the surface
+    /// is configured as `PostMultiplied` since its content is already
+    /// straight.
+    pub straight_alpha: bool,
+    /// Which array layer to read, for array textures like an
+    /// `ArrayTarget`'s. 0 by default.
+    pub layer: u32,
+}
+
+impl Default for ScreenshotOptions {
+    fn default() -> Self {
+        Self {
+            straight_alpha: true,
+            layer: 0,
+        }
+    }
+}
+
+/// Pixels read back from a texture.
+#[derive(Debug, Clone)]
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed 8-bit rgba, row-major starting from the top-left.
+    /// sRGB encoded if the texture's format was.
+    pub rgba: Vec<u8>,
+}
+
+#[cfg(feature = "png")]
+impl Screenshot {
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> Result<(), png::EncodingError> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.rgba)
+    }
+}
+
+pub(crate) fn read_pixels(
+    rc: &Context,
+    texture: &Texture,
+    options: &ScreenshotOptions,
+) -> Screenshot {
+    let readback = Readback::start(rc, texture, options);
+    readback
+        .buffer
+        .slice(..)
+        .map_async(MapMode::Read, |result| {
+            result.expect("Failed to map the readback buffer");
+        });
+    rc.device.poll(Maintain::Wait);
+    readback.finish()
+}
+
+/// A `Screenshot` on its way back from the GPU. See
+/// `Context::read_pixels_async`.
+///
+/// Resolves once the copy is done, which wgpu notices the next time the
+/// device is polled or work is submitted. Polling the future does a
+/// non-blocking poll of the device itself; after that, an app that keeps
+/// rendering frames keeps it moving, so an `await` on another task
+/// finishes a frame or two later without stalling the render loop. The
+/// future doesn't borrow the context, so it can be spawned on, e.g.,
+/// `async_std::task::spawn` or `tokio::spawn` to save the image there.
+///
+/// Dropping it before it resolves abandons the copy.
+pub struct ScreenshotFuture {
+    device: Arc<Device>,
+    /// Taken when the future resolves.
+    readback: Option<Readback>,
+    state: Arc<Mutex<MapState>>,
+}
+
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+pub(crate) fn read_pixels_async(
+    rc: &Context,
+    texture: &Texture,
+    options: &ScreenshotOptions,
+) -> ScreenshotFuture {
+    let readback = Readback::start(rc, texture, options);
+    let state = Arc::new(Mutex::new(MapState::default()));
+    let callback_state = state.clone();
+    readback
+        .buffer
+        .slice(..)
+        .map_async(MapMode::Read, move |result| {
+            let mut state = callback_state.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+    ScreenshotFuture {
+        device: rc.device.clone(),
+        readback: Some(readback),
+        state,
+    }
+}
+
+impl ScreenshotFuture {
+    fn take_result(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> Option<Result<(), BufferAsyncError>> {
+        let mut state = self.state.lock().unwrap();
+        let result = state.result.take();
+        if result.is_none() {
+            state.waker = Some(cx.waker().clone());
+        }
+        result
+    }
+}
+
+impl Future for ScreenshotFuture {
+    type Output = Screenshot;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Screenshot> {
+        // The map callback runs inside the device poll and takes the lock,
+        // so the lock isn't held across it.
+        let result = match self.take_result(cx) {
+            Some(result) => Some(result),
+            None => {
+                self.device.poll(Maintain::Poll);
+                self.take_result(cx)
+            }
+        };
+        match result {
+            Some(result) => {
+                result.expect("Failed to map the readback buffer");
+                let readback = self
+                    .readback
+                    .take()
+                    .expect("ScreenshotFuture polled after it resolved");
+                Poll::Ready(readback.finish())
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// A copy of a texture into a buffer, and what it takes to turn the
+/// buffer's contents into a `Screenshot`.
+struct Readback {
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    row_bytes: u32,
+    padded_row_bytes: u32,
+    bgra: bool,
+    srgb: bool,
+    straighten: bool,
+}
+
+impl Readback {
+    /// Submits the copy.
+    fn start(rc: &Context, texture: &Texture, options: &ScreenshotOptions) -> Self {
+        let format = texture.format();
+        let (bgra, srgb) = match format {
+            TextureFormat::Rgba8Unorm => (false, false),
+            TextureFormat::Rgba8UnormSrgb => (false, true),
+            TextureFormat::Bgra8Unorm => (true, false),
+            TextureFormat::Bgra8UnormSrgb => (true, true),
+            _ => panic!("Can't read pixels of {format:?} textures, only 8-bit rgba or bgra"),
+        };
+        let (width, height) = (texture.width(), texture.height());
+
+        // Rows of the copy have to be aligned.
+        let row_bytes = 4 * width;
+        let padded_row_bytes = row_bytes.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = rc.device.create_buffer(&BufferDescriptor {
+            label: Some("Readback buffer"),
+            size: (padded_row_bytes * height) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        commands.copy_texture_to_buffer(
+            ImageCopyTexture {
+                origin: Origin3d {
+                    z: options.layer,
+                    ..Default::default()
+                },
+                ..texture.as_image_copy()
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        rc.commands.submit(std::iter::once(commands.finish()));
+
+        let premultiplied = rc.config.alpha_mode != CompositeAlphaMode::PostMultiplied;
+        Self {
+            buffer,
+            width,
+            height,
+            row_bytes,
+            padded_row_bytes,
+            bgra,
+            srgb,
+            straighten: options.straight_alpha && premultiplied,
+        }
+    }
+
+    /// Reads the buffer, which has to be mapped by now.
+    fn finish(self) -> Screenshot {
+        let Self {
+            buffer,
+            width,
+            height,
+            row_bytes,
+            padded_row_bytes,
+            bgra,
+            srgb,
+            straighten,
+        } = self;
+        let mut rgba = Vec::with_capacity((row_bytes * height) as usize);
+        for row in buffer
+            .slice(..)
+            .get_mapped_range()
+            .chunks(padded_row_bytes as usize)
+        {
+            rgba.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        buffer.unmap();
+
+        for pixel in rgba.chunks_exact_mut(4) {
+            if bgra {
+                pixel.swap(0, 2);
+            }
+            if straighten {
+                unpremultiply(pixel, srgb);
+            }
+        }
+
+        Screenshot {
+            width,
+            height,
+            rgba,
+        }
+    }
+}
+
+/// Premultiplication happens on linear values, so sRGB encoded color is
+/// decoded before dividing.
+fn unpremultiply(pixel: &mut [u8], srgb: bool) {
+    let alpha = pixel[3];
+    if alpha == 0 || alpha == 255 {
+        return;
+    }
+    let alpha = alpha as f32 / 255.0;
+    for c in &mut pixel[..3] {
+        let v = *c as f32 / 255.0;
+        let v = if srgb {
+            linear_to_srgb((srgb_to_linear(v) / alpha).min(1.0))
+        } else {
+            (v / alpha).min(1.0)
+        };
+        *c = (255.0 * v).round() as u8;
+    }
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rect::{PainterSettings, Rect},
+        test_util::{context, stored, Target},
+    };
+
+    #[test]
+    fn unpremultiplies_linear_values() {
+        let mut pixel = [64, 32, 0, 128];
+        unpremultiply(&mut pixel, false);
+        assert_eq!(pixel, [128, 64, 0, 128]);
+        // Opaque and fully transparent pixels are left alone.
+        for mut pixel in [[10, 20, 30, 255], [10, 20, 30, 0]] {
+            let before = pixel;
+            unpremultiply(&mut pixel, true);
+            assert_eq!(pixel, before);
+        }
+    }
+
+    #[test]
+    fn straight_alpha_unpremultiplies_a_translucent_rect() {
+        let Some(rc) = context(16, 16) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let mut painter = rc.make_rect_painter();
+        painter
+            .set_rects(&rc, &[Rect::new([0.0, 0.0], [2.0, 2.0], 0.0)])
+            .unwrap();
+        // Premultiplied, as painters take colors.
+        let color = [0.5, 0.25, 0.0, 0.5];
+        painter.set_uniforms(
+            &rc,
+            &PainterSettings {
+                edge: color,
+                fill: color,
+                ..Default::default()
+            },
+        );
+        painter
+            .draw(&rc, &target.view, wgpu::Color::TRANSPARENT)
+            .unwrap();
+
+        let encode = |v| stored(target.texture.format(), v);
+        let assert_near = |pixel: [u8; 4], expected: [f32; 4]| {
+            for (p, e) in pixel.iter().zip(expected) {
+                assert!((*p as f32 - e).abs() <= 2.0, "{pixel:?} != {expected:?}");
+            }
+        };
+        let premultiplied = target.read(&rc).at(8, 8);
+        assert_near(premultiplied, [encode(0.5), encode(0.25), 0.0, 127.5]);
+        let straight = rc.read_pixels(&target.texture, &ScreenshotOptions::default());
+        let i = 4 * (8 * 16 + 8);
+        let straight: [u8; 4] = straight.rgba[i..i + 4].try_into().unwrap();
+        assert_near(straight, [255.0, encode(0.5), 0.0, 127.5]);
+    }
+}
@@ -0,0 +1,150 @@
+use wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Color, ColorTargetState, ColorWrites,
+    CommandEncoderDescriptor, Extent3d, FragmentState, ImageCopyTexture, LoadOp, MultisampleState,
+    Operations, Origin3d, PipelineLayoutDescriptor, PrimitiveState, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, Texture, TextureAspect, TextureDescriptor, TextureDimension,
+    TextureSampleType, TextureUsages, TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+use super::texture::FORMAT;
+use crate::Context;
+
+/// The number of levels in a full mip chain for a `width` by `height`
+/// texture, down to 1x1.
+pub(crate) fn level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Fills in mip levels by rendering each from the one before.
+pub(crate) struct Generator {
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+}
+
+impl Generator {
+    pub(crate) fn new(rc: &Context) -> Self {
+        let layout = rc
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Mip generator bind group layout"),
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+            });
+
+        let module = &rc.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Mip generator shader module"),
+            source: ShaderSource::Wgsl(include_str!("mips.wgsl").into()),
+        });
+
+        let pipeline = rc.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Mip generator pipeline"),
+            layout: Some(
+                &rc.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("Mip generator pipeline layout"),
+                    bind_group_layouts: &[&layout],
+                    push_constant_ranges: &[],
+                }),
+            ),
+            vertex: VertexState {
+                module,
+                entry_point: "vs",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module,
+                entry_point: "fs",
+                targets: &[Some(ColorTargetState {
+                    format: FORMAT,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self { pipeline, layout }
+    }
+
+    /// Renders levels `1..` of `texture` from level 0. The work is
+    /// submitted right away.
+    ///
+    /// `texture` needs `COPY_SRC` and `RENDER_ATTACHMENT` usage.
+    pub(crate) fn run(&self, rc: &Context, texture: &Texture) {
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        for level in 1..texture.mip_level_count() {
+            // The GL backend can't sample views that start past level 0, so
+            // the previous level is read from a copy.
+            let size = Extent3d {
+                width: (texture.width() >> (level - 1)).max(1),
+                height: (texture.height() >> (level - 1)).max(1),
+                depth_or_array_layers: 1,
+            };
+            let previous = rc.device.create_texture(&TextureDescriptor {
+                label: Some("Mip generator source"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: FORMAT,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            commands.copy_texture_to_texture(
+                ImageCopyTexture {
+                    texture,
+                    mip_level: level - 1,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                previous.as_image_copy(),
+                size,
+            );
+            let bind_group = rc.device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Mip generator bind group"),
+                layout: &self.layout,
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(
+                        &previous.create_view(&TextureViewDescriptor::default()),
+                    ),
+                }],
+            });
+            let target = texture.create_view(&TextureViewDescriptor {
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            });
+            let mut pass = commands.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Mip generator pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        rc.commands.submit(std::iter::once(commands.finish()));
+    }
+}
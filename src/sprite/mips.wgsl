@@ -0,0 +1,48 @@
+// The next larger mip level.
+@group(0) @binding(0)
+var previous: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+}
+
+// One triangle covering the viewport.
+@vertex
+fn vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(2.0 * uv - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// Averages the texels of the previous level under this one: 2x2 of them,
+// or 3 wide (or tall) in the last column (or row) when the previous level
+// has an odd size, so no texel is left out.
+@fragment
+fn fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    let previous_size = vec2<i32>(textureDimensions(previous));
+    let size = max(previous_size / 2, vec2(1));
+    let p = vec2<i32>(in.position.xy);
+    let lo = 2 * p;
+    let hi = min(select(lo + 1, previous_size - 1, p == size - 1), previous_size - 1);
+
+    var color = vec3<f32>();
+    var alpha = 0.0;
+    var count = 0.0;
+    for (var y = lo.y; y <= hi.y; y++) {
+        for (var x = lo.x; x <= hi.x; x++) {
+            // Loads from an sRGB texture are decoded to linear.
+            let texel = textureLoad(previous, vec2(x, y), 0);
+            // Sprite textures have straight alpha. Weighting by alpha keeps
+            // the color of transparent texels from bleeding into their
+            // neighbours.
+            color += texel.rgb * texel.a;
+            alpha += texel.a;
+            count += 1.0;
+        }
+    }
+    if alpha == 0.0 {
+        return vec4<f32>();
+    }
+    return vec4(color / alpha, alpha / count);
+}
@@ -0,0 +1,565 @@
+use std::sync::Arc;
+
+use log::debug;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    vertex_attr_array, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState,
+    Buffer, BufferBindingType, BufferUsages, Color, ColorTargetState, ColorWrites,
+    CommandEncoderDescriptor, Face, FragmentState, FrontFace, MultisampleState,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPass,
+    RenderPipeline, RenderPipelineDescriptor, SamplerBindingType, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, SurfaceError, TextureFormat, TextureSampleType, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexAttribute, VertexBufferLayout, VertexState,
+    VertexStepMode,
+};
+
+use crate::{
+    ignore_depth_stencil, transform,
+    upload::Uploader,
+    util::{as_u8_slice, grow, shrink},
+    view::View,
+    Context, GeometryError, RenderPassBuilder, Transform2D, UploadStrategy,
+};
+
+pub(crate) mod mips;
+mod texture;
+#[cfg(feature = "video")]
+pub(crate) mod yuv;
+
+pub use texture::{SamplerDesc, SpriteTexture, TextureOptions};
+#[cfg(feature = "video")]
+pub use yuv::YuvColorSpace;
+
+/// The number of texture slots a sprite painter can bind at once.
+///
+/// Each slot is a separate texture and sampler binding, so this is limited
+/// by `max_sampled_textures_per_shader_stage` and
+/// `max_samplers_per_shader_stage`. Both are 16 on every backend with
+/// default limits. Bindless texture arrays would lift the limit, but they
+/// need native-only features that aren't requested.
+pub const MAX_TEXTURES: usize = 8;
+
+/// The sprite painter's WGSL, with entry points `vs` and `fs`.
+///
+/// Bindings:
+///
+/// - group 0, bindings `2 * i` and `2 * i + 1` for `i` in
+///   `0..MAX_TEXTURES`: slot `i`'s texture and sampler, fragment stage.
+/// - group 0, binding `2 * MAX_TEXTURES`: the painter's transform as a
+///   `mat4x4<f32>` (see `set_transform`), vertex stage.
+/// - group 0, binding `2 * MAX_TEXTURES + 1`: each slot's parameters as an
+///   `array<TextureParams, MAX_TEXTURES>`, fragment stage. Each holds the
+///   color key as a `vec4<f32>`, with the sRGB key color in `xyz` and the
+///   tolerance in `w`, negative when off, then a `u32` that's 1 if the
+///   texture is premultiplied and the painter's alpha cutoff as an `f32`,
+///   0 when off, padded to 32 bytes.
+/// - group 1, binding 0: the context's view uniforms, a struct holding one
+///   `mat4x4<f32>`, vertex stage. Shared by every painter of a context.
+///
+/// Vertex buffer 0 holds one `Sprite` per instance at locations 0 through
+/// 4, in field order. Quad corners come from the vertex index.
+pub const SHADER_SOURCE: &str = include_str!("painter.wgsl");
+
+/// Follows the texture slots.
+const TRANSFORM_BINDING: u32 = 2 * MAX_TEXTURES as u32;
+const TEXTURE_PARAMS_BINDING: u32 = TRANSFORM_BINDING + 1;
+
+/// One slot's entry in the texture parameters uniform. See `SHADER_SOURCE`.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct TextureParams {
+    color_key: [f32; 4],
+    premultiplied: u32,
+    /// The same in every slot. See `Painter::set_alpha_cutoff`.
+    alpha_cutoff: f32,
+    _padding: [u32; 2],
+}
+
+impl TextureParams {
+    fn new(texture: Option<&SpriteTexture>, alpha_cutoff: Option<f32>) -> Self {
+        let color_key = match texture.and_then(|t| t.color_key) {
+            Some(([r, g, b], tolerance)) => [r, g, b, tolerance],
+            None => [0.0, 0.0, 0.0, -1.0],
+        };
+        Self {
+            color_key,
+            premultiplied: texture.map_or(0, |t| t.premultiplied as u32),
+            alpha_cutoff: alpha_cutoff.unwrap_or(0.0),
+            _padding: [0; 2],
+        }
+    }
+}
+
+/// One textured quad, drawn as an instance.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Sprite {
+    /// In clip space.
+    pub center: [f32; 2],
+    /// In clip space.
+    pub size: [f32; 2],
+    /// `(u0, v0, u1, v1)`: the region of the texture to draw. `(0, 0)` is
+    /// the top-left of the texture.
+    pub uv_rect: [f32; 4],
+    pub orientation_radians: f32,
+    /// Which of the painter's texture slots to sample.
+    /// Indexes past the last slot sample the last slot.
+    pub texture_index: u32,
+}
+
+impl Sprite {
+    /// An upright sprite showing the whole texture in slot `texture_index`.
+    pub fn new(center: [f32; 2], size: [f32; 2], texture_index: u32) -> Self {
+        Self {
+            center,
+            size,
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            orientation_radians: 0.0,
+            texture_index,
+        }
+    }
+
+    const ATTRS: [VertexAttribute; 5] = vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x4,
+        3 => Float32,
+        4 => Uint32
+    ];
+
+    fn layout<'a>() -> VertexBufferLayout<'a> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as _,
+            step_mode: VertexStepMode::Instance,
+            attributes: &Self::ATTRS,
+        }
+    }
+}
+
+pub struct Painter {
+    pipeline: RenderPipeline,
+    /// The color format the pipeline was made for.
+    format: TextureFormat,
+    /// The context's view uniforms, bound as group 1.
+    pub(crate) view: Arc<View>,
+    layout: BindGroupLayout,
+    bind_group: BindGroup,
+    /// Bound to slots that don't have a texture.
+    placeholder: SpriteTexture,
+    transform: Buffer,
+    texture_params: Buffer,
+    /// What's in `texture_params`, to rewrite it with a new cutoff.
+    params: [TextureParams; MAX_TEXTURES],
+    alpha_cutoff: Option<f32>,
+    instances: Buffer,
+    instance_count: usize,
+    uploader: Uploader,
+}
+
+impl Painter {
+    pub(crate) fn new(rc: &Context) -> Self {
+        let mut entries: Vec<_> = (0..MAX_TEXTURES as u32)
+            .flat_map(|slot| {
+                [
+                    BindGroupLayoutEntry {
+                        binding: 2 * slot,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 2 * slot + 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ]
+            })
+            .collect();
+        let uniform = |binding, visibility| BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        entries.push(uniform(TRANSFORM_BINDING, ShaderStages::VERTEX));
+        entries.push(uniform(TEXTURE_PARAMS_BINDING, ShaderStages::FRAGMENT));
+        let layout = rc
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Sprite painter bind group layout"),
+                entries: &entries,
+            });
+
+        let placeholder = SpriteTexture::new(rc, 1, 1, &[255; 4], &Default::default());
+        let transform = transform::make_buffer(rc, &Transform2D::IDENTITY);
+        let texture_params = rc.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Sprite texture params"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            contents: unsafe { as_u8_slice(&[TextureParams::new(None, None); MAX_TEXTURES]) },
+        });
+        let bind_group =
+            make_bind_group(rc, &layout, &[], &placeholder, &transform, &texture_params);
+
+        let pipeline = make_pipeline(rc, &layout);
+
+        // Grows as needed in `set_sprites`.
+        let instances = rc.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Sprite instance buffer"),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            contents: &[0; 64 * std::mem::size_of::<Sprite>()],
+        });
+
+        Self {
+            pipeline,
+            format: rc.format(),
+            view: rc.view.clone(),
+            layout,
+            bind_group,
+            placeholder,
+            transform,
+            texture_params,
+            params: [TextureParams::new(None, None); MAX_TEXTURES],
+            alpha_cutoff: None,
+            instances,
+            instance_count: 0,
+            uploader: Uploader::new(UploadStrategy::default()),
+        }
+    }
+
+    /// Remakes the painter's pipeline for the context's current `format()`,
+    /// after it changed from the one the painter was made with.
+    ///
+    /// Sprites, textures and the transform are kept. Does nothing if the
+    /// format is unchanged. Painters aren't tracked by their context, so
+    /// this is up to the caller whenever the format changes.
+    pub fn rebuild_for_format(&mut self, rc: &Context) {
+        if self.format != rc.format() {
+            self.pipeline = make_pipeline(rc, &self.layout);
+            self.format = rc.format();
+        }
+    }
+
+    /// The layout of bind group 0. See `SHADER_SOURCE` for its bindings.
+    pub fn bind_group_layout(&self) -> &BindGroupLayout {
+        &self.layout
+    }
+
+    /// Binds `textures` to slots `0..textures.len()`.
+    ///
+    /// Unused slots sample opaque white.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than `MAX_TEXTURES` textures.
+    pub fn set_textures(&mut self, rc: &Context, textures: &[&SpriteTexture]) {
+        assert!(
+            textures.len() <= MAX_TEXTURES,
+            "A sprite painter has {MAX_TEXTURES} texture slots but {} textures were given",
+            textures.len()
+        );
+        self.params = std::array::from_fn(|slot| {
+            TextureParams::new(textures.get(slot).copied(), self.alpha_cutoff)
+        });
+        rc.commands.write_buffer(&self.texture_params, 0, unsafe {
+            as_u8_slice(&self.params)
+        });
+        self.bind_group = make_bind_group(
+            rc,
+            &self.layout,
+            textures,
+            &self.placeholder,
+            &self.transform,
+            &self.texture_params,
+        );
+    }
+
+    /// Discards fragments whose alpha, after any color keying, is below
+    /// `cutoff`, for cutouts such as foliage. `None`, the default, blends
+    /// everything. See `rect::PainterSettings::alpha_cutoff`; the same
+    /// caveats apply.
+    pub fn set_alpha_cutoff(&mut self, rc: &Context, cutoff: Option<f32>) {
+        self.alpha_cutoff = cutoff;
+        for params in &mut self.params {
+            params.alpha_cutoff = cutoff.unwrap_or(0.0);
+        }
+        rc.commands.write_buffer(&self.texture_params, 0, unsafe {
+            as_u8_slice(&self.params)
+        });
+    }
+
+    pub fn alpha_cutoff(&self) -> Option<f32> {
+        self.alpha_cutoff
+    }
+
+    /// Transforms sprite corners before the context's orientation is
+    /// applied. The identity by default.
+    pub fn set_transform(&self, rc: &Context, transform: &Transform2D) {
+        transform::write_buffer(rc, &self.transform, transform);
+    }
+
+    /// Bytes held in the painter's instance and uniform buffers, and its
+    /// placeholder texture.
+    ///
+    /// An estimate for diagnostics. Bound textures aren't included since
+    /// they can be shared; see `SpriteTexture::gpu_memory_bytes`.
+    pub fn gpu_memory_bytes(&self) -> usize {
+        (self.instances.size() + self.transform.size() + self.texture_params.size()) as usize
+            + self.placeholder.gpu_memory_bytes()
+    }
+
+    /// Uploads sprites for drawing.
+    ///
+    /// Fails if the instance buffer would be larger than the device's
+    /// `max_buffer_size`. The painter draws nothing until the next
+    /// successful call in that case.
+    pub fn set_sprites(&mut self, rc: &Context, sprites: &[Sprite]) -> Result<(), GeometryError> {
+        self.instance_count = 0;
+        grow(
+            rc,
+            &mut self.instances,
+            std::mem::size_of_val(sprites),
+            "Sprite instance buffer",
+            BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        )?;
+        self.instance_count = sprites.len();
+        self.uploader
+            .write(rc, &[(&self.instances, 0, unsafe { as_u8_slice(sprites) })]);
+        Ok(())
+    }
+
+    /// Reallocates the instance buffer to fit the current sprites, giving
+    /// back memory held since some earlier, larger upload.
+    ///
+    /// The buffer otherwise only grows. This always copies the sprites
+    /// into a new buffer, so call it once after a one-off large batch
+    /// rather than routinely.
+    pub fn shrink_to_fit(&mut self, rc: &Context) {
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        shrink(
+            rc,
+            &mut commands,
+            &mut self.instances,
+            self.instance_count * std::mem::size_of::<Sprite>(),
+            "Sprite instance buffer",
+        );
+        rc.commands.submit(std::iter::once(commands.finish()));
+    }
+
+    /// Selects how `set_sprites` copies instances to the GPU.
+    pub fn set_upload_strategy(&mut self, strategy: UploadStrategy) {
+        if strategy != self.uploader.strategy() {
+            self.uploader = Uploader::new(strategy);
+        }
+    }
+
+    pub fn upload_strategy(&self) -> UploadStrategy {
+        self.uploader.strategy()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `rc` isn't on the device the painter was made with.
+    pub fn draw(
+        &self,
+        rc: &Context,
+        view: &TextureView,
+        clear_color: impl Into<Option<Color>>,
+    ) -> Result<(), SurfaceError> {
+        self.view.assert_same_device(rc);
+        let clear_color = clear_color.into().unwrap_or(rc.clear_color());
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        {
+            let builder = RenderPassBuilder::new(view).clear_color(clear_color);
+            let mut pass = builder.begin(rc, &mut commands);
+            self.paint(&mut pass);
+        }
+        rc.commands.submit(std::iter::once(commands.finish()));
+        Ok(())
+    }
+
+    /// Acquires the next frame, draws into it, and presents it.
+    ///
+    /// If the surface is lost or outdated it's reconfigured and acquisition
+    /// is retried once before giving up. Does nothing while the context is
+    /// paused (see `Context::set_active`) or minimized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context has no surface (see `Context::from_raw`).
+    pub fn present(
+        &self,
+        rc: &mut Context,
+        clear_color: impl Into<Option<Color>>,
+    ) -> Result<(), SurfaceError> {
+        let Some(frame) = rc.acquire_frame()? else {
+            return Ok(());
+        };
+        let view = frame.texture.create_view(&TextureViewDescriptor::default());
+        self.draw(rc, &view, clear_color)?;
+        rc.present_frame(frame);
+        Ok(())
+    }
+
+    /// Records the draw into a render pass someone else began.
+    ///
+    /// All the sprites, whatever their texture, go out in one draw call.
+    pub fn paint<'a>(&'a self, pass: &mut RenderPass<'a>) {
+        if self.instance_count == 0 {
+            // Empty buffer slices aren't allowed.
+            return;
+        }
+        debug!("sprite count {}", self.instance_count);
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_bind_group(1, &self.view.bind_group, &[]);
+        pass.set_vertex_buffer(
+            0,
+            self.instances
+                .slice(..(std::mem::size_of::<Sprite>() * self.instance_count) as u64),
+        );
+        pass.draw(0..6, 0..self.instance_count as u32);
+    }
+}
+
+fn make_bind_group(
+    rc: &Context,
+    layout: &BindGroupLayout,
+    textures: &[&SpriteTexture],
+    placeholder: &SpriteTexture,
+    transform: &Buffer,
+    texture_params: &Buffer,
+) -> BindGroup {
+    let mut entries: Vec<_> = (0..MAX_TEXTURES)
+        .flat_map(|slot| {
+            let texture = textures.get(slot).copied().unwrap_or(placeholder);
+            [
+                BindGroupEntry {
+                    binding: 2 * slot as u32,
+                    resource: BindingResource::TextureView(&texture.view),
+                },
+                BindGroupEntry {
+                    binding: 2 * slot as u32 + 1,
+                    resource: BindingResource::Sampler(&texture.sampler),
+                },
+            ]
+        })
+        .collect();
+    entries.push(BindGroupEntry {
+        binding: TRANSFORM_BINDING,
+        resource: transform.as_entire_binding(),
+    });
+    entries.push(BindGroupEntry {
+        binding: TEXTURE_PARAMS_BINDING,
+        resource: texture_params.as_entire_binding(),
+    });
+    rc.device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Sprite painter bind group"),
+        layout,
+        entries: &entries,
+    })
+}
+
+fn make_pipeline(rc: &Context, layout: &BindGroupLayout) -> RenderPipeline {
+    let module = &rc.device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Sprite painter shader module"),
+        source: ShaderSource::Wgsl(SHADER_SOURCE.into()),
+    });
+
+    rc.device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Sprite Painter Render Pipeline"),
+        layout: Some(
+            &rc.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Sprite Painter Render Pipeline Layout"),
+                bind_group_layouts: &[layout, &rc.view.layout],
+                push_constant_ranges: &[],
+            }),
+        ),
+        vertex: VertexState {
+            module,
+            entry_point: "vs",
+            buffers: &[Sprite::layout()],
+        },
+        fragment: Some(FragmentState {
+            module,
+            entry_point: "fs",
+            targets: &[Some(ColorTargetState {
+                format: rc.config.format,
+                blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Back),
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: ignore_depth_stencil(rc),
+        multisample: MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::FilterMode;
+
+    use super::*;
+    use crate::test_util::{context, Target};
+
+    #[test]
+    fn alpha_cutoff_discards_the_fainter_half_of_a_gradient() {
+        let Some(rc) = context(32, 32) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        // White, fading in from transparent on the left, a texel a pixel.
+        let alphas: Vec<u8> = (0..32).map(|x| (x * 255 / 31) as u8).collect();
+        let rgba: Vec<u8> = alphas.iter().flat_map(|&a| [255, 255, 255, a]).collect();
+        let texture = rc.upload_texture(32, 1, &rgba).with_sampler(
+            &rc,
+            SamplerDesc {
+                mag_filter: FilterMode::Nearest,
+                min_filter: FilterMode::Nearest,
+                ..Default::default()
+            },
+        );
+        let mut painter = rc.make_sprite_painter();
+        painter.set_textures(&rc, &[&texture]);
+        painter
+            .set_sprites(&rc, &[Sprite::new([0.0, 0.0], [2.0, 2.0], 0)])
+            .unwrap();
+
+        for cutoff in [None, Some(0.5)] {
+            painter.set_alpha_cutoff(&rc, cutoff);
+            painter.draw(&rc, &target.view, Color::TRANSPARENT).unwrap();
+            let pixels = target.read(&rc);
+            for (x, &alpha) in alphas.iter().enumerate() {
+                let kept = cutoff.is_none_or(|c| alpha as f32 / 255.0 >= c);
+                let expected = if kept { alpha } else { 0 };
+                assert_eq!(pixels.at(x as u32, 16)[3], expected, "{x} with {cutoff:?}");
+            }
+        }
+    }
+}
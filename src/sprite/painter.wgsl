@@ -0,0 +1,136 @@
+// Texture slots. The count has to match `MAX_TEXTURES` in mod.rs.
+// Separate bindings, rather than a binding array, keep this portable to
+// backends without bindless support.
+@group(0) @binding(0) var t0: texture_2d<f32>;
+@group(0) @binding(1) var s0: sampler;
+@group(0) @binding(2) var t1: texture_2d<f32>;
+@group(0) @binding(3) var s1: sampler;
+@group(0) @binding(4) var t2: texture_2d<f32>;
+@group(0) @binding(5) var s2: sampler;
+@group(0) @binding(6) var t3: texture_2d<f32>;
+@group(0) @binding(7) var s3: sampler;
+@group(0) @binding(8) var t4: texture_2d<f32>;
+@group(0) @binding(9) var s4: sampler;
+@group(0) @binding(10) var t5: texture_2d<f32>;
+@group(0) @binding(11) var s5: sampler;
+@group(0) @binding(12) var t6: texture_2d<f32>;
+@group(0) @binding(13) var s6: sampler;
+@group(0) @binding(14) var t7: texture_2d<f32>;
+@group(0) @binding(15) var s7: sampler;
+
+// The painter's transform. See transform.rs.
+@group(0) @binding(16) var<uniform> transform: mat4x4<f32>;
+
+struct TextureParams {
+    // The sRGB color key in xyz and the tolerance in w. A negative
+    // tolerance turns keying off. See SpriteTexture::with_color_key.
+    color_key: vec4<f32>,
+    // 1 if the texture holds premultiplied alpha. See
+    // TextureOptions::premultiply.
+    premultiplied: u32,
+    // Fragments with less alpha are discarded, 0 when off. See
+    // Painter::set_alpha_cutoff.
+    alpha_cutoff: f32,
+}
+
+// Per slot, so the length matches MAX_TEXTURES too.
+@group(0) @binding(17) var<uniform> texture_params: array<TextureParams, 8>;
+
+struct View {
+    // Applied to clip space positions. See view.rs.
+    transform: mat4x4<f32>,
+}
+
+@group(1) @binding(0) var<uniform> view: View;
+
+struct InstanceInput {
+    @location(0) center: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    // (u0,v0,u1,v1): the region of the texture to draw
+    @location(2) uv_rect: vec4<f32>,
+    @location(3) orientation_radians: f32,
+    @location(4) texture_index: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) texture_index: u32,
+}
+
+@vertex
+fn vs(
+    @builtin(vertex_index) vertex_index: u32,
+    sprite: InstanceInput,
+) -> VertexOutput {
+    // Two counter-clockwise triangles covering a unit square centered at the
+    // origin.
+    var corners = array<vec2<f32>, 6>(
+        vec2(-0.5, -0.5),
+        vec2(0.5, -0.5),
+        vec2(0.5, 0.5),
+        vec2(-0.5, -0.5),
+        vec2(0.5, 0.5),
+        vec2(-0.5, 0.5),
+    );
+    let corner = corners[vertex_index];
+    let p = corner * sprite.size;
+    let c = cos(sprite.orientation_radians);
+    let s = sin(sprite.orientation_radians);
+
+    var out: VertexOutput;
+    out.position = view.transform * transform * vec4<f32>(
+        p.x * c - p.y * s + sprite.center.x,
+        p.x * s + p.y * c + sprite.center.y,
+        0.0,
+        1.0
+    );
+    // Clip space y points up, but texture v points down.
+    out.uv = mix(sprite.uv_rect.xy, sprite.uv_rect.zw, vec2(corner.x + 0.5, 0.5 - corner.y));
+    out.texture_index = sprite.texture_index;
+    return out;
+}
+
+@fragment
+fn fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The texture index varies per instance, so sampling happens in
+    // non-uniform control flow. Implicit derivatives aren't available there,
+    // so take them up front and sample with explicit gradients.
+    let ddx = dpdx(in.uv);
+    let ddy = dpdy(in.uv);
+
+    var color: vec4<f32>;
+    switch in.texture_index {
+        case 0u: { color = textureSampleGrad(t0, s0, in.uv, ddx, ddy); }
+        case 1u: { color = textureSampleGrad(t1, s1, in.uv, ddx, ddy); }
+        case 2u: { color = textureSampleGrad(t2, s2, in.uv, ddx, ddy); }
+        case 3u: { color = textureSampleGrad(t3, s3, in.uv, ddx, ddy); }
+        case 4u: { color = textureSampleGrad(t4, s4, in.uv, ddx, ddy); }
+        case 5u: { color = textureSampleGrad(t5, s5, in.uv, ddx, ddy); }
+        case 6u: { color = textureSampleGrad(t6, s6, in.uv, ddx, ddy); }
+        default: { color = textureSampleGrad(t7, s7, in.uv, ddx, ddy); }
+    }
+
+    let params = texture_params[min(in.texture_index, 7u)];
+    let key = params.color_key;
+    // Sampling decodes to linear, but keys are picked from the image's
+    // sRGB values.
+    if all(abs(linear_to_srgb(color.rgb) - key.xyz) <= vec3(key.w)) {
+        discard;
+    }
+
+    if color.a < params.alpha_cutoff {
+        discard;
+    }
+    // The blend expects premultiplied alpha.
+    if params.premultiplied != 0u {
+        return color;
+    }
+    return vec4(color.rgb * color.a, color.a);
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let low = 12.92 * c;
+    let high = 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3(0.0031308));
+}
@@ -0,0 +1,467 @@
+use log::debug;
+use wgpu::{
+    AddressMode, Extent3d, FilterMode, ImageCopyTexture, ImageDataLayout, Origin3d, Sampler,
+    SamplerDescriptor, Texture, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsages, TextureView, TextureViewDescriptor,
+};
+
+use super::mips;
+use crate::Context;
+
+/// Sprite textures hold 8-bit sRGB.
+pub(crate) const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// How `Context::upload_texture_with` prepares an image.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct TextureOptions {
+    /// Generate a full mip chain. See `Context::upload_texture_with_mips`.
+    pub mips: bool,
+    /// Multiply color by alpha on the CPU before uploading, so the texture
+    /// holds premultiplied alpha like the painters' output, and have
+    /// sprite painters sample it as is.
+    ///
+    /// Painters blend premultiplied color. A straight-alpha texture is
+    /// premultiplied per fragment, after filtering, and filtering mixes
+    /// the color of transparent texels into their opaque neighbours: those
+    /// usually hold black or whatever the image editor left behind, so the
+    /// edges of sprites pick up a dark or off-color fringe, worst when
+    /// they're scaled up or drawn between texels. Premultiplied texels
+    /// weigh their color by alpha, so transparent texels add nothing and
+    /// edges blend cleanly. Mips are built from the premultiplied image
+    /// too, which keeps them from fringing the same way.
+    ///
+    /// Color is multiplied in linear space, rounding to 8 bits, so very
+    /// transparent texels lose precision. Off by default.
+    pub premultiply: bool,
+}
+
+/// How a `SpriteTexture` is filtered and what happens outside its edges.
+///
+/// The default, linear filtering clamped to the edge, suits photographic
+/// content. Pixel art wants `Nearest` filters so texels stay sharp; tiled
+/// textures want `AddressMode::Repeat` together with a `uv_rect` reaching
+/// past `[0, 1]`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SamplerDesc {
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    /// How the two mip levels nearest the sprite's on-screen size are
+    /// combined. Only matters for textures uploaded with
+    /// `Context::upload_texture_with_mips`; others have a single level.
+    /// `Linear` avoids visible steps when a sprite is scaled smoothly.
+    pub mipmap_filter: FilterMode,
+    /// Used for both u and v.
+    pub address_mode: AddressMode,
+    /// The most texels along a sprite's footprint that are blended when
+    /// it's drawn squashed in one direction, as when rotated or scaled
+    /// unevenly. 1, the default, turns anisotropic filtering off; values
+    /// go up to 16 and larger ones are clamped.
+    ///
+    /// It only applies with all three filters `Linear`, and is ignored
+    /// otherwise. It refines mip selection, so it needs a texture uploaded
+    /// with `Context::upload_texture_with_mips` to make a difference.
+    /// Adapters without anisotropic filtering, such as some GL ones, treat
+    /// every value as 1.
+    pub anisotropy_clamp: u16,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            address_mode: AddressMode::ClampToEdge,
+            anisotropy_clamp: 1,
+        }
+    }
+}
+
+fn make_sampler(rc: &Context, desc: &SamplerDesc) -> Sampler {
+    // wgpu rejects anisotropy with any other filter.
+    let linear = [desc.mag_filter, desc.min_filter, desc.mipmap_filter]
+        .iter()
+        .all(|&filter| filter == FilterMode::Linear);
+    let anisotropy_clamp = if linear {
+        desc.anisotropy_clamp.clamp(1, 16)
+    } else {
+        if desc.anisotropy_clamp > 1 {
+            debug!("Ignoring anisotropy_clamp without Linear filters");
+        }
+        1
+    };
+    rc.device.create_sampler(&SamplerDescriptor {
+        label: Some("Sprite sampler"),
+        address_mode_u: desc.address_mode,
+        address_mode_v: desc.address_mode,
+        address_mode_w: desc.address_mode,
+        mag_filter: desc.mag_filter,
+        min_filter: desc.min_filter,
+        mipmap_filter: desc.mipmap_filter,
+        anisotropy_clamp,
+        ..Default::default()
+    })
+}
+
+/// An image on the GPU, ready to be bound to a sprite painter slot.
+pub struct SpriteTexture {
+    texture: Texture,
+    pub(crate) view: TextureView,
+    pub(crate) sampler: Sampler,
+    sampler_desc: SamplerDesc,
+    /// The key color and tolerance. See `with_color_key`.
+    pub(crate) color_key: Option<([f32; 3], f32)>,
+    /// See `TextureOptions::premultiply`.
+    pub(crate) premultiplied: bool,
+}
+
+impl SpriteTexture {
+    /// `rgba` is tightly packed 8-bit sRGB with straight alpha, row-major
+    /// starting from the top-left.
+    pub(crate) fn new(
+        rc: &Context,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+        options: &TextureOptions,
+    ) -> Self {
+        assert_eq!(
+            rgba.len(),
+            4 * width as usize * height as usize,
+            "Expected {width}x{height} rgba pixels"
+        );
+        let max = rc.max_texture_dimension();
+        assert!(
+            width <= max && height <= max,
+            "A {width}x{height} texture is over the device's max_texture_dimension_2d of {max}"
+        );
+        let premultiplied;
+        let rgba = if options.premultiply {
+            premultiplied = premultiply(rgba);
+            &premultiplied
+        } else {
+            rgba
+        };
+        let mips = options.mips;
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = rc.device.create_texture(&TextureDescriptor {
+            label: Some("Sprite texture"),
+            size,
+            mip_level_count: if mips {
+                mips::level_count(width, height)
+            } else {
+                1
+            },
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: FORMAT,
+            usage: if mips {
+                // Levels past the first are rendered, from copies of the
+                // level before.
+                TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_DST
+                    | TextureUsages::COPY_SRC
+                    | TextureUsages::RENDER_ATTACHMENT
+            } else {
+                TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST
+            },
+            view_formats: &[],
+        });
+        rc.commands.write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            rgba,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+        if mips {
+            rc.mips
+                .get_or_init(|| mips::Generator::new(rc))
+                .run(rc, &texture);
+        }
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler_desc = SamplerDesc::default();
+        Self {
+            texture,
+            view,
+            sampler: make_sampler(rc, &sampler_desc),
+            sampler_desc,
+            color_key: None,
+            premultiplied: options.premultiply,
+        }
+    }
+
+    /// A texture holding a video frame decoded to NV12, converted to RGB on
+    /// the GPU, so frames from a decoder can be drawn as sprites without
+    /// converting them on the CPU.
+    ///
+    /// NV12 is the layout hardware decoders and most software ones hand
+    /// out: `y_plane` is `width * height` bytes of luma, row by row, and
+    /// `uv_plane` holds interleaved Cb, Cr byte pairs at half the
+    /// resolution, rounded up, in both directions. Both are tightly
+    /// packed; copy out rows of decoders that pad their strides. Other
+    /// planar layouts, such as I420's separate U and V planes, can be
+    /// interleaved into NV12 first. `color_space` picks the conversion
+    /// matrix; see `YuvColorSpace`.
+    ///
+    /// The result is an ordinary opaque sprite texture. For playback, make
+    /// one per video and `update_yuv` it with each frame, so painters
+    /// don't need new bind groups.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a plane isn't the size above, or either side is over the
+    /// device's `max_texture_dimension_2d`.
+    #[cfg(feature = "video")]
+    pub fn from_yuv(
+        rc: &Context,
+        y_plane: &[u8],
+        uv_plane: &[u8],
+        width: u32,
+        height: u32,
+        color_space: super::YuvColorSpace,
+    ) -> Self {
+        let max = rc.max_texture_dimension();
+        assert!(
+            width <= max && height <= max,
+            "A {width}x{height} texture is over the device's max_texture_dimension_2d of {max}"
+        );
+        let texture = rc.device.create_texture(&TextureDescriptor {
+            label: Some("Sprite video texture"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: FORMAT,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler_desc = SamplerDesc::default();
+        let sprite = Self {
+            texture,
+            view,
+            sampler: make_sampler(rc, &sampler_desc),
+            sampler_desc,
+            color_key: None,
+            premultiplied: false,
+        };
+        sprite.update_yuv(rc, y_plane, uv_plane, color_space);
+        sprite
+    }
+
+    /// Replaces the contents of a texture made by `from_yuv` with another
+    /// frame of the same size. Painters the texture is bound to draw the
+    /// new frame without another `set_textures`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a plane isn't the size `from_yuv` describes for the
+    /// texture's size, or the texture wasn't made by `from_yuv`.
+    #[cfg(feature = "video")]
+    pub fn update_yuv(
+        &self,
+        rc: &Context,
+        y_plane: &[u8],
+        uv_plane: &[u8],
+        color_space: super::YuvColorSpace,
+    ) {
+        assert!(
+            self.texture
+                .usage()
+                .contains(TextureUsages::RENDER_ATTACHMENT)
+                && self.mip_level_count() == 1,
+            "Only textures made by from_yuv can be updated from YUV planes"
+        );
+        let (width, height) = (self.width(), self.height());
+        let (chroma_width, chroma_height) = super::yuv::chroma_size(width, height);
+        assert_eq!(
+            y_plane.len(),
+            width as usize * height as usize,
+            "Expected {width}x{height} luma samples"
+        );
+        assert_eq!(
+            uv_plane.len(),
+            2 * chroma_width as usize * chroma_height as usize,
+            "Expected {chroma_width}x{chroma_height} interleaved chroma pairs"
+        );
+        rc.yuv.get_or_init(|| super::yuv::Converter::new(rc)).run(
+            rc,
+            [y_plane, uv_plane],
+            width,
+            height,
+            color_space,
+            &self.view,
+        );
+    }
+
+    /// Makes texels of the color `key` transparent, for images without an
+    /// alpha channel like retro sprite sheets keyed on magenta
+    /// (`[1.0, 0.0, 1.0]`). Off by default.
+    ///
+    /// `key` is in the image's sRGB values scaled to `[0, 1]`. A texel is
+    /// dropped when every channel is within `tolerance` of it. The test
+    /// runs per fragment on the filtered color, so with linear filtering
+    /// the edges of keyed areas blend toward the key color and may be only
+    /// partly dropped; `Nearest` filtering (see `SamplerDesc`) keeps them
+    /// crisp. Painters pick up the key in `set_textures`.
+    pub fn with_color_key(mut self, key: [f32; 3], tolerance: f32) -> Self {
+        self.color_key = Some((key, tolerance));
+        self
+    }
+
+    /// Replaces the default sampler. See `SamplerDesc`.
+    pub fn with_sampler(mut self, rc: &Context, desc: SamplerDesc) -> Self {
+        self.set_sampler(rc, desc);
+        self
+    }
+
+    /// Changes how the texture is sampled.
+    ///
+    /// Painters the texture is already bound to keep the old sampler until
+    /// their next `set_textures`, which rebuilds their bind group.
+    pub fn set_sampler(&mut self, rc: &Context, desc: SamplerDesc) {
+        if desc != self.sampler_desc {
+            self.sampler = make_sampler(rc, &desc);
+            self.sampler_desc = desc;
+        }
+    }
+
+    pub fn sampler(&self) -> SamplerDesc {
+        self.sampler_desc
+    }
+
+    /// Whether the texture holds premultiplied alpha. See
+    /// `TextureOptions::premultiply`.
+    pub fn is_premultiplied(&self) -> bool {
+        self.premultiplied
+    }
+
+    pub fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.texture.height()
+    }
+
+    /// The number of mip levels, 1 unless uploaded with mips.
+    pub fn mip_level_count(&self) -> u32 {
+        self.texture.mip_level_count()
+    }
+
+    /// Size of the pixel data, including mip levels. An estimate; drivers
+    /// may pad or align it.
+    pub fn gpu_memory_bytes(&self) -> usize {
+        (0..self.mip_level_count())
+            .map(|level| {
+                let (w, h) = (self.width() >> level, self.height() >> level);
+                4 * w.max(1) as usize * h.max(1) as usize
+            })
+            .sum()
+    }
+}
+
+/// Multiplies sRGB color by alpha in linear space.
+fn premultiply(rgba: &[u8]) -> Vec<u8> {
+    let to_linear: [f32; 256] = std::array::from_fn(|i| {
+        let c = i as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    let to_srgb = |c: f32| {
+        let c = if c <= 0.0031308 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        (c * 255.0).round() as u8
+    };
+    rgba.chunks_exact(4)
+        .flat_map(|pixel| {
+            let alpha = pixel[3] as f32 / 255.0;
+            let [r, g, b] = [0, 1, 2].map(|i| to_srgb(to_linear[pixel[i] as usize] * alpha));
+            [r, g, b, pixel[3]]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::Color;
+
+    use super::*;
+    use crate::{
+        sprite::Sprite,
+        test_util::{context, Target},
+        ScreenshotOptions,
+    };
+
+    /// An sRGB orange at half alpha. Premultiplied in linear space, 255 is
+    /// 1.0 and 188 is 0.503, which halve to 0.502 and 0.252, or 188 and 138
+    /// in sRGB.
+    const PIXEL: [u8; 4] = [255, 188, 0, 128];
+    const PREMULTIPLIED: [u8; 4] = [188, 138, 0, 128];
+
+    #[test]
+    fn premultiply_known_pixel() {
+        assert_eq!(premultiply(&PIXEL), PREMULTIPLIED);
+    }
+
+    #[test]
+    fn uploads_premultiplied_only_when_asked() {
+        let Some(rc) = context(32, 32) else {
+            return;
+        };
+        let target = Target::new(&rc);
+        let mut drawn = Vec::new();
+        for (premultiply, stored) in [(true, PREMULTIPLIED), (false, PIXEL)] {
+            // Mips give the texture COPY_SRC, for reading it back; a 1x1
+            // texture has just the one level.
+            let options = TextureOptions {
+                mips: true,
+                premultiply,
+            };
+            let texture = rc.upload_texture_with(1, 1, &PIXEL, &options);
+            let read = ScreenshotOptions {
+                straight_alpha: false,
+                ..Default::default()
+            };
+            let shot = rc.read_pixels(&texture.texture, &read);
+            assert_eq!(shot.rgba, stored, "premultiply: {premultiply}");
+
+            let mut painter = rc.make_sprite_painter();
+            painter.set_textures(&rc, &[&texture]);
+            painter
+                .set_sprites(&rc, &[Sprite::new([0.0, 0.0], [2.0, 2.0], 0)])
+                .unwrap();
+            painter.draw(&rc, &target.view, Color::TRANSPARENT).unwrap();
+            drawn.push(target.read(&rc).at(16, 16));
+        }
+        // Either way, the painter blends the same premultiplied color, but
+        // for rounding on the CPU or the GPU.
+        for (a, b) in drawn[0].into_iter().zip(drawn[1]) {
+            assert!(a.abs_diff(b) <= 1, "{:?} and {:?}", drawn[0], drawn[1]);
+        }
+        assert_eq!(drawn[0][3], 128);
+    }
+}
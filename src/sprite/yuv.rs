@@ -0,0 +1,270 @@
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BufferBindingType, BufferUsages, Color,
+    ColorTargetState, ColorWrites, CommandEncoderDescriptor, Extent3d, FilterMode, FragmentState,
+    ImageDataLayout, LoadOp, MultisampleState, Operations, PipelineLayoutDescriptor,
+    PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, Texture, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+use super::texture::FORMAT;
+use crate::{util::as_raw_bytes, Context};
+
+/// Which matrix turns a video frame's Y'CbCr into R'G'B'.
+///
+/// Video usually says in its metadata: BT.709 for HD and most modern
+/// content, BT.601 for SD (up to 576 lines) and older captures. Picking
+/// the wrong one tints the image slightly, most visibly in skin tones and
+/// greens.
+///
+/// Both expect limited (studio) range, luma in 16..=235 and chroma in
+/// 16..=240, which is what video decoders output unless the stream is
+/// flagged full range.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum YuvColorSpace {
+    Bt601,
+    #[default]
+    Bt709,
+}
+
+#[repr(C, align(16))]
+struct Conversion {
+    matrix: [[f32; 4]; 3],
+    offset: [f32; 4],
+}
+
+impl YuvColorSpace {
+    fn conversion(self) -> Conversion {
+        // Red's weight from Cr, green's from Cb and Cr, blue's from Cb.
+        let (rv, gu, gv, bu) = match self {
+            YuvColorSpace::Bt601 => (1.402, -0.344136, -0.714136, 1.772),
+            YuvColorSpace::Bt709 => (1.5748, -0.187324, -0.468124, 1.8556),
+        };
+        // Stretches limited range out to [0, 1].
+        let (y, c) = (255.0 / 219.0, 255.0 / 224.0);
+        Conversion {
+            matrix: [
+                [y, y, y, 0.0],
+                [0.0, gu * c, bu * c, 0.0],
+                [rv * c, gv * c, 0.0, 0.0],
+            ],
+            offset: [16.0 / 255.0, 128.0 / 255.0, 128.0 / 255.0, 0.0],
+        }
+    }
+}
+
+/// The size of an NV12 frame's chroma plane, in samples.
+pub(crate) fn chroma_size(width: u32, height: u32) -> (u32, u32) {
+    (width.div_ceil(2), height.div_ceil(2))
+}
+
+/// Renders NV12 planes into sprite textures.
+pub(crate) struct Converter {
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    sampler: Sampler,
+}
+
+impl Converter {
+    pub(crate) fn new(rc: &Context) -> Self {
+        let texture = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let layout = rc
+            .device
+            .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("YUV converter bind group layout"),
+                entries: &[
+                    texture(0),
+                    texture(1),
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let module = &rc.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("YUV converter shader module"),
+            source: ShaderSource::Wgsl(include_str!("yuv.wgsl").into()),
+        });
+
+        let pipeline = rc.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("YUV converter pipeline"),
+            layout: Some(
+                &rc.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("YUV converter pipeline layout"),
+                    bind_group_layouts: &[&layout],
+                    push_constant_ranges: &[],
+                }),
+            ),
+            vertex: VertexState {
+                module,
+                entry_point: "vs",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module,
+                entry_point: "fs",
+                targets: &[Some(ColorTargetState {
+                    format: FORMAT,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        let sampler = rc.device.create_sampler(&SamplerDescriptor {
+            label: Some("YUV chroma sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline,
+            layout,
+            sampler,
+        }
+    }
+
+    /// Uploads the planes of a `width` by `height` NV12 frame and renders
+    /// them into `target`, which must be that size. The work is submitted
+    /// right away.
+    pub(crate) fn run(
+        &self,
+        rc: &Context,
+        [y_plane, uv_plane]: [&[u8]; 2],
+        width: u32,
+        height: u32,
+        color_space: YuvColorSpace,
+        target: &TextureView,
+    ) {
+        let (chroma_width, chroma_height) = chroma_size(width, height);
+        let y = upload_plane(rc, y_plane, width, height, TextureFormat::R8Unorm);
+        let uv = upload_plane(
+            rc,
+            uv_plane,
+            chroma_width,
+            chroma_height,
+            TextureFormat::Rg8Unorm,
+        );
+        let conversion = rc.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("YUV conversion uniforms"),
+            usage: BufferUsages::UNIFORM,
+            contents: unsafe { as_raw_bytes(&color_space.conversion()) },
+        });
+        let bind_group = rc.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("YUV converter bind group"),
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(
+                        &y.create_view(&TextureViewDescriptor::default()),
+                    ),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(
+                        &uv.create_view(&TextureViewDescriptor::default()),
+                    ),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: conversion.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut commands = rc
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        {
+            let mut pass = commands.begin_render_pass(&RenderPassDescriptor {
+                label: Some("YUV converter pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        rc.commands.submit(std::iter::once(commands.finish()));
+    }
+}
+
+/// A texture holding one plane, tightly packed.
+fn upload_plane(
+    rc: &Context,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+) -> Texture {
+    let texel = format.block_size(None).unwrap();
+    let size = Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = rc.device.create_texture(&TextureDescriptor {
+        label: Some("YUV plane"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    rc.commands.write_texture(
+        texture.as_image_copy(),
+        data,
+        ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(texel * width),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+    texture
+}
@@ -0,0 +1,53 @@
+// Luma, one texel per pixel.
+@group(0) @binding(0)
+var y_plane: texture_2d<f32>;
+
+// Interleaved Cb and Cr at half the resolution in both directions.
+@group(0) @binding(1)
+var uv_plane: texture_2d<f32>;
+
+@group(0) @binding(2)
+var chroma: sampler;
+
+struct Conversion {
+    // Columns of the Y'CbCr to R'G'B' matrix, after the offsets below.
+    matrix: mat3x3<f32>,
+    // Subtracted from (Y', Cb, Cr) first, in [0, 1] units.
+    offset: vec3<f32>,
+}
+
+@group(0) @binding(3)
+var<uniform> conversion: Conversion;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+}
+
+// One triangle covering the viewport.
+@vertex
+fn vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(2.0 * uv - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs(in: VertexOutput) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(in.position.xy);
+    let y = textureLoad(y_plane, p, 0).r;
+    // Chroma samples sit between each 2x2 block of luma (MPEG-1 siting),
+    // so filtering at the pixel center interpolates between them.
+    let uv = in.position.xy / vec2<f32>(textureDimensions(y_plane));
+    let cbcr = textureSample(uv_plane, chroma, uv).rg;
+    let rgb = saturate(conversion.matrix * (vec3(y, cbcr) - conversion.offset));
+    // The result is gamma encoded, like the sRGB bytes sprite textures
+    // hold, and the target encodes what's written, so decode it first.
+    return vec4(srgb_to_linear(rgb), 1.0);
+}
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3(2.4));
+    return select(high, low, c <= vec3(0.04045));
+}
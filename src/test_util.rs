@@ -0,0 +1,102 @@
+//! Shared setup for the GPU tests, on a software rasterizer.
+
+use wgpu::{
+    Extent3d, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    TextureView, TextureViewDescriptor,
+};
+
+use crate::{Context, ScreenshotOptions};
+
+/// A headless software context, or `None` where there's no software adapter,
+/// in which case the calling test should return early.
+pub(crate) fn context(width: u32, height: u32) -> Option<Context> {
+    match async_std::task::block_on(Context::headless_software(width, height)) {
+        Ok(rc) => Some(rc),
+        Err(e) => {
+            eprintln!("Skipping a GPU test: {e}");
+            None
+        }
+    }
+}
+
+/// Like `context`, as if built `with_stencil(true)`.
+pub(crate) fn stencil_context(width: u32, height: u32) -> Option<Context> {
+    let mut rc = context(width, height)?;
+    rc.stencil = Some(crate::make_stencil(&rc.device, width, height));
+    Some(rc)
+}
+
+/// An 8-bit format, like the one `rc` has but with the other encoding, to
+/// switch to with `set_format`.
+pub(crate) fn other_format(rc: &Context) -> TextureFormat {
+    match rc.format() {
+        TextureFormat::Rgba8UnormSrgb => TextureFormat::Rgba8Unorm,
+        TextureFormat::Bgra8UnormSrgb => TextureFormat::Bgra8Unorm,
+        TextureFormat::Bgra8Unorm => TextureFormat::Bgra8UnormSrgb,
+        _ => TextureFormat::Rgba8UnormSrgb,
+    }
+}
+
+/// What a linear channel `value` is stored as in `format`, out of 255.
+pub(crate) fn stored(format: TextureFormat, value: f32) -> f32 {
+    let value = if format.is_srgb() {
+        crate::screenshot::linear_to_srgb(value)
+    } else {
+        value
+    };
+    255.0 * value
+}
+
+/// A texture of the context's size and format to draw into and read back.
+pub(crate) struct Target {
+    pub(crate) texture: Texture,
+    pub(crate) view: TextureView,
+}
+
+impl Target {
+    pub(crate) fn new(rc: &Context) -> Self {
+        let texture = rc.device.create_texture(&TextureDescriptor {
+            label: Some("Test target"),
+            size: Extent3d {
+                width: rc.config.width,
+                height: rc.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: rc.format(),
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::COPY_SRC
+                | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        Self { texture, view }
+    }
+
+    /// The target's pixels, as they're stored.
+    pub(crate) fn read(&self, rc: &Context) -> Pixels {
+        let options = ScreenshotOptions {
+            straight_alpha: false,
+            ..Default::default()
+        };
+        let shot = rc.read_pixels(&self.texture, &options);
+        Pixels {
+            width: shot.width,
+            rgba: shot.rgba,
+        }
+    }
+}
+
+pub(crate) struct Pixels {
+    width: u32,
+    pub(crate) rgba: Vec<u8>,
+}
+
+impl Pixels {
+    pub(crate) fn at(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = 4 * (y * self.width + x) as usize;
+        self.rgba[i..i + 4].try_into().unwrap()
+    }
+}
@@ -0,0 +1,62 @@
+use std::time::{Duration, Instant};
+
+/// Where the CPU spent the last presented frame. See
+/// `Context::last_frame_latency`.
+///
+/// These are wall times measured around wgpu calls, not when the image
+/// reached the display: wgpu doesn't report presentation timestamps on any
+/// backend. What they show still depends on the backend and present mode:
+///
+/// - Vulkan and DX12 usually block in acquisition when the swapchain is
+///   full, so with `Fifo` the wait for vsync shows up in `acquire_time`
+///   and `present_time` stays small.
+/// - Metal blocks in acquisition when all its drawables are in use, and
+///   presents after the GPU finishes, without blocking.
+/// - GL often blocks in the buffer swap behind `present_time`, and some
+///   drivers defer the wait to the next frame's first draw instead, where
+///   it lands in `cpu_time`.
+///
+/// For the GPU's side of the frame, time the host's passes with timestamp
+/// queries (`wgpu::Features::TIMESTAMP_QUERY`) where the adapter has them.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FrameLatency {
+    /// Spent waiting for the surface to hand out the frame.
+    pub acquire_time: Duration,
+    /// From acquiring the frame to presenting it: drawing and encoding.
+    pub cpu_time: Duration,
+    /// Spent in `SurfaceTexture::present`.
+    pub present_time: Duration,
+}
+
+/// Timestamps of the frame in flight.
+#[derive(Default)]
+pub(crate) struct FrameTimer {
+    /// When the frame in flight was acquired, and how long that took.
+    acquired: Option<(Instant, Duration)>,
+    last: Option<FrameLatency>,
+}
+
+impl FrameTimer {
+    /// `start` is when acquisition began.
+    pub(crate) fn acquired(&mut self, start: Instant) {
+        let now = Instant::now();
+        self.acquired = Some((now, now - start));
+    }
+
+    /// `start` is when presenting began. Frames that weren't acquired
+    /// through the context aren't recorded.
+    pub(crate) fn presented(&mut self, start: Instant) {
+        let now = Instant::now();
+        if let Some((acquired_at, acquire_time)) = self.acquired.take() {
+            self.last = Some(FrameLatency {
+                acquire_time,
+                cpu_time: start - acquired_at,
+                present_time: now - start,
+            });
+        }
+    }
+
+    pub(crate) fn last(&self) -> Option<FrameLatency> {
+        self.last
+    }
+}
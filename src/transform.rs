@@ -0,0 +1,120 @@
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    Buffer, BufferUsages,
+};
+
+use crate::{util::as_raw_bytes, Context};
+
+/// A 2D affine transform applied to a painter's geometry.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform2D {
+    /// Column-major: the images of the x and y axes, then the translation.
+    cols: [[f32; 2]; 3],
+}
+
+impl Default for Transform2D {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform2D {
+    pub const IDENTITY: Self = Self {
+        cols: [[1.0, 0.0], [0.0, 1.0], [0.0, 0.0]],
+    };
+
+    pub fn from_cols(x_axis: [f32; 2], y_axis: [f32; 2], translation: [f32; 2]) -> Self {
+        Self {
+            cols: [x_axis, y_axis, translation],
+        }
+    }
+
+    pub fn scale(sx: f32, sy: f32) -> Self {
+        Self::from_cols([sx, 0.0], [0.0, sy], [0.0, 0.0])
+    }
+
+    pub fn translate(tx: f32, ty: f32) -> Self {
+        Self::from_cols([1.0, 0.0], [0.0, 1.0], [tx, ty])
+    }
+
+    /// Counter-clockwise rotation about the origin.
+    pub fn rotate(radians: f32) -> Self {
+        let (s, c) = radians.sin_cos();
+        Self::from_cols([c, s], [-s, c], [0.0, 0.0])
+    }
+
+    /// Undoes the stretching clip space gets on a surface that isn't
+    /// square, so squares stay square.
+    ///
+    /// `aspect` is width over height, as from `Context::aspect_ratio`. The
+    /// vertical axis is preserved: y still spans `[-1, 1]` and x is scaled
+    /// by `1 / aspect`. On a wide surface that leaves margins left and
+    /// right; on a tall one, content near `x = ±1` ends up off screen.
+    pub fn aspect_correct(aspect: f32) -> Self {
+        Self::scale(1.0 / aspect, 1.0)
+    }
+
+    /// Applies `self`, then `next`.
+    pub fn then(&self, next: &Transform2D) -> Transform2D {
+        let [x, y, t] = self.cols;
+        Self {
+            cols: [next.apply_vector(x), next.apply_vector(y), next.apply(t)],
+        }
+    }
+
+    pub fn apply(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        let [tx, ty] = self.cols[2];
+        let [vx, vy] = self.apply_vector([x, y]);
+        [vx + tx, vy + ty]
+    }
+
+    /// Applies the linear part only, ignoring translation.
+    pub fn apply_vector(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        let [[ax, ay], [bx, by], _] = self.cols;
+        [ax * x + bx * y, ay * x + by * y]
+    }
+
+    /// Column-major 4x4 matrix, as uploaded to shaders.
+    pub fn to_mat4(&self) -> [[f32; 4]; 4] {
+        let [[ax, ay], [bx, by], [tx, ty]] = self.cols;
+        [
+            [ax, ay, 0.0, 0.0],
+            [bx, by, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [tx, ty, 0.0, 1.0],
+        ]
+    }
+}
+
+/// A uniform buffer holding `transform` as a `mat4x4<f32>`.
+pub(crate) fn make_buffer(rc: &Context, transform: &Transform2D) -> Buffer {
+    rc.device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Painter transform"),
+        contents: unsafe { as_raw_bytes(&transform.to_mat4()) },
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    })
+}
+
+pub(crate) fn write_buffer(rc: &Context, buffer: &Buffer, transform: &Transform2D) {
+    rc.commands
+        .write_buffer(buffer, 0, unsafe { as_raw_bytes(&transform.to_mat4()) });
+}
+
+/// Keeps the part of `m` that acts on x and y.
+#[cfg(feature = "glam")]
+impl From<glam::Mat4> for Transform2D {
+    fn from(m: glam::Mat4) -> Self {
+        Self::from_cols(
+            m.x_axis.truncate().truncate().into(),
+            m.y_axis.truncate().truncate().into(),
+            m.w_axis.truncate().truncate().into(),
+        )
+    }
+}
+
+#[cfg(feature = "glam")]
+impl From<Transform2D> for glam::Mat4 {
+    fn from(t: Transform2D) -> Self {
+        glam::Mat4::from_cols_array_2d(&t.to_mat4())
+    }
+}
@@ -0,0 +1,79 @@
+use std::{num::NonZeroU64, sync::Mutex};
+
+use wgpu::{util::StagingBelt, Buffer, CommandEncoderDescriptor};
+
+use crate::Context;
+
+/// How a painter copies geometry to the GPU.
+///
+/// Which is faster depends on the backend and driver, so it's worth
+/// measuring both with representative geometry.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum UploadStrategy {
+    /// `Queue::write_buffer`. wgpu copies the data into staging memory of
+    /// its own and runs the copy with the next submission. Usually best
+    /// for small or occasional uploads.
+    #[default]
+    WriteBuffer,
+    /// Writes straight into mapped buffers from a `wgpu::util::StagingBelt`
+    /// that's kept for reuse, and submits the copy right away. Can win for
+    /// large uploads every frame by saving an extra copy and allocation,
+    /// but the belt's memory stays allocated between uploads. It isn't
+    /// counted by `gpu_memory_bytes`.
+    StagingBelt,
+}
+
+/// Size of the staging belt's chunks. Bigger uploads get a chunk of
+/// their own.
+const CHUNK_SIZE: u64 = 1 << 20;
+
+pub(crate) enum Uploader {
+    Queue,
+    /// The belt isn't `Sync`. The lock keeps painters shareable; it's
+    /// never contended since writes need `&mut self` anyway.
+    Belt(Mutex<StagingBelt>),
+}
+
+impl Uploader {
+    pub(crate) fn new(strategy: UploadStrategy) -> Self {
+        match strategy {
+            UploadStrategy::WriteBuffer => Self::Queue,
+            UploadStrategy::StagingBelt => Self::Belt(Mutex::new(StagingBelt::new(CHUNK_SIZE))),
+        }
+    }
+
+    pub(crate) fn strategy(&self) -> UploadStrategy {
+        match self {
+            Self::Queue => UploadStrategy::WriteBuffer,
+            Self::Belt(_) => UploadStrategy::StagingBelt,
+        }
+    }
+
+    /// Copies each slice into its buffer at the given byte offset.
+    pub(crate) fn write(&mut self, rc: &Context, writes: &[(&Buffer, u64, &[u8])]) {
+        match self {
+            Self::Queue => {
+                for &(buffer, offset, data) in writes {
+                    rc.commands.write_buffer(buffer, offset, data);
+                }
+            }
+            Self::Belt(belt) => {
+                let belt = belt.get_mut().unwrap();
+                let mut commands = rc.device.create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some("Staging belt upload"),
+                });
+                for &(buffer, offset, data) in writes {
+                    // Zero sized copies aren't allowed.
+                    let Some(size) = NonZeroU64::new(data.len() as u64) else {
+                        continue;
+                    };
+                    belt.write_buffer(&mut commands, buffer, offset, size, &rc.device)
+                        .copy_from_slice(data);
+                }
+                belt.finish();
+                rc.commands.submit(std::iter::once(commands.finish()));
+                belt.recall();
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use log::debug;
+use wgpu::{Buffer, BufferDescriptor, BufferUsages, CommandEncoder, COPY_BUFFER_ALIGNMENT};
+
+use crate::{Context, GeometryError};
+
+pub(crate) unsafe fn as_u8_slice<T>(x: &[T]) -> &[u8] {
+    std::slice::from_raw_parts(x.as_ptr() as *const u8, std::mem::size_of_val(x))
+}
+
+pub(crate) unsafe fn as_raw_bytes<T>(x: &T) -> &[u8] {
+    std::slice::from_raw_parts(x as *const T as *const u8, std::mem::size_of::<T>())
+}
+
+/// Reallocates `buffer` if it's smaller than `size` bytes.
+///
+/// Buffers only grow. The old contents are not preserved.
+pub(crate) fn grow(
+    rc: &Context,
+    buffer: &mut Buffer,
+    size: usize,
+    label: &'static str,
+    usage: BufferUsages,
+) -> Result<(), GeometryError> {
+    let size = size as u64;
+    let max = rc.limits().max_buffer_size;
+    if size > max {
+        return Err(GeometryError::TooLarge {
+            buffer: label,
+            requested: size,
+            max,
+        });
+    }
+    if buffer.size() < size {
+        debug!("Growing {label} from {} to {size} bytes", buffer.size());
+        *buffer = rc.device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            size: size.next_power_of_two().min(max),
+            usage,
+            mapped_at_creation: false,
+        });
+    }
+    Ok(())
+}
+
+/// Copies the first `size` bytes of `buffer` back to the CPU, waiting for
+/// the GPU to finish everything submitted so far.
+///
+/// The buffer needs `COPY_SRC` usage.
+#[cfg(any(test, feature = "debug"))]
+pub(crate) fn read_buffer(rc: &Context, buffer: &Buffer, size: usize) -> Vec<u8> {
+    let size = (size as u64).next_multiple_of(COPY_BUFFER_ALIGNMENT);
+    if size == 0 {
+        return Vec::new();
+    }
+    let staging = rc.device.create_buffer(&BufferDescriptor {
+        label: Some("Debug readback buffer"),
+        size,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut commands = rc
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    commands.copy_buffer_to_buffer(buffer, 0, &staging, 0, size);
+    rc.commands.submit(std::iter::once(commands.finish()));
+
+    let slice = staging.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |result| {
+        result.expect("Failed to map the readback buffer");
+    });
+    rc.device.poll(wgpu::Maintain::Wait);
+    let bytes = slice.get_mapped_range().to_vec();
+    staging.unmap();
+    bytes
+}
+
+/// Reallocates `buffer` to hold just its first `used` bytes, which are
+/// copied over by `commands`. Does nothing if it's no bigger than that.
+///
+/// The buffer needs `COPY_SRC` usage.
+pub(crate) fn shrink(
+    rc: &Context,
+    commands: &mut CommandEncoder,
+    buffer: &mut Buffer,
+    used: usize,
+    label: &'static str,
+) {
+    let size = (used as u64)
+        .next_multiple_of(COPY_BUFFER_ALIGNMENT)
+        .max(COPY_BUFFER_ALIGNMENT);
+    if buffer.size() <= size {
+        return;
+    }
+    debug!("Shrinking {label} from {} to {size} bytes", buffer.size());
+    let shrunk = rc.device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        size,
+        usage: buffer.usage(),
+        mapped_at_creation: false,
+    });
+    commands.copy_buffer_to_buffer(buffer, 0, &shrunk, 0, size);
+    *buffer = shrunk;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::context;
+
+    #[test]
+    fn grow_past_max_buffer_size_fails() {
+        let Some(mut rc) = context(4, 4) else {
+            return;
+        };
+        let max = 1 << 12;
+        rc.limits.max_buffer_size = max;
+        let usage = BufferUsages::VERTEX | BufferUsages::COPY_DST;
+        let mut buffer = rc.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: 16,
+            usage,
+            mapped_at_creation: false,
+        });
+        // Grown to the next power of two, but never past the limit.
+        grow(&rc, &mut buffer, max as usize - 4, "Test buffer", usage).unwrap();
+        assert_eq!(buffer.size(), max);
+        grow(&rc, &mut buffer, max as usize, "Test buffer", usage).unwrap();
+        match grow(&rc, &mut buffer, max as usize + 1, "Test buffer", usage) {
+            Err(e @ GeometryError::TooLarge { .. }) => {
+                assert!(matches!(
+                    e,
+                    GeometryError::TooLarge {
+                        buffer: "Test buffer",
+                        requested,
+                        max: m,
+                    } if requested == max + 1 && m == max
+                ));
+                assert!(e.to_string().contains("max_buffer_size"));
+            }
+            other => panic!("Grew past the limit: {other:?}"),
+        }
+        // The buffer is left as it was.
+        assert_eq!(buffer.size(), max);
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::{Arc, Weak};
+
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferUsages,
+    Device, Queue, ShaderStages,
+};
+
+use crate::{util::as_raw_bytes, Context, Transform2D};
+
+/// Rotation applied to everything drawn, so content follows a display that
+/// has been rotated.
+///
+/// Each variant rotates content counter-clockwise on the surface by that
+/// many degrees about the center of clip space.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum SurfaceRotation {
+    #[default]
+    Rotate0,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl SurfaceRotation {
+    /// Column-major rotation of clip space.
+    fn matrix(&self) -> [[f32; 4]; 4] {
+        let (s, c) = match self {
+            SurfaceRotation::Rotate0 => (0.0, 1.0),
+            SurfaceRotation::Rotate90 => (1.0, 0.0),
+            SurfaceRotation::Rotate180 => (0.0, -1.0),
+            SurfaceRotation::Rotate270 => (-1.0, 0.0),
+        };
+        [
+            [c, s, 0.0, 0.0],
+            [-s, c, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]
+    }
+}
+
+#[repr(C, align(16))]
+struct ViewUniforms {
+    transform: [[f32; 4]; 4],
+}
+
+/// Context-wide uniforms every painter binds as group 1.
+pub(crate) struct View {
+    /// The context's device, to tell painters drawn with another context.
+    /// Weak so painters don't keep it alive; the allocation stays, so the
+    /// address isn't reused while a painter can compare against it.
+    device: Weak<Device>,
+    pub(crate) uniforms: Buffer,
+    pub(crate) layout: BindGroupLayout,
+    pub(crate) bind_group: BindGroup,
+}
+
+impl View {
+    pub(crate) fn new(device: &Arc<Device>, queue: &Queue) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("View bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let uniforms = device.create_buffer(&BufferDescriptor {
+            label: Some("View uniforms"),
+            size: std::mem::size_of::<ViewUniforms>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("View bind group"),
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: uniforms.as_entire_binding(),
+            }],
+        });
+        let view = Self {
+            device: Arc::downgrade(device),
+            uniforms,
+            layout,
+            bind_group,
+        };
+        view.write(queue, SurfaceRotation::default(), &Transform2D::IDENTITY);
+        view
+    }
+
+    /// Panics with a clear message, rather than a wgpu validation error,
+    /// if a painter holding this view is drawn with a context on another
+    /// device.
+    pub(crate) fn assert_same_device(&self, rc: &Context) {
+        assert!(
+            self.device.as_ptr() == Arc::as_ptr(&rc.device),
+            "The painter was made with a different Context, on another device. Painters can \
+             only draw with contexts sharing their device, and have to be made again after \
+             switch_adapter"
+        );
+    }
+
+    pub(crate) fn gpu_memory_bytes(&self) -> usize {
+        self.uniforms.size() as usize
+    }
+
+    /// `camera` is applied first, then `rotation`.
+    pub(crate) fn write(&self, queue: &Queue, rotation: SurfaceRotation, camera: &Transform2D) {
+        let uniforms = ViewUniforms {
+            transform: mul(&rotation.matrix(), &camera.to_mat4()),
+        };
+        queue.write_buffer(&self.uniforms, 0, unsafe { as_raw_bytes(&uniforms) });
+    }
+}
+
+/// `a * b` for column-major matrices.
+fn mul(a: &[[f32; 4]; 4], b: &[[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    std::array::from_fn(|col| {
+        std::array::from_fn(|row| (0..4).map(|k| a[k][row] * b[col][k]).sum())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use wgpu::{Color, DeviceType, Instance, TextureFormat};
+
+    use crate::{rect::Rect, test_util::Target, Context};
+
+    /// Two contexts on devices of their own, from the same adapter, since GL
+    /// fails to open a second display while one's open.
+    fn two_contexts() -> Option<[Context; 2]> {
+        let instance = Instance::default();
+        let adapter = instance
+            .enumerate_adapters(wgpu::Backends::all())
+            .find(|adapter| adapter.get_info().device_type == DeviceType::Cpu)?;
+        let context = || {
+            let (device, queue) =
+                async_std::task::block_on(adapter.request_device(&Default::default(), None))
+                    .ok()?;
+            let mut rc =
+                Context::from_raw(Arc::new(device), Arc::new(queue), TextureFormat::Rgba8Unorm);
+            rc.resize(8, 8);
+            Some(rc)
+        };
+        Some([context()?, context()?])
+    }
+
+    #[test]
+    fn drawing_with_another_device_panics() {
+        let Some([first, second]) = two_contexts() else {
+            eprintln!("Skipping a GPU test: no software adapter");
+            return;
+        };
+        let mut painter = first.make_rect_painter();
+        painter
+            .set_rects(&first, &[Rect::new([0.0, 0.0], [1.0, 1.0], 0.0)])
+            .unwrap();
+        let target = Target::new(&second);
+        let drawn = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            painter.draw(&second, &target.view, Color::BLACK)
+        }));
+        let message = *drawn.unwrap_err().downcast::<&str>().unwrap();
+        assert!(message.contains("different Context"), "{message}");
+    }
+}